@@ -1,3 +1,45 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
 fn main() {
+    generate_migrations();
     tauri_build::build()
 }
+
+/// Writes `$OUT_DIR/migrations.rs`: every `<version>_<description>.sql` in
+/// `migrations/`, in version order, for `src/migrations.rs` to include.
+fn generate_migrations() {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("migrations");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut migrations: Vec<(i64, String, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(&dir).expect("failed to read the migrations directory") {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "sql") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let version = stem
+            .split_once('_')
+            .and_then(|(version, _)| version.parse().ok())
+            .unwrap_or_else(|| {
+                panic!("migration {stem}.sql is not named <version>_<description>.sql")
+            });
+        let description = stem.split_once('_').unwrap().1.replace('_', " ");
+        migrations.push((version, description, path));
+    }
+    migrations.sort_by_key(|(version, ..)| *version);
+
+    let mut out = String::from("&[\n");
+    for (version, description, path) in &migrations {
+        out += &format!(
+            "    SqlMigration {{ version: {version}, description: {description:?}, sql: include_str!({path:?}) }},\n"
+        );
+    }
+    out += "]\n";
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("migrations.rs"),
+        out,
+    )
+    .unwrap();
+}
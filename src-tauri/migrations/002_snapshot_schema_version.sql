@@ -0,0 +1,3 @@
+-- Tag snapshots with the GameState schema they were taken under, so a
+-- snapshot from an older build is rebuilt from events instead of loaded.
+ALTER TABLE snapshots ADD COLUMN schema_version INTEGER NOT NULL DEFAULT 1;
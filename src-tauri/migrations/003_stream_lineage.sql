@@ -0,0 +1,9 @@
+-- Parent linkage for streams created by forking another stream's prefix
+CREATE TABLE stream_lineage (
+  stream_id TEXT PRIMARY KEY,
+  parent_stream_id TEXT NOT NULL,
+  forked_at_sequence INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_stream_lineage_parent ON stream_lineage(parent_stream_id);
@@ -0,0 +1,4 @@
+-- Tamper-evident hash chain over each stream. Rows written before the chain
+-- existed keep NULL hashes.
+ALTER TABLE events ADD COLUMN prev_hash TEXT;
+ALTER TABLE events ADD COLUMN hash TEXT;
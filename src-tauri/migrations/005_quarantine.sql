@@ -0,0 +1,9 @@
+-- Rows moved aside by the integrity checker's repair mode, kept for inspection
+CREATE TABLE quarantine (
+  quarantine_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  source_table TEXT NOT NULL,
+  row_key TEXT NOT NULL,
+  row_data TEXT NOT NULL,
+  problem TEXT NOT NULL,
+  quarantined_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,21 @@
+-- Per-profile settings; the profile id is the player id of its streams
+CREATE TABLE profile_settings (
+  profile_id TEXT PRIMARY KEY,
+  ironman INTEGER NOT NULL DEFAULT 0
+);
+
+-- Events removed by undo, kept as they were stored
+CREATE TABLE undone_events (
+  event_id TEXT PRIMARY KEY,
+  stream_id TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  event_data TEXT NOT NULL,
+  metadata TEXT,
+  sequence INTEGER NOT NULL,
+  created_at TEXT NOT NULL,
+  prev_hash TEXT,
+  hash TEXT,
+  undone_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_undone_events_stream ON undone_events(stream_id, sequence);
@@ -0,0 +1,30 @@
+-- Player profiles. A profile's id is the player id of its main stream, and it
+-- owns every stream forked from there.
+CREATE TABLE profiles (
+  profile_id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  selected INTEGER NOT NULL DEFAULT 0,
+  created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE UNIQUE INDEX idx_profiles_selected ON profiles(selected) WHERE selected = 1;
+
+-- Existing data belongs to the player id the webview always used
+INSERT INTO profiles (profile_id, name, selected) VALUES ('1', 'Player 1', 1);
+
+-- Save names are unique per profile rather than per install
+CREATE TABLE save_games_by_profile (
+  profile_id TEXT NOT NULL DEFAULT '1',
+  save_name TEXT NOT NULL,
+  player_id TEXT NOT NULL,
+  last_event_id TEXT NOT NULL,
+  preview_data TEXT NOT NULL,
+  saved_at TEXT NOT NULL DEFAULT (datetime('now')),
+  PRIMARY KEY (profile_id, save_name)
+);
+
+INSERT INTO save_games_by_profile (save_name, player_id, last_event_id, preview_data, saved_at)
+SELECT save_name, player_id, last_event_id, preview_data, saved_at FROM save_games;
+
+DROP TABLE save_games;
+ALTER TABLE save_games_by_profile RENAME TO save_games;
@@ -0,0 +1,7 @@
+-- App-wide backup settings, kept in a single row
+CREATE TABLE backup_settings (
+  id INTEGER PRIMARY KEY CHECK (id = 1),
+  retention INTEGER NOT NULL DEFAULT 10
+);
+
+INSERT INTO backup_settings (id) VALUES (1);
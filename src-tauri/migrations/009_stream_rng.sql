@@ -0,0 +1,9 @@
+-- Seeded random generator of each stream, with draws not yet recorded in an
+-- event's metadata
+CREATE TABLE stream_rng (
+  stream_id TEXT PRIMARY KEY,
+  seed INTEGER NOT NULL,
+  state INTEGER NOT NULL,
+  draws INTEGER NOT NULL DEFAULT 0,
+  pending TEXT NOT NULL DEFAULT '[]'
+);
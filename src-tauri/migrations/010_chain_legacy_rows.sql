@@ -0,0 +1,15 @@
+-- Rows written before the hash chain existed, per stream: only rows up to
+-- `last_sequence` may lack a hash. Later unhashed rows break the chain.
+CREATE TABLE chain_legacy (
+  stream_id TEXT PRIMARY KEY,
+  last_sequence INTEGER NOT NULL
+);
+
+INSERT INTO chain_legacy (stream_id, last_sequence)
+SELECT stream_id, MAX(sequence) FROM events e
+WHERE hash IS NULL
+  AND NOT EXISTS (
+    SELECT 1 FROM events h
+    WHERE h.stream_id = e.stream_id AND h.hash IS NOT NULL AND h.sequence < e.sequence
+  )
+GROUP BY stream_id;
@@ -0,0 +1,38 @@
+-- The profile owning each stream. Streams used to belong to the profile
+-- named by their root's player id; a root whose player id is not a profile
+-- (a game the webview started under a timestamp id) goes to the selected
+-- profile.
+CREATE TABLE stream_profiles (
+  stream_id TEXT PRIMARY KEY,
+  profile_id TEXT NOT NULL
+);
+
+CREATE INDEX idx_stream_profiles_profile ON stream_profiles(profile_id);
+
+INSERT INTO stream_profiles (stream_id, profile_id)
+WITH RECURSIVE roots(stream_id, root) AS (
+  SELECT stream_id, stream_id FROM (
+    SELECT stream_id FROM events UNION SELECT stream_id FROM stream_lineage
+  )
+  UNION
+  SELECT r.stream_id, l.parent_stream_id FROM roots r
+  JOIN stream_lineage l ON l.stream_id = r.root
+)
+SELECT r.stream_id, COALESCE(
+  p.profile_id,
+  (SELECT profile_id FROM profiles WHERE selected = 1),
+  CASE WHEN r.root LIKE 'player-%' THEN substr(r.root, 8) ELSE r.root END
+)
+FROM roots r
+LEFT JOIN profiles p ON 'player-' || p.profile_id = r.root
+WHERE NOT EXISTS (SELECT 1 FROM stream_lineage l WHERE l.stream_id = r.root);
+
+-- Saves filed under a player id that is not a profile follow their stream.
+UPDATE OR IGNORE save_games SET profile_id = (
+  SELECT s.profile_id FROM stream_profiles s
+  WHERE s.stream_id = 'player-' || save_games.player_id
+)
+WHERE profile_id NOT IN (SELECT profile_id FROM profiles)
+  AND EXISTS (
+    SELECT 1 FROM stream_profiles s WHERE s.stream_id = 'player-' || save_games.player_id
+  );
@@ -0,0 +1,4 @@
+-- Looks up the events of a tactical battle by the `battleId` in their data.
+-- Rows that are not valid JSON are left out, so they cannot fail the index.
+CREATE INDEX idx_events_battle ON events(json_extract(event_data, '$.battleId'))
+WHERE json_valid(event_data);
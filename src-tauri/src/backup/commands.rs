@@ -0,0 +1,29 @@
+//! Tauri commands exposing the [`BackupService`] to the webview.
+
+use tauri::State;
+
+use super::{BackupInfo, BackupService, BackupSettings};
+use crate::error::Result;
+
+#[tauri::command]
+pub async fn list_backups(backups: State<'_, BackupService>) -> Result<Vec<BackupInfo>> {
+    backups.list_backups().await
+}
+
+#[tauri::command]
+pub async fn restore_backup(backups: State<'_, BackupService>, file_name: String) -> Result<()> {
+    backups.restore_backup(&file_name).await
+}
+
+#[tauri::command]
+pub async fn backup_settings(backups: State<'_, BackupService>) -> Result<BackupSettings> {
+    backups.settings().await
+}
+
+#[tauri::command]
+pub async fn update_backup_settings(
+    backups: State<'_, BackupService>,
+    settings: BackupSettings,
+) -> Result<()> {
+    backups.update_settings(&settings).await
+}
@@ -0,0 +1,540 @@
+//! Rotating backups of `game.db`.
+//!
+//! Backups are written with SQLite's online backup API, so they are
+//! consistent copies taken while the game keeps running. They are made on
+//! startup, after every `QUEST_COMPLETED` and every [`BACKUP_INTERVAL`], into
+//! a `backups` directory under the app data dir. Only the newest
+//! `retention` backups are kept, see [`BackupSettings`].
+//!
+//! Restoring copies a backup back over the live database the same way,
+//! after first backing up the current state. That backup skips pruning, so
+//! restoring the oldest backup never deletes it before it is read; the next
+//! backup prunes instead.
+
+pub mod commands;
+
+use std::ffi::{c_int, CStr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use libsqlite3_sys as ffi;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::{Connection, SqliteConnection};
+use tauri::{AppHandle, Emitter, Listener};
+
+use crate::error::{Error, Result};
+use crate::event_store::EVENTS_APPENDED;
+use crate::migrations;
+
+/// Directory under the app data dir holding the backups.
+pub const BACKUP_DIR: &str = "backups";
+
+/// Name of the Tauri event emitted when an automatic backup fails.
+pub const BACKUP_FAILED: &str = "backup:failed";
+
+/// Time between two timed backups.
+pub const BACKUP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Attempts at a backup step while another connection holds a lock.
+const BUSY_RETRIES: u32 = 50;
+const BUSY_SLEEP_MS: c_int = 100;
+
+/// Why a backup was made; part of its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupReason {
+    Startup,
+    QuestCompleted,
+    Timer,
+    BeforeRestore,
+}
+
+impl BackupReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupReason::Startup => "startup",
+            BackupReason::QuestCompleted => "quest-completed",
+            BackupReason::Timer => "timer",
+            BackupReason::BeforeRestore => "before-restore",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSettings {
+    /// Number of backups kept; older ones are deleted.
+    pub retention: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+    pub saves: Vec<BackupSave>,
+    /// Why the backup could not be read, `null` when it could; its `saves`
+    /// are then empty.
+    pub error: Option<Error>,
+}
+
+/// Payload of [`BACKUP_FAILED`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupFailed<'a> {
+    pub reason: &'static str,
+    pub error: &'a Error,
+}
+
+/// A save slot as recorded in a backup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSave {
+    pub profile_id: String,
+    pub save_name: String,
+    /// `null` when the stored preview does not parse.
+    pub preview: Value,
+    pub saved_at: String,
+}
+
+/// Makes and restores backups of the database behind `pool`, managed as
+/// Tauri state.
+#[derive(Clone)]
+pub struct BackupService {
+    pool: SqlitePool,
+    dir: PathBuf,
+}
+
+impl BackupService {
+    pub fn new(pool: SqlitePool, dir: PathBuf) -> Self {
+        Self { pool, dir }
+    }
+
+    pub async fn settings(&self) -> Result<BackupSettings> {
+        let retention: i64 = sqlx::query_scalar("SELECT retention FROM backup_settings")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(BackupSettings {
+            retention: retention.try_into().unwrap_or(0),
+        })
+    }
+
+    /// Fails with [`Error::InvalidBackupSettings`] if `retention` is 0,
+    /// which would delete every backup including the one just written.
+    pub async fn update_settings(&self, settings: &BackupSettings) -> Result<()> {
+        if settings.retention < 1 {
+            return Err(Error::InvalidBackupSettings(
+                "at least one backup must be kept".to_string(),
+            ));
+        }
+        sqlx::query("UPDATE backup_settings SET retention = ?")
+            .bind(settings.retention)
+            .execute(&self.pool)
+            .await?;
+        self.prune().await
+    }
+
+    /// Writes a new backup, then deletes any beyond the retention count
+    /// unless it was made before a restore. Returns the backup's file name.
+    pub async fn back_up(&self, reason: BackupReason) -> Result<String> {
+        std::fs::create_dir_all(&self.dir)?;
+        let file_name = format!(
+            "game-{}-{}.db",
+            Utc::now().format("%Y%m%d-%H%M%S%.6f"),
+            reason.as_str()
+        );
+        // Written under a temporary name so a half-written copy is never
+        // listed or restored.
+        let partial = self.dir.join(format!("{file_name}.partial"));
+
+        let mut dest = SqliteConnection::connect_with(
+            &SqliteConnectOptions::new()
+                .filename(&partial)
+                .create_if_missing(true),
+        )
+        .await?;
+        let mut source = self.pool.acquire().await?;
+        let copied = copy_database(&mut source, &mut dest).await;
+        drop(source);
+        dest.close().await?;
+        if let Err(err) = copied {
+            let _ = std::fs::remove_file(&partial);
+            return Err(err);
+        }
+        std::fs::rename(&partial, self.dir.join(&file_name))?;
+
+        if reason != BackupReason::BeforeRestore {
+            self.prune().await?;
+        }
+        Ok(file_name)
+    }
+
+    /// Lists the backups, newest first, with the saves each one holds.
+    pub async fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        let mut backups = Vec::new();
+        for file_name in self.backup_files()?.into_iter().rev() {
+            let path = self.dir.join(&file_name);
+            // A backup deleted since the directory was read is left out.
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let (saves, error) = match backup_saves(&path).await {
+                Ok(saves) => (saves, None),
+                Err(err) => (Vec::new(), Some(err)),
+            };
+            backups.push(BackupInfo {
+                created_at: metadata
+                    .modified()
+                    .map(|modified| DateTime::<Utc>::from(modified).to_rfc3339())
+                    .unwrap_or_default(),
+                size_bytes: metadata.len(),
+                saves,
+                error,
+                file_name,
+            });
+        }
+        Ok(backups)
+    }
+
+    /// Replaces the live database with the backup `file_name`, after backing
+    /// up the current state.
+    pub async fn restore_backup(&self, file_name: &str) -> Result<()> {
+        if !self.backup_files()?.iter().any(|name| name == file_name) {
+            return Err(Error::BackupNotFound(file_name.to_string()));
+        }
+        let path = self.dir.join(file_name);
+        // Opened before anything else is written to the backup dir.
+        let mut source = SqliteConnection::connect_with(
+            &SqliteConnectOptions::new().filename(&path).read_only(true),
+        )
+        .await?;
+        if let Err(err) = self.back_up(BackupReason::BeforeRestore).await {
+            source.close().await?;
+            return Err(err);
+        }
+
+        let mut dest = self.pool.acquire().await?;
+        let restored = copy_database(&mut source, &mut dest).await;
+        drop(dest);
+        source.close().await?;
+        restored?;
+
+        // A backup from an older build may predate later migrations.
+        migrations::migrate(&self.pool).await
+    }
+
+    /// Deletes the oldest backups beyond the retention count.
+    async fn prune(&self) -> Result<()> {
+        let retention = self.settings().await?.retention as usize;
+        let files = self.backup_files()?;
+        let excess = files.len().saturating_sub(retention);
+        for file_name in &files[..excess] {
+            std::fs::remove_file(self.dir.join(file_name))?;
+        }
+        Ok(())
+    }
+
+    /// Names of the finished backups, oldest first.
+    fn backup_files(&self) -> Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name.starts_with("game-") && name.ends_with(".db") {
+                files.push(name);
+            }
+        }
+        // The timestamp in the name sorts chronologically.
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Starts the automatic backups: one now, then one every
+/// [`BACKUP_INTERVAL`] and one after every committed `QUEST_COMPLETED`.
+///
+/// Failures are reported to every window as [`BACKUP_FAILED`]; a missed
+/// backup must not stop the game.
+pub fn start(app: &AppHandle, service: BackupService) {
+    let quest_backups = service.clone();
+    let quest_app = app.clone();
+    app.listen(EVENTS_APPENDED, move |event| {
+        if completes_quest(event.payload()) {
+            let service = quest_backups.clone();
+            let app = quest_app.clone();
+            tauri::async_runtime::spawn(async move {
+                back_up(&app, &service, BackupReason::QuestCompleted).await;
+            });
+        }
+    });
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        tauri::async_runtime::block_on(back_up(&app, &service, BackupReason::Startup));
+        loop {
+            std::thread::sleep(BACKUP_INTERVAL);
+            tauri::async_runtime::block_on(back_up(&app, &service, BackupReason::Timer));
+        }
+    });
+}
+
+async fn back_up(app: &AppHandle, service: &BackupService, reason: BackupReason) {
+    if let Err(error) = service.back_up(reason).await {
+        let payload = BackupFailed {
+            reason: reason.as_str(),
+            error: &error,
+        };
+        // Nothing else can be done about it; the next backup tries again.
+        let _ = app.emit(BACKUP_FAILED, payload);
+    }
+}
+
+/// Whether an `EVENTS_APPENDED` payload carries a `QUEST_COMPLETED`.
+fn completes_quest(payload: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Appended {
+        events: Vec<AppendedEvent>,
+    }
+    #[derive(Deserialize)]
+    struct AppendedEvent {
+        event_type: String,
+    }
+    serde_json::from_str::<Appended>(payload).is_ok_and(|appended| {
+        appended
+            .events
+            .iter()
+            .any(|event| event.event_type == "QUEST_COMPLETED")
+    })
+}
+
+async fn backup_saves(path: &Path) -> Result<Vec<BackupSave>> {
+    let mut conn =
+        SqliteConnection::connect_with(&SqliteConnectOptions::new().filename(path).read_only(true))
+            .await?;
+    let rows = sqlx::query_as::<_, (String, String, String, String)>(
+        "SELECT profile_id, save_name, preview_data, saved_at FROM save_games
+         ORDER BY saved_at DESC",
+    )
+    .fetch_all(&mut conn)
+    .await?;
+    conn.close().await?;
+    Ok(rows
+        .into_iter()
+        .map(|(profile_id, save_name, preview, saved_at)| BackupSave {
+            profile_id,
+            save_name,
+            preview: serde_json::from_str(&preview).unwrap_or(Value::Null),
+            saved_at,
+        })
+        .collect())
+}
+
+/// Copies the main database of `source` over that of `dest` with the online
+/// backup API, in a single step.
+async fn copy_database(source: &mut SqliteConnection, dest: &mut SqliteConnection) -> Result<()> {
+    let mut source = source.lock_handle().await?;
+    let mut dest = dest.lock_handle().await?;
+    let source = source.as_raw_handle().as_ptr();
+    let dest = dest.as_raw_handle().as_ptr();
+    let main = c"main".as_ptr();
+
+    // SAFETY: both handles are open connections, locked for the whole
+    // backup so nothing else uses them until it is finished.
+    unsafe {
+        let backup = ffi::sqlite3_backup_init(dest, main, source, main);
+        if backup.is_null() {
+            return Err(Error::Backup(error_message(dest)));
+        }
+        let mut step = ffi::sqlite3_backup_step(backup, -1);
+        for _ in 0..BUSY_RETRIES {
+            if step != ffi::SQLITE_BUSY && step != ffi::SQLITE_LOCKED {
+                break;
+            }
+            ffi::sqlite3_sleep(BUSY_SLEEP_MS);
+            step = ffi::sqlite3_backup_step(backup, -1);
+        }
+        let finish = ffi::sqlite3_backup_finish(backup);
+        if step != ffi::SQLITE_DONE || finish != ffi::SQLITE_OK {
+            return Err(Error::Backup(error_message(dest)));
+        }
+    }
+    Ok(())
+}
+
+/// # Safety
+///
+/// `db` must be an open connection.
+unsafe fn error_message(db: *mut ffi::sqlite3) -> String {
+    CStr::from_ptr(ffi::sqlite3_errmsg(db))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, EventStore};
+    use serde_json::json;
+
+    fn backup_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "space-fortress-{}-backups-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn keeps_the_newest_backups_with_their_saves() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let store = EventStore::new(pool.clone());
+            store
+                .append_events("player-1", None, None, &[bounty_modified(1)])
+                .await
+                .unwrap();
+            store
+                .save_game("1", "autosave", &json!({ "bounty": 1 }))
+                .await
+                .unwrap();
+            let dir = backup_dir("rotate");
+            let service = BackupService::new(pool, dir.clone());
+            service
+                .update_settings(&BackupSettings { retention: 2 })
+                .await
+                .unwrap();
+
+            let mut made = Vec::new();
+            for reason in [
+                BackupReason::Startup,
+                BackupReason::Timer,
+                BackupReason::QuestCompleted,
+            ] {
+                made.push(service.back_up(reason).await.unwrap());
+            }
+
+            let backups = service.list_backups().await.unwrap();
+            let _ = std::fs::remove_dir_all(&dir);
+            let names: Vec<_> = backups.iter().map(|b| b.file_name.as_str()).collect();
+            assert_eq!(names, [made[2].as_str(), made[1].as_str()]);
+            assert!(names[0].ends_with("-quest-completed.db"));
+            assert_eq!(backups[0].saves[0].save_name, "autosave");
+            assert_eq!(backups[0].saves[0].preview, json!({ "bounty": 1 }));
+        });
+    }
+
+    #[test]
+    fn restore_brings_back_the_backed_up_state() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let store = EventStore::new(pool.clone());
+            store
+                .append_events("player-1", None, None, &[bounty_modified(1)])
+                .await
+                .unwrap();
+            let dir = backup_dir("restore");
+            let service = BackupService::new(pool, dir.clone());
+            let backup = service.back_up(BackupReason::Timer).await.unwrap();
+            store
+                .append_events("player-1", None, None, &[bounty_modified(2)])
+                .await
+                .unwrap();
+
+            service.restore_backup(&backup).await.unwrap();
+            let unknown = service.restore_backup("../game.db").await;
+            let backups = service.list_backups().await.unwrap();
+            let _ = std::fs::remove_dir_all(&dir);
+
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 1);
+            assert!(matches!(unknown, Err(Error::BackupNotFound(_))));
+            // The state replaced by the restore was backed up first.
+            assert!(backups[0].file_name.ends_with("-before-restore.db"));
+        });
+    }
+
+    #[test]
+    fn restoring_the_oldest_backup_at_the_retention_limit() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let store = EventStore::new(pool.clone());
+            let dir = backup_dir("oldest");
+            let service = BackupService::new(pool, dir.clone());
+            service
+                .update_settings(&BackupSettings { retention: 2 })
+                .await
+                .unwrap();
+            let mut made = Vec::new();
+            for amount in 1..=2 {
+                store
+                    .append_events("player-1", None, None, &[bounty_modified(amount)])
+                    .await
+                    .unwrap();
+                made.push(service.back_up(BackupReason::Timer).await.unwrap());
+            }
+
+            service.restore_backup(&made[0]).await.unwrap();
+            let backups = service.list_backups().await.unwrap();
+            let _ = std::fs::remove_dir_all(&dir);
+
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 1);
+            assert_eq!(backups.len(), 3);
+        });
+    }
+
+    #[test]
+    fn keeps_at_least_one_backup() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let service = BackupService::new(pool, backup_dir("retention"));
+            let result = service
+                .update_settings(&BackupSettings { retention: 0 })
+                .await;
+            assert!(matches!(result, Err(Error::InvalidBackupSettings(_))));
+            assert_ne!(service.settings().await.unwrap().retention, 0);
+        });
+    }
+
+    #[test]
+    fn lists_an_unreadable_backup_with_its_error() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let dir = backup_dir("unreadable");
+            let service = BackupService::new(pool, dir.clone());
+            let made = service.back_up(BackupReason::Startup).await.unwrap();
+            std::fs::write(dir.join("game-0-timer.db"), "not a database").unwrap();
+
+            let backups = service.list_backups().await.unwrap();
+            let _ = std::fs::remove_dir_all(&dir);
+            assert_eq!(backups.len(), 2);
+            assert_eq!(backups[0].file_name, made);
+            assert!(backups[0].error.is_none());
+            assert_eq!(backups[1].file_name, "game-0-timer.db");
+            assert!(backups[1].saves.is_empty());
+            assert!(backups[1].error.is_some());
+        });
+    }
+
+    #[test]
+    fn only_quest_completions_trigger_a_backup() {
+        let payload = |event_type: &str| {
+            json!({
+                "streamId": "player-1",
+                "sequence": 1,
+                "events": [{ "event_id": "e1", "event_type": event_type }],
+            })
+            .to_string()
+        };
+        assert!(completes_quest(&payload("QUEST_COMPLETED")));
+        assert!(!completes_quest(&payload("BOUNTY_MODIFIED")));
+        assert!(!completes_quest("not json"));
+    }
+}
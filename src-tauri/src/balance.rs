@@ -0,0 +1,265 @@
+//! Monte Carlo balance runs over card content, driven by the `balance-sim`
+//! binary.
+//!
+//! Each battle pits five distinct cards drawn from the exported card list
+//! against a fleet drawn from the next [`FleetTemplate`] in turn, under the
+//! d20 rules of [`combat`]. Every card, faction and template is credited
+//! with the outcome from its own side of the battle; opponent cards under
+//! the id of the card template they were made from.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::game::combat::{self, FLEET_SIZE};
+use crate::game::events::Difficulty;
+use crate::game::opponents::{self, FleetTemplate};
+use crate::game::types::{BattleOutcome, Card, OpponentFactionId};
+use crate::game::Lcg;
+
+/// Battles fought by one card, faction or template.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub battles: u64,
+    pub wins: u64,
+    pub draws: u64,
+}
+
+impl Tally {
+    fn record(&mut self, outcome: BattleOutcome) {
+        self.battles += 1;
+        match outcome {
+            BattleOutcome::Victory => self.wins += 1,
+            BattleOutcome::Draw => self.draws += 1,
+            BattleOutcome::Defeat => {}
+        }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.battles.max(1) as f64
+    }
+
+    pub fn draw_rate(&self) -> f64 {
+        self.draws as f64 / self.battles.max(1) as f64
+    }
+}
+
+/// Tallies of a run, keyed by card id, faction and template name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub battles: u64,
+    pub cards: BTreeMap<String, Tally>,
+    pub factions: BTreeMap<String, Tally>,
+    pub templates: BTreeMap<String, Tally>,
+}
+
+/// Names of the templates whose pool cannot field [`FLEET_SIZE`] cards.
+/// A fleet holds at most two of each card, and a hard fleet only one of the
+/// flagship it opens with.
+pub fn short_templates(templates: &[FleetTemplate]) -> Vec<&str> {
+    templates
+        .iter()
+        .filter(|template| {
+            let pool = &template.card_pool;
+            let distinct: BTreeSet<&str> = pool.iter().map(|card| card.id.as_str()).collect();
+            let single_flagship =
+                template.difficulty == Difficulty::Hard && pool.iter().any(|card| card.weight == 1);
+            2 * distinct.len() - usize::from(single_flagship) < FLEET_SIZE
+        })
+        .map(|template| template.name.as_str())
+        .collect()
+}
+
+/// Plays `battles` seeded battles of `cards` against `templates`.
+///
+/// # Panics
+/// If there are fewer than [`FLEET_SIZE`] cards, no templates, or any of
+/// the [`short_templates`].
+pub fn simulate(cards: &[Card], templates: &[FleetTemplate], battles: u64, seed: i64) -> Report {
+    assert!(cards.len() >= FLEET_SIZE, "need {FLEET_SIZE} cards");
+    assert!(!templates.is_empty(), "need a fleet template");
+    let short = short_templates(templates);
+    assert!(
+        short.is_empty(),
+        "cannot field {FLEET_SIZE} cards: {short:?}"
+    );
+
+    let mut rng = Lcg::new(seed);
+    let mut report = Report {
+        battles,
+        ..Report::default()
+    };
+    let mut order: Vec<usize> = (0..cards.len()).collect();
+
+    for battle in 0..battles {
+        rng.shuffle(&mut order);
+        let player_fleet: Vec<Card> = order[..FLEET_SIZE]
+            .iter()
+            .map(|&index| cards[index].clone())
+            .collect();
+
+        let template = &templates[(battle % templates.len() as u64) as usize];
+        let picks = opponents::select_cards(
+            &template.card_pool,
+            FLEET_SIZE,
+            template.difficulty,
+            &mut rng,
+        );
+        let opponent_fleet: Vec<Card> = picks
+            .iter()
+            .enumerate()
+            .map(|(index, pick)| {
+                opponents::template_to_card(pick, template.faction_id, template.difficulty, index)
+            })
+            .collect();
+
+        let outcome = combat::resolve_battle(&player_fleet, &opponent_fleet, &mut rng);
+        let reversed = match outcome {
+            BattleOutcome::Victory => BattleOutcome::Defeat,
+            BattleOutcome::Defeat => BattleOutcome::Victory,
+            BattleOutcome::Draw => BattleOutcome::Draw,
+        };
+
+        for card in &player_fleet {
+            tally(&mut report.cards, &card.id).record(outcome);
+            tally(&mut report.factions, faction_name(card.faction)).record(outcome);
+        }
+        for pick in &picks {
+            tally(&mut report.cards, &pick.id).record(reversed);
+            tally(&mut report.factions, faction_name(template.faction_id)).record(reversed);
+        }
+        tally(&mut report.templates, &template.name).record(reversed);
+    }
+    report
+}
+
+fn tally<'a>(tallies: &'a mut BTreeMap<String, Tally>, key: &str) -> &'a mut Tally {
+    tallies.entry(key.to_string()).or_default()
+}
+
+fn faction_name(faction: OpponentFactionId) -> &'static str {
+    match faction {
+        OpponentFactionId::Ironveil => "ironveil",
+        OpponentFactionId::Ashfall => "ashfall",
+        OpponentFactionId::Meridian => "meridian",
+        OpponentFactionId::VoidWardens => "void_wardens",
+        OpponentFactionId::SunderedOath => "sundered_oath",
+        OpponentFactionId::Scavengers => "scavengers",
+        OpponentFactionId::Pirates => "pirates",
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} battles", self.battles)?;
+        for (heading, tallies) in [
+            ("Cards", &self.cards),
+            ("Factions", &self.factions),
+            ("Fleet templates", &self.templates),
+        ] {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{heading:<32} {:>12} {:>8} {:>8}",
+                "battles", "win %", "draw %"
+            )?;
+            let mut rows: Vec<_> = tallies.iter().collect();
+            rows.sort_by(|a, b| b.1.win_rate().total_cmp(&a.1.win_rate()));
+            for (name, tally) in rows {
+                writeln!(
+                    f,
+                    "{name:<32} {:>12} {:>8.2} {:>8.2}",
+                    tally.battles,
+                    tally.win_rate() * 100.0,
+                    tally.draw_rate() * 100.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn card(id: &str, attack: i32) -> Card {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "faction": "ironveil",
+            "attack": attack,
+            "defense": 1,
+            "hull": 4,
+            "agility": 3,
+            "energyCost": 2,
+            "abilities": [],
+        }))
+        .unwrap()
+    }
+
+    fn template(name: &str, difficulty: &str) -> FleetTemplate {
+        serde_json::from_value(json!({
+            "name": name,
+            "factionId": "scavengers",
+            "difficulty": difficulty,
+            "description": "",
+            "cardPool": [
+                { "id": "dart", "name": "Dart", "attack": 2, "defense": 1, "hull": 3,
+                  "agility": 4, "energyCost": 1, "weight": 3 },
+                { "id": "hauler", "name": "Hauler", "attack": 1, "defense": 2, "hull": 6,
+                  "agility": 1, "energyCost": 2, "weight": 2 },
+                { "id": "raider", "name": "Raider", "attack": 3, "defense": 1, "hull": 5,
+                  "agility": 3, "energyCost": 3, "weight": 1 },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn every_side_is_credited() {
+        let cards: Vec<Card> = (0..6).map(|i| card(&format!("card{i}"), 2)).collect();
+        let templates = [template("Easy pickings", "easy"), template("Raid", "hard")];
+        let report = simulate(&cards, &templates, 1_000, 7);
+
+        assert_eq!(report, simulate(&cards, &templates, 1_000, 7));
+        let fielded: u64 = report.cards.values().map(|tally| tally.battles).sum();
+        assert_eq!(fielded, 2 * 5 * 1_000);
+        assert!(report.cards["dart"].battles > 0);
+        assert_eq!(report.templates["Raid"].battles, 500);
+        assert_eq!(report.factions["ironveil"].battles, 5 * 1_000);
+        assert_eq!(report.factions["scavengers"].battles, 5 * 1_000);
+        assert!(report.templates["Easy pickings"].win_rate() < report.templates["Raid"].win_rate());
+    }
+
+    #[test]
+    fn stronger_cards_win_more() {
+        let mut cards: Vec<Card> = (0..5).map(|i| card(&format!("card{i}"), 1)).collect();
+        cards.push(card("dreadnought", 8));
+        let report = simulate(&cards, &[template("Raid", "medium")], 2_000, 3);
+
+        let ranked = report.to_string();
+        let dreadnought = report.cards["dreadnought"].win_rate();
+        assert!(report
+            .cards
+            .iter()
+            .filter(|(id, _)| id.starts_with("card"))
+            .all(|(_, tally)| tally.win_rate() < dreadnought));
+        assert!(ranked.find("dreadnought") < ranked.find("card0"));
+    }
+
+    #[test]
+    fn templates_must_field_a_full_fleet() {
+        let mut short = template("Stragglers", "medium");
+        short.card_pool.truncate(2);
+        let mut hard = template("Flagship escort", "hard");
+        hard.card_pool.truncate(2);
+        hard.card_pool[1].weight = 1;
+        let templates = [template("Raid", "hard"), short, hard];
+
+        assert_eq!(
+            short_templates(&templates),
+            ["Stragglers", "Flagship escort"]
+        );
+    }
+}
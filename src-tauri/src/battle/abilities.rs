@@ -0,0 +1,1121 @@
+//! Interpreter for the `AbilityEffect` union of `types.ts`.
+//!
+//! Triggered abilities resolve when the engine fires their trigger, e.g.
+//! `onAttack` with the defender as context and `onDefend` with the attacker.
+//! Their targets follow the ability's `targetType`: a single `ally`, `enemy`
+//! or `any_card` target is the context or a chosen target when it fits, and
+//! otherwise one drawn at random; group target types take every ship afloat.
+//! Effects that act on a side rather than a ship (energy, cards, favor)
+//! ignore the targets.
+//!
+//! Passive abilities never trigger. Stat modifiers, taunt and damage
+//! redirection in them apply continuously to the ships their `targetType`
+//! covers, for as long as their condition holds. Any other passive effect is
+//! one-shot and resolves each time a ship is destroyed, which is when
+//! `card_destroyed_this_turn` can turn true.
+//!
+//! Statuses last for `duration` turns of the affected ship's owner, not
+//! counting the turn they were applied in, so a one-turn stun applied on
+//! your turn holds through the opponent's next turn.
+
+use serde_json::Value;
+
+use super::events::BattleEvent;
+use super::state::{ShipRef, Status, StatusEffect, Target};
+use super::{Battle, SIDES};
+use crate::game::types::{
+    AbilityCondition, AbilityEffect, AbilityTargetType, AbilityTrigger, AreaTargets, CardAbility,
+    Comparison, RedirectTarget, ReturnTarget, Side,
+};
+
+impl Battle {
+    /// Fires the abilities of the ship at `at` that have `trigger`.
+    pub fn trigger(&mut self, at: ShipRef, trigger: AbilityTrigger, context: Option<Target>) {
+        let Some(ship) = self.state.ship(at) else {
+            return;
+        };
+        if !ship.abilities_active() {
+            return;
+        }
+        let card_id = ship.card.id.clone();
+        let abilities: Vec<_> = ship
+            .card
+            .abilities
+            .iter()
+            .filter(|ability| ability.trigger == trigger)
+            .cloned()
+            .collect();
+        for ability in abilities {
+            // An earlier ability may have sent the ship back to hand.
+            if self
+                .state
+                .ship(at)
+                .is_none_or(|ship| ship.card.id != card_id)
+            {
+                break;
+            }
+            self.resolve(at, &ability, trigger, context);
+        }
+    }
+
+    /// Fires `trigger` on every ship of `side`, in lane order.
+    pub fn trigger_side(&mut self, side: Side, trigger: AbilityTrigger) {
+        for at in self.state.ships(side) {
+            self.trigger(at, trigger, None);
+        }
+    }
+
+    /// Resolves `ability` of the ship at `at`, recording `ABILITY_TRIGGERED`
+    /// and then the events of its effect. An ability whose top-level
+    /// condition fails does nothing and records nothing.
+    pub fn resolve(
+        &mut self,
+        at: ShipRef,
+        ability: &CardAbility,
+        trigger: AbilityTrigger,
+        context: Option<Target>,
+    ) {
+        if let AbilityEffect::Conditional { condition, .. } = &ability.effect {
+            if !self.holds(at, condition) {
+                return;
+            }
+        }
+        let Some(ship) = self.state.ship(at) else {
+            return;
+        };
+        let card_id = ship.card.id.clone();
+        let targets = self.targets(at, ability.target_type, context);
+        self.emit(BattleEvent::AbilityTriggered {
+            ship: at,
+            card_id,
+            ability_id: ability.id.clone(),
+            trigger,
+            targets: targets.clone(),
+        });
+        self.apply(at, &ability.effect, &targets, context);
+    }
+
+    fn targets(
+        &mut self,
+        at: ShipRef,
+        target_type: AbilityTargetType,
+        context: Option<Target>,
+    ) -> Vec<Target> {
+        let own = at.side;
+        let enemy = own.other();
+        let ships = |side| -> Vec<Target> {
+            self.state
+                .afloat(side)
+                .into_iter()
+                .map(Target::Ship)
+                .collect()
+        };
+        match target_type {
+            AbilityTargetType::Own => vec![Target::Ship(at)],
+            AbilityTargetType::Ally => {
+                let mut allies = ships(own);
+                allies.retain(|&target| target != Target::Ship(at));
+                self.single(context, allies, None)
+            }
+            AbilityTargetType::Enemy => self.single(context, ships(enemy), Some(enemy)),
+            AbilityTargetType::AnyCard => {
+                let mut all = ships(own);
+                all.extend(ships(enemy));
+                self.single(context, all, Some(enemy))
+            }
+            AbilityTargetType::AllEnemies => ships(enemy),
+            AbilityTargetType::AllAllies => ships(own),
+            AbilityTargetType::Adjacent => {
+                let mut adjacent = ships(own);
+                adjacent.retain(|&target| is_adjacent(target, at));
+                adjacent
+            }
+            AbilityTargetType::Flagship => vec![Target::Flagship { side: enemy }],
+        }
+    }
+
+    /// `context` when it is one of `candidates` or the flagship of
+    /// `flagship`, otherwise a candidate drawn at random.
+    fn single(
+        &mut self,
+        context: Option<Target>,
+        candidates: Vec<Target>,
+        flagship: Option<Side>,
+    ) -> Vec<Target> {
+        match context {
+            Some(target) if candidates.contains(&target) => vec![target],
+            Some(Target::Flagship { side }) if Some(side) == flagship => {
+                vec![Target::Flagship { side }]
+            }
+            _ => self.pick(&candidates).into_iter().collect(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        at: ShipRef,
+        effect: &AbilityEffect,
+        targets: &[Target],
+        context: Option<Target>,
+    ) {
+        let own = at.side;
+        let ship_targets: Vec<ShipRef> = targets
+            .iter()
+            .filter_map(|target| match target {
+                Target::Ship(ship) => Some(*ship),
+                Target::Flagship { .. } => None,
+            })
+            .collect();
+        match effect {
+            AbilityEffect::DealDamage { amount } => {
+                for &target in targets {
+                    self.damage(target, *amount, None);
+                }
+            }
+            AbilityEffect::AreaDamage { amount, targets } => {
+                for target in self.area(at, *targets, context) {
+                    self.damage(Target::Ship(target), *amount, None);
+                }
+            }
+            AbilityEffect::DamageFlagship { amount } => {
+                self.damage(Target::Flagship { side: own.other() }, *amount, None);
+            }
+            AbilityEffect::Repair { amount } => {
+                for &target in targets {
+                    self.repair(target, *amount);
+                }
+            }
+            AbilityEffect::RepairFlagship { amount } => {
+                self.repair(Target::Flagship { side: own }, *amount);
+            }
+            AbilityEffect::Shield { amount, duration } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::Shield { amount: *amount },
+                    *duration,
+                );
+            }
+            AbilityEffect::RedirectDamage { to } => {
+                self.add_status(&ship_targets, StatusEffect::Redirect { to: *to }, 1);
+            }
+            AbilityEffect::Stun { duration } => {
+                self.add_status(&ship_targets, StatusEffect::Stunned, *duration);
+            }
+            AbilityEffect::DisableAbility { duration } => {
+                self.add_status(&ship_targets, StatusEffect::AbilitiesDisabled, *duration);
+            }
+            AbilityEffect::ForceAttack { target } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::ForcedTarget { target: *target },
+                    1,
+                );
+            }
+            AbilityEffect::Taunt { duration } => {
+                self.add_status(&ship_targets, StatusEffect::Taunt, *duration);
+            }
+            AbilityEffect::BoostAttack { amount, duration } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::Attack { amount: *amount },
+                    *duration,
+                );
+            }
+            AbilityEffect::BoostDefense { amount, duration } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::Defense { amount: *amount },
+                    *duration,
+                );
+            }
+            AbilityEffect::ReduceAttack { amount, duration } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::Attack { amount: -amount },
+                    *duration,
+                );
+            }
+            AbilityEffect::ReduceDefense { amount, duration } => {
+                self.add_status(
+                    &ship_targets,
+                    StatusEffect::Defense { amount: -amount },
+                    *duration,
+                );
+            }
+            AbilityEffect::EnergyDrain { amount } => self.drain_energy(own.other(), *amount),
+            AbilityEffect::EnergyRestore { amount } => self.gain_energy(own, *amount),
+            AbilityEffect::DrawCard { amount } => {
+                for _ in 0..*amount {
+                    self.draw(own);
+                }
+            }
+            AbilityEffect::DiscardRandom { amount } => {
+                for _ in 0..*amount {
+                    self.discard_random(own.other());
+                }
+            }
+            AbilityEffect::ReturnToHand { target } => match target {
+                ReturnTarget::Own => self.return_to_hand(at),
+                ReturnTarget::Enemy => {
+                    for &target in ship_targets.iter().filter(|ship| ship.side != own) {
+                        self.return_to_hand(target);
+                    }
+                }
+            },
+            AbilityEffect::DestroyCard => {
+                for &target in &ship_targets {
+                    if let Some(ship) = self.state.ship_mut(target) {
+                        if ship.hull > 0 {
+                            ship.hull = 0;
+                            self.destroy(target);
+                        }
+                    }
+                }
+            }
+            AbilityEffect::CopyStats => {
+                if let Some(&from) = ship_targets.iter().find(|&&target| target != at) {
+                    self.copy_stats(at, from);
+                }
+            }
+            AbilityEffect::GainFavor { amount, faction } => {
+                let faction = match (faction.as_str(), self.state.ship(at)) {
+                    ("same", Some(ship)) => match serde_json::to_value(ship.card.faction) {
+                        Ok(Value::String(faction)) => faction,
+                        _ => return,
+                    },
+                    _ => faction.clone(),
+                };
+                self.emit(BattleEvent::FavorGained {
+                    side: own,
+                    faction,
+                    amount: *amount,
+                });
+            }
+            AbilityEffect::Conditional { condition, effect } => {
+                if self.holds(at, condition) {
+                    self.apply(at, effect, targets, context);
+                }
+            }
+            AbilityEffect::Multi { effects } => {
+                for effect in effects {
+                    self.apply(at, effect, targets, context);
+                }
+            }
+        }
+    }
+
+    /// Enemy ships hit by an area effect of the ship at `at`. Adjacent means
+    /// next to the enemy ship in `context`, or to the lane facing `at`.
+    fn area(&self, at: ShipRef, area: AreaTargets, context: Option<Target>) -> Vec<ShipRef> {
+        let enemy = at.side.other();
+        let mut ships = self.state.afloat(enemy);
+        if area == AreaTargets::Adjacent {
+            let centre = match context {
+                Some(Target::Ship(target)) if target.side == enemy => target.lane,
+                _ => at.lane,
+            };
+            ships.retain(|ship| ship.lane.abs_diff(centre) == 1);
+        }
+        ships
+    }
+
+    /// Deals `amount` damage to `target`. `attacker` is set for attack
+    /// damage, one point of which a redirect can divert; shields absorb any
+    /// damage. A ship brought to 0 hull is destroyed.
+    pub fn damage(&mut self, target: Target, amount: i32, attacker: Option<ShipRef>) {
+        if amount <= 0 {
+            return;
+        }
+        let at = match target {
+            Target::Ship(at) => at,
+            Target::Flagship { side } => {
+                let flagship = &mut self.state.side_mut(side).flagship;
+                let amount = amount.min(flagship.hull);
+                flagship.hull -= amount;
+                let hull = flagship.hull;
+                self.emit(BattleEvent::FlagshipDamaged { side, amount, hull });
+                return;
+            }
+        };
+        if self.state.ship(at).is_none_or(|ship| ship.hull <= 0) {
+            return;
+        }
+
+        let mut amount = amount;
+        if let Some(to) = attacker.and_then(|attacker| self.redirect(at, attacker)) {
+            amount -= 1;
+            self.damage(Target::Ship(to), 1, None);
+        }
+        let Some(ship) = self.state.ship_mut(at) else {
+            return;
+        };
+
+        let mut absorbed = 0;
+        let mut broken = Vec::new();
+        for status in &mut ship.statuses {
+            if let StatusEffect::Shield { amount: shield } = &mut status.effect {
+                let taken = (*shield).min(amount - absorbed);
+                if taken == *shield {
+                    broken.push(StatusEffect::Shield { amount: *shield });
+                }
+                *shield -= taken;
+                absorbed += taken;
+            }
+        }
+        ship.statuses
+            .retain(|status| status.effect != StatusEffect::Shield { amount: 0 });
+        ship.hull -= amount - absorbed;
+        let (card_id, hull) = (ship.card.id.clone(), ship.hull);
+        for status in broken {
+            self.emit(BattleEvent::StatusExpired {
+                target: at,
+                card_id: card_id.clone(),
+                status,
+            });
+        }
+        self.emit(BattleEvent::DamageDealt {
+            target: at,
+            card_id,
+            amount: amount - absorbed,
+            absorbed,
+            hull,
+        });
+        if hull <= 0 {
+            self.destroy(at);
+        }
+    }
+
+    /// Where one point of an attack on the ship at `at` goes instead: to the
+    /// attacker, or to an adjacent ally guarding it.
+    fn redirect(&self, at: ShipRef, attacker: ShipRef) -> Option<ShipRef> {
+        let ship = self.state.ship(at)?;
+        for status in &ship.statuses {
+            if let StatusEffect::Redirect { to } = status.effect {
+                return match to {
+                    RedirectTarget::Attacker => Some(attacker),
+                    RedirectTarget::Adjacent => self
+                        .state
+                        .afloat(at.side)
+                        .into_iter()
+                        .find(|&ally| is_adjacent(Target::Ship(ally), at)),
+                };
+            }
+        }
+        self.passives_on(at)
+            .into_iter()
+            .find_map(|(source, effect)| match effect {
+                AbilityEffect::RedirectDamage {
+                    to: RedirectTarget::Attacker,
+                } => Some(attacker),
+                AbilityEffect::RedirectDamage {
+                    to: RedirectTarget::Adjacent,
+                } if source != at => Some(source),
+                _ => None,
+            })
+    }
+
+    fn repair(&mut self, target: Target, amount: i32) {
+        match target {
+            Target::Ship(at) => {
+                let Some(ship) = self.state.ship_mut(at) else {
+                    return;
+                };
+                // A ship being destroyed comes back from 0.
+                let before = ship.hull.max(0);
+                ship.hull = (before + amount).min(ship.card.hull);
+                let (card_id, hull) = (ship.card.id.clone(), ship.hull);
+                if hull > before {
+                    self.emit(BattleEvent::ShipRepaired {
+                        target: at,
+                        card_id,
+                        amount: hull - before,
+                        hull,
+                    });
+                }
+            }
+            Target::Flagship { side } => {
+                let flagship = &mut self.state.side_mut(side).flagship;
+                let before = flagship.hull;
+                flagship.hull = (before + amount).min(flagship.max_hull);
+                let hull = flagship.hull;
+                if hull > before {
+                    self.emit(BattleEvent::FlagshipRepaired {
+                        side,
+                        amount: hull - before,
+                        hull,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Removes the ship at `at`, once its `onDestroyed` abilities have had
+    /// their chance to save it.
+    fn destroy(&mut self, at: ShipRef) {
+        let Some(ship) = self.state.ship_mut(at) else {
+            return;
+        };
+        if !ship.destroyed_triggered {
+            ship.destroyed_triggered = true;
+            self.trigger(at, AbilityTrigger::OnDestroyed, None);
+        }
+        if self.state.ship(at).is_none_or(|ship| ship.hull > 0) {
+            return;
+        }
+
+        let owner = self.state.side_mut(at.side);
+        let Some(ship) = owner.lanes[at.lane].take() else {
+            return;
+        };
+        let card_id = ship.card.id.clone();
+        owner.discard.push(ship.card);
+        self.state.destroyed_this_turn += 1;
+        self.emit(BattleEvent::ShipDestroyed {
+            target: at,
+            card_id,
+        });
+
+        for side in SIDES {
+            for ship in self.state.afloat(side) {
+                self.one_shot_passives(ship);
+            }
+        }
+    }
+
+    fn one_shot_passives(&mut self, at: ShipRef) {
+        let Some(ship) = self.state.ship(at) else {
+            return;
+        };
+        if !ship.abilities_active() {
+            return;
+        }
+        let abilities: Vec<_> = ship
+            .card
+            .abilities
+            .iter()
+            .filter(|ability| {
+                ability.trigger == AbilityTrigger::Passive && !is_continuous(&ability.effect)
+            })
+            .cloned()
+            .collect();
+        for ability in abilities {
+            self.resolve(at, &ability, AbilityTrigger::Passive, None);
+        }
+    }
+
+    fn add_status(&mut self, targets: &[ShipRef], effect: StatusEffect, duration: i32) {
+        if duration <= 0 {
+            return;
+        }
+        let turn = self.state.turn;
+        for &at in targets {
+            let Some(ship) = self.state.ship_mut(at).filter(|ship| ship.hull > 0) else {
+                continue;
+            };
+            ship.statuses.push(Status {
+                effect,
+                remaining: duration,
+                applied_turn: turn,
+            });
+            let card_id = ship.card.id.clone();
+            self.emit(BattleEvent::StatusApplied {
+                target: at,
+                card_id,
+                status: effect,
+                duration,
+            });
+        }
+    }
+
+    /// Counts down the statuses and cooldowns of `side`'s ships at the end
+    /// of its turn, recording `STATUS_EXPIRED` for each status that runs out.
+    pub fn expire_statuses(&mut self, side: Side) {
+        let turn = self.state.turn;
+        for at in self.state.ships(side) {
+            let Some(ship) = self.state.ship_mut(at) else {
+                continue;
+            };
+            let mut expired = Vec::new();
+            ship.statuses.retain_mut(|status| {
+                if status.applied_turn != turn {
+                    status.remaining -= 1;
+                }
+                if status.remaining > 0 {
+                    return true;
+                }
+                expired.push(status.effect);
+                false
+            });
+            ship.cooldowns.retain_mut(|cooldown| {
+                cooldown.remaining -= 1;
+                cooldown.remaining > 0
+            });
+            let card_id = ship.card.id.clone();
+            for status in expired {
+                self.emit(BattleEvent::StatusExpired {
+                    target: at,
+                    card_id: card_id.clone(),
+                    status,
+                });
+            }
+        }
+    }
+
+    /// Attack of the ship at `at`, with its statuses and the passives
+    /// covering it; never below 0.
+    pub fn attack(&self, at: ShipRef) -> i32 {
+        let Some(ship) = self.state.ship(at) else {
+            return 0;
+        };
+        let statuses: i32 = ship
+            .statuses
+            .iter()
+            .map(|status| match status.effect {
+                StatusEffect::Attack { amount } => amount,
+                _ => 0,
+            })
+            .sum();
+        let passives: i32 = self
+            .passives_on(at)
+            .iter()
+            .map(|(_, effect)| match effect {
+                AbilityEffect::BoostAttack { amount, .. } => *amount,
+                AbilityEffect::ReduceAttack { amount, .. } => -amount,
+                _ => 0,
+            })
+            .sum();
+        (ship.card.attack + statuses + passives).max(0)
+    }
+
+    /// Defense of the ship at `at`, like [`attack`](Self::attack).
+    pub fn defense(&self, at: ShipRef) -> i32 {
+        let Some(ship) = self.state.ship(at) else {
+            return 0;
+        };
+        let statuses: i32 = ship
+            .statuses
+            .iter()
+            .map(|status| match status.effect {
+                StatusEffect::Defense { amount } => amount,
+                _ => 0,
+            })
+            .sum();
+        let passives: i32 = self
+            .passives_on(at)
+            .iter()
+            .map(|(_, effect)| match effect {
+                AbilityEffect::BoostDefense { amount, .. } => *amount,
+                AbilityEffect::ReduceDefense { amount, .. } => -amount,
+                _ => 0,
+            })
+            .sum();
+        (ship.card.defense + statuses + passives).max(0)
+    }
+
+    /// Whether enemies attacking `at.side` must pick the ship at `at`.
+    pub fn taunting(&self, at: ShipRef) -> bool {
+        self.state
+            .ship(at)
+            .is_some_and(|ship| ship.has_status(|effect| *effect == StatusEffect::Taunt))
+            || self
+                .passives_on(at)
+                .iter()
+                .any(|(_, effect)| matches!(effect, AbilityEffect::Taunt { .. }))
+    }
+
+    /// Continuous effects of the passive abilities covering the ship at
+    /// `at`, each with the ship it comes from.
+    fn passives_on(&self, at: ShipRef) -> Vec<(ShipRef, AbilityEffect)> {
+        let mut found = Vec::new();
+        for side in SIDES {
+            for source in self.state.afloat(side) {
+                let Some(ship) = self.state.ship(source) else {
+                    continue;
+                };
+                if !ship.abilities_active() {
+                    continue;
+                }
+                for ability in &ship.card.abilities {
+                    if ability.trigger == AbilityTrigger::Passive
+                        && covers(source, at, ability.target_type)
+                    {
+                        self.continuous(source, &ability.effect, &mut found);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn continuous(
+        &self,
+        source: ShipRef,
+        effect: &AbilityEffect,
+        found: &mut Vec<(ShipRef, AbilityEffect)>,
+    ) {
+        match effect {
+            AbilityEffect::Conditional { condition, effect } if self.holds(source, condition) => {
+                self.continuous(source, effect, found);
+            }
+            AbilityEffect::Conditional { .. } => {}
+            AbilityEffect::Multi { effects } => {
+                for effect in effects {
+                    self.continuous(source, effect, found);
+                }
+            }
+            effect if is_continuous(effect) => found.push((source, effect.clone())),
+            _ => {}
+        }
+    }
+
+    /// Evaluates `condition` for the ship at `at`.
+    fn holds(&self, at: ShipRef, condition: &AbilityCondition) -> bool {
+        let Some(ship) = self.state.ship(at) else {
+            return false;
+        };
+        let owner = self.state.side(at.side);
+        match condition {
+            AbilityCondition::HullBelow { percentage } => {
+                ship.hull * 100 < ship.card.hull * percentage
+            }
+            AbilityCondition::HullAbove { percentage } => {
+                ship.hull * 100 > ship.card.hull * percentage
+            }
+            AbilityCondition::EnergyAbove { amount } => owner.energy.current > *amount,
+            AbilityCondition::AlliesCount { comparison, count } => {
+                let allies = self
+                    .state
+                    .afloat(at.side)
+                    .into_iter()
+                    .filter(|&ally| ally != at)
+                    .count();
+                compare(allies as i32, *comparison, *count)
+            }
+            AbilityCondition::EnemiesCount { comparison, count } => {
+                let enemies = self.state.afloat(at.side.other()).len();
+                compare(enemies as i32, *comparison, *count)
+            }
+            AbilityCondition::CardDestroyedThisTurn => self.state.destroyed_this_turn > 0,
+            AbilityCondition::FirstCardPlayed => owner.actions_this_turn == 0,
+        }
+    }
+
+    /// Adds up to `amount` energy to `side`, up to its maximum.
+    pub fn gain_energy(&mut self, side: Side, amount: i32) {
+        let energy = &mut self.state.side_mut(side).energy;
+        let gained = amount.min(energy.maximum - energy.current).max(0);
+        energy.current += gained;
+        let energy = energy.current;
+        if gained > 0 {
+            self.emit(BattleEvent::EnergyGained {
+                side,
+                amount: gained,
+                energy,
+            });
+        }
+    }
+
+    fn drain_energy(&mut self, side: Side, amount: i32) {
+        let energy = &mut self.state.side_mut(side).energy;
+        let drained = amount.min(energy.current).max(0);
+        energy.current -= drained;
+        let energy = energy.current;
+        if drained > 0 {
+            self.emit(BattleEvent::EnergyDrained {
+                side,
+                amount: drained,
+                energy,
+            });
+        }
+    }
+
+    /// Moves the top card of `side`'s deck to its hand; nothing happens
+    /// once the deck is empty.
+    pub fn draw(&mut self, side: Side) {
+        let combatant = self.state.side_mut(side);
+        if combatant.deck.is_empty() {
+            return;
+        }
+        let card = combatant.deck.remove(0);
+        let card_id = card.id.clone();
+        combatant.hand.push(card);
+        let deck_remaining = combatant.deck.len();
+        self.emit(BattleEvent::CardDrawn {
+            side,
+            card_id,
+            deck_remaining,
+        });
+    }
+
+    fn discard_random(&mut self, side: Side) {
+        let indices: Vec<_> = (0..self.state.side(side).hand.len()).collect();
+        let Some(index) = self.pick(&indices) else {
+            return;
+        };
+        let combatant = self.state.side_mut(side);
+        let card = combatant.hand.remove(index);
+        let card_id = card.id.clone();
+        combatant.discard.push(card);
+        self.emit(BattleEvent::CardDiscarded { side, card_id });
+    }
+
+    fn return_to_hand(&mut self, at: ShipRef) {
+        let owner = self.state.side_mut(at.side);
+        let Some(ship) = owner.lanes.get_mut(at.lane).and_then(Option::take) else {
+            return;
+        };
+        let card_id = ship.card.id.clone();
+        owner.hand.push(ship.card);
+        self.emit(BattleEvent::ShipReturned {
+            target: at,
+            card_id,
+        });
+    }
+
+    fn copy_stats(&mut self, at: ShipRef, from: ShipRef) {
+        let Some((attack, defense)) = self
+            .state
+            .ship(from)
+            .map(|ship| (ship.card.attack, ship.card.defense))
+        else {
+            return;
+        };
+        let Some(ship) = self.state.ship_mut(at) else {
+            return;
+        };
+        ship.card.attack = attack;
+        ship.card.defense = defense;
+        let card_id = ship.card.id.clone();
+        self.emit(BattleEvent::StatsCopied {
+            target: at,
+            card_id,
+            from,
+            attack,
+            defense,
+        });
+    }
+}
+
+/// Effects that passive abilities apply continuously rather than resolve.
+fn is_continuous(effect: &AbilityEffect) -> bool {
+    match effect {
+        AbilityEffect::BoostAttack { .. }
+        | AbilityEffect::BoostDefense { .. }
+        | AbilityEffect::ReduceAttack { .. }
+        | AbilityEffect::ReduceDefense { .. }
+        | AbilityEffect::RedirectDamage { .. }
+        | AbilityEffect::Taunt { .. } => true,
+        AbilityEffect::Conditional { effect, .. } => is_continuous(effect),
+        AbilityEffect::Multi { effects } => effects.iter().all(is_continuous),
+        _ => false,
+    }
+}
+
+/// Whether a passive ability of the ship at `source` with `target_type`
+/// covers the ship at `target`.
+fn covers(source: ShipRef, target: ShipRef, target_type: AbilityTargetType) -> bool {
+    let allied = source.side == target.side;
+    match target_type {
+        AbilityTargetType::Own => source == target,
+        AbilityTargetType::Ally => allied && source != target,
+        AbilityTargetType::AllAllies => allied,
+        AbilityTargetType::Adjacent => allied && source.lane.abs_diff(target.lane) == 1,
+        AbilityTargetType::Enemy | AbilityTargetType::AllEnemies => !allied,
+        AbilityTargetType::AnyCard => true,
+        AbilityTargetType::Flagship => false,
+    }
+}
+
+fn is_adjacent(target: Target, at: ShipRef) -> bool {
+    matches!(target, Target::Ship(ship) if ship.side == at.side && ship.lane.abs_diff(at.lane) == 1)
+}
+
+fn compare(value: i32, comparison: Comparison, count: i32) -> bool {
+    match comparison {
+        Comparison::Gt => value > count,
+        Comparison::Lt => value < count,
+        Comparison::Eq => value == count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::state::{BattleState, Combatant, Energy, Ship};
+    use crate::game::types::{Card, OpponentFactionId};
+    use serde_json::json;
+
+    fn card(id: &str, abilities: Value) -> Card {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "faction": "ironveil",
+            "attack": 3,
+            "defense": 2,
+            "hull": 6,
+            "agility": 3,
+            "energyCost": 2,
+            "abilities": abilities,
+        }))
+        .unwrap()
+    }
+
+    fn ability(id: &str, trigger: &str, target_type: &str, effect: Value) -> Value {
+        json!({
+            "id": id,
+            "name": id,
+            "trigger": trigger,
+            "targetType": target_type,
+            "effect": effect,
+            "description": "",
+        })
+    }
+
+    fn battle(seed: i64) -> Battle {
+        let energy = Energy {
+            current: 3,
+            maximum: 3,
+            regeneration: 2,
+        };
+        let side = || Combatant::new(Vec::new(), 12, energy);
+        Battle::new(BattleState::new("b1", side(), side()), seed)
+    }
+
+    fn deploy(battle: &mut Battle, side: Side, lane: usize, card: Card) -> ShipRef {
+        battle.state.side_mut(side).lanes[lane] = Some(Ship::new(card));
+        ShipRef { side, lane }
+    }
+
+    fn opponent(lane: usize) -> ShipRef {
+        ShipRef {
+            side: Side::Opponent,
+            lane,
+        }
+    }
+
+    #[test]
+    fn on_attack_debuff_lasts_through_the_targets_turns() {
+        let mut battle = battle(1);
+        let breach = ability(
+            "breach",
+            "onAttack",
+            "enemy",
+            json!({ "type": "reduce_defense", "amount": 1, "duration": 2 }),
+        );
+        let attacker = deploy(
+            &mut battle,
+            Side::Player,
+            0,
+            card("hammerhead", json!([breach])),
+        );
+        let defender = deploy(&mut battle, Side::Opponent, 0, card("target", json!([])));
+        deploy(&mut battle, Side::Opponent, 1, card("bystander", json!([])));
+
+        battle.trigger(
+            attacker,
+            AbilityTrigger::OnAttack,
+            Some(Target::Ship(defender)),
+        );
+        assert_eq!(battle.defense(defender), 1);
+        assert_eq!(battle.defense(opponent(1)), 2);
+        let events = battle.take_events();
+        assert!(matches!(
+            &events[0],
+            BattleEvent::AbilityTriggered { targets, .. } if targets == &[Target::Ship(defender)]
+        ));
+        assert!(matches!(
+            events[1],
+            BattleEvent::StatusApplied {
+                status: StatusEffect::Defense { amount: -1 },
+                duration: 2,
+                ..
+            }
+        ));
+
+        // The player's own turn ending does not count for the opponent's ship,
+        // and the turn the status was applied in does not count at all.
+        battle.expire_statuses(Side::Player);
+        battle.state.turn += 1;
+        battle.expire_statuses(Side::Opponent);
+        assert_eq!(battle.defense(defender), 1);
+        battle.state.turn += 2;
+        battle.expire_statuses(Side::Opponent);
+        assert_eq!(battle.defense(defender), 2);
+        assert!(matches!(
+            battle.take_events()[..],
+            [BattleEvent::StatusExpired { target, .. }] if target == defender
+        ));
+    }
+
+    #[test]
+    fn random_targets_follow_the_seed() {
+        let run = |seed| {
+            let mut battle = battle(seed);
+            let collect = ability(
+                "collect",
+                "onDestroyed",
+                "enemy",
+                json!({ "type": "deal_damage", "amount": 3 }),
+            );
+            let creditor = deploy(
+                &mut battle,
+                Side::Player,
+                2,
+                card("creditor", json!([collect])),
+            );
+            for lane in 0..5 {
+                deploy(&mut battle, Side::Opponent, lane, card("enemy", json!([])));
+            }
+            battle.damage(Target::Ship(creditor), 10, None);
+            let hit: Vec<_> = battle
+                .take_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    BattleEvent::DamageDealt { target, .. } if target.side == Side::Opponent => {
+                        Some(target.lane)
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert!(battle.state.ship(creditor).is_none());
+            assert_eq!(battle.state.player.discard.len(), 1);
+            hit
+        };
+        assert_eq!(run(7).len(), 1);
+        assert_eq!(run(7), run(7));
+        assert!((1..50).any(|seed| run(seed) != run(7)));
+    }
+
+    #[test]
+    fn a_ship_revives_itself_once() {
+        let mut battle = battle(1);
+        let rebirth = ability(
+            "rebirth",
+            "onDestroyed",
+            "self",
+            json!({ "type": "repair", "amount": 3 }),
+        );
+        let phoenix = deploy(
+            &mut battle,
+            Side::Player,
+            0,
+            card("phoenix", json!([rebirth])),
+        );
+
+        battle.damage(Target::Ship(phoenix), 8, None);
+        assert_eq!(battle.state.ship(phoenix).unwrap().hull, 3);
+        battle.damage(Target::Ship(phoenix), 8, None);
+        assert!(battle.state.ship(phoenix).is_none());
+        let destroyed = battle
+            .take_events()
+            .into_iter()
+            .filter(|event| matches!(event, BattleEvent::ShipDestroyed { .. }))
+            .count();
+        assert_eq!(destroyed, 1);
+    }
+
+    #[test]
+    fn passives_apply_while_their_condition_holds() {
+        let mut battle = battle(1);
+        let reckless = ability(
+            "reckless",
+            "passive",
+            "self",
+            json!({
+                "type": "conditional",
+                "condition": { "type": "hull_below", "percentage": 50 },
+                "effect": { "type": "boost_attack", "amount": 2, "duration": 99 },
+            }),
+        );
+        let feast = ability(
+            "feast",
+            "passive",
+            "self",
+            json!({
+                "type": "conditional",
+                "condition": { "type": "card_destroyed_this_turn" },
+                "effect": { "type": "repair", "amount": 2 },
+            }),
+        );
+        let desperado = deploy(
+            &mut battle,
+            Side::Player,
+            0,
+            card("desperado", json!([reckless])),
+        );
+        let vulture = deploy(
+            &mut battle,
+            Side::Player,
+            1,
+            card("vulture", json!([feast])),
+        );
+        let victim = deploy(&mut battle, Side::Opponent, 0, card("victim", json!([])));
+
+        assert_eq!(battle.attack(desperado), 3);
+        battle.damage(Target::Ship(desperado), 4, None);
+        assert_eq!(battle.attack(desperado), 5);
+
+        battle.damage(Target::Ship(vulture), 3, None);
+        battle.damage(Target::Ship(victim), 6, None);
+        assert_eq!(battle.state.ship(vulture).unwrap().hull, 5);
+
+        // Stunned, the passive lapses.
+        battle.add_status(&[desperado], StatusEffect::Stunned, 1);
+        assert_eq!(battle.attack(desperado), 3);
+    }
+
+    #[test]
+    fn shields_and_redirects_soak_attack_damage() {
+        let mut battle = battle(1);
+        let guard = ability(
+            "guard",
+            "passive",
+            "adjacent",
+            json!({ "type": "redirect_damage", "to": "adjacent" }),
+        );
+        let warden = deploy(&mut battle, Side::Player, 1, card("warden", json!([guard])));
+        let ally = deploy(&mut battle, Side::Player, 2, card("ally", json!([])));
+        let attacker = deploy(&mut battle, Side::Opponent, 2, card("raider", json!([])));
+        battle.add_status(&[ally], StatusEffect::Shield { amount: 2 }, 1);
+
+        battle.damage(Target::Ship(ally), 5, Some(attacker));
+        assert_eq!(battle.state.ship(warden).unwrap().hull, 5);
+        assert_eq!(battle.state.ship(ally).unwrap().hull, 4);
+        assert!(battle.state.ship(ally).unwrap().statuses.is_empty());
+
+        // Ability damage is not redirected.
+        battle.damage(Target::Ship(ally), 1, None);
+        assert_eq!(battle.state.ship(warden).unwrap().hull, 5);
+    }
+
+    #[test]
+    fn side_effects_ignore_targets() {
+        let mut battle = battle(1);
+        let siphon = ability(
+            "siphon",
+            "onDeploy",
+            "any_card",
+            json!({
+                "type": "multi",
+                "effects": [
+                    { "type": "energy_drain", "amount": 2 },
+                    { "type": "draw_card", "amount": 2 },
+                    { "type": "gain_favor", "amount": 1, "faction": "same" },
+                ],
+            }),
+        );
+        let mut siphon_card = card("siphon", json!([siphon]));
+        siphon_card.faction = OpponentFactionId::Meridian;
+        let ship = deploy(&mut battle, Side::Player, 0, siphon_card);
+        battle.state.player.deck = vec![card("a", json!([]))];
+
+        battle.trigger(ship, AbilityTrigger::OnDeploy, None);
+        assert_eq!(battle.state.opponent.energy.current, 1);
+        assert_eq!(battle.state.player.hand.len(), 1);
+        assert!(battle.take_events().contains(&BattleEvent::FavorGained {
+            side: Side::Player,
+            faction: "meridian".to_string(),
+            amount: 1,
+        }));
+    }
+}
@@ -0,0 +1,414 @@
+//! Opponent AI for tactical battles, after the "AI Decision Tree" of the
+//! redesign doc.
+//!
+//! Positions are scored from one side's view by [`evaluate`]: hull on both
+//! sides, then the threats and opportunities of the next exchange of
+//! attacks, i.e. which ships the enemy can sink, whether it can sink the
+//! flagship, and what the side's own ships can sink in return. Unspent
+//! energy counts a little, so a move has to be worth more than it costs.
+//!
+//! - `easy` plays cards in hand order into random lanes and never uses
+//!   abilities.
+//! - `normal` is greedy: it makes the move that scores best once its ships
+//!   have attacked, until ending the turn scores best.
+//! - `hard` looks ahead: its best few moves are played out on copies of the
+//!   battle, seeded generator included, through the rest of its turn and
+//!   the player's reply, both sides playing `normal`.
+//!
+//! Plans are made by playing them on a copy of the battle, so executing the
+//! orders in turn is always legal and draws the same numbers.
+
+use std::cmp::Reverse;
+
+use serde::{Deserialize, Serialize};
+
+use super::state::{BattlePhase, ShipRef, StatusEffect, Target};
+use super::turns::Order;
+use super::Battle;
+use crate::game::types::{AbilityTargetType, AbilityTrigger, Side};
+use crate::game::Lcg;
+
+/// How well the opponent plays, and how much hull its flagship gets.
+///
+/// Tactical battles have their own easy/normal/hard levels, apart from the
+/// easy/medium/hard of the fleet templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AiDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// Score of a won battle; a lost one scores its negative.
+const WIN: i32 = 10_000;
+/// A point of flagship hull counts this many times a point of ship value.
+const FLAGSHIP_WEIGHT: i32 = 3;
+/// Moves `hard` plays out at each step.
+const LOOKAHEAD_WIDTH: usize = 3;
+/// Orders after which a turn is ended regardless.
+const MAX_ORDERS: usize = 20;
+
+/// Orders for the opponent's turn, ending with [`Order::EndTurn`]; none
+/// when it is not the opponent's turn.
+pub fn choose_opponent_orders(battle: &Battle, difficulty: AiDifficulty) -> Vec<Order> {
+    if battle.state.phase != BattlePhase::Playing || battle.state.active_side != Side::Opponent {
+        return Vec::new();
+    }
+    play(&mut battle.clone(), difficulty)
+}
+
+/// Plays the rest of the active side's turn on `battle`, returning the
+/// orders executed.
+fn play(battle: &mut Battle, difficulty: AiDifficulty) -> Vec<Order> {
+    let side = battle.state.active_side;
+    let mut rng = battle.rng.clone();
+    let mut orders = Vec::new();
+    while battle.state.phase == BattlePhase::Playing && battle.state.active_side == side {
+        let order = match difficulty {
+            _ if orders.len() >= MAX_ORDERS => Order::EndTurn,
+            AiDifficulty::Easy => easy_order(battle, &mut rng),
+            AiDifficulty::Normal => best_order(battle, side, false),
+            AiDifficulty::Hard => best_order(battle, side, true),
+        };
+        if battle.execute(&order).is_err() {
+            // Orders are picked among legal moves, so this is a bug; the
+            // turn still ends rather than leaving the opponent stuck in it.
+            if order != Order::EndTurn && battle.execute(&Order::EndTurn).is_ok() {
+                orders.push(Order::EndTurn);
+            }
+            break;
+        }
+        battle.take_events();
+        orders.push(order);
+    }
+    orders
+}
+
+/// The first affordable card in hand, into a random empty lane.
+fn easy_order(battle: &Battle, rng: &mut Lcg) -> Order {
+    let combatant = battle.state.side(battle.state.active_side);
+    let empty: Vec<_> = (0..combatant.lanes.len())
+        .filter(|&lane| combatant.lanes[lane].is_none())
+        .collect();
+    let affordable = combatant
+        .hand
+        .iter()
+        .position(|card| card.energy_cost <= combatant.energy.current);
+    match affordable {
+        Some(hand_index) if !empty.is_empty() => Order::PlayCard {
+            hand_index,
+            lane: empty[rng.below(empty.len() as u64) as usize],
+        },
+        _ => Order::EndTurn,
+    }
+}
+
+/// The move scoring best for `side`, ending the turn on ties. With
+/// `lookahead` the best few are compared again by [`rollout`].
+fn best_order(battle: &Battle, side: Side, lookahead: bool) -> Order {
+    let mut scored: Vec<_> = candidates(battle)
+        .into_iter()
+        .filter_map(|order| Some((after_attacks(battle, &order, side)?, order)))
+        .collect();
+    // The sort is stable and ending the turn comes first.
+    scored.sort_by_key(|(score, _)| Reverse(*score));
+    if lookahead {
+        scored.truncate(LOOKAHEAD_WIDTH);
+        if !scored.iter().any(|(_, order)| *order == Order::EndTurn) {
+            scored.insert(0, (0, Order::EndTurn));
+        }
+        scored = scored
+            .into_iter()
+            .filter_map(|(_, order)| Some((rollout(battle, &order, side)?, order)))
+            .collect();
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+    }
+    scored
+        .into_iter()
+        .next()
+        .map_or(Order::EndTurn, |(_, order)| order)
+}
+
+/// Ending the turn, each affordable card into each empty lane, and each
+/// usable activated ability at each target it can pick.
+fn candidates(battle: &Battle) -> Vec<Order> {
+    let side = battle.state.active_side;
+    let combatant = battle.state.side(side);
+    let energy = combatant.energy.current;
+    let mut orders = vec![Order::EndTurn];
+
+    let empty: Vec<_> = (0..combatant.lanes.len())
+        .filter(|&lane| combatant.lanes[lane].is_none())
+        .collect();
+    for (hand_index, card) in combatant.hand.iter().enumerate() {
+        // Copies of a card play the same.
+        if card.energy_cost > energy || combatant.hand[..hand_index].contains(card) {
+            continue;
+        }
+        orders.extend(
+            empty
+                .iter()
+                .map(|&lane| Order::PlayCard { hand_index, lane }),
+        );
+    }
+
+    let ships = |side| -> Vec<Option<Target>> {
+        battle
+            .state
+            .afloat(side)
+            .into_iter()
+            .map(|ship| Some(Target::Ship(ship)))
+            .collect()
+    };
+    for at in battle.state.afloat(side) {
+        let Some(ship) = battle.state.ship(at).filter(|ship| ship.abilities_active()) else {
+            continue;
+        };
+        for ability in &ship.card.abilities {
+            if ability.trigger != AbilityTrigger::Activated
+                || ability.energy_cost.unwrap_or(0) > energy
+                || ship
+                    .cooldowns
+                    .iter()
+                    .any(|cooldown| cooldown.ability_id == ability.id)
+            {
+                continue;
+            }
+            let mut targets = match ability.target_type {
+                AbilityTargetType::Enemy => ships(side.other()),
+                AbilityTargetType::Ally => ships(side),
+                AbilityTargetType::AnyCard => [ships(side), ships(side.other())].concat(),
+                _ => vec![None],
+            };
+            targets.retain(|&target| target != Some(Target::Ship(at)));
+            if targets.is_empty() {
+                targets.push(None);
+            }
+            orders.extend(targets.into_iter().map(|target| Order::ActivateAbility {
+                lane: at.lane,
+                ability_id: ability.id.clone(),
+                target,
+            }));
+        }
+    }
+    orders
+}
+
+/// Score for `side` after `order` and the attacks that end the turn.
+fn after_attacks(battle: &Battle, order: &Order, side: Side) -> Option<i32> {
+    let mut copy = battle.clone();
+    copy.execute(order).ok()?;
+    if copy.state.phase == BattlePhase::Playing && copy.state.active_side == side {
+        copy.end_turn().ok()?;
+    }
+    Some(evaluate(&copy, side))
+}
+
+/// Score for `side` after `order`, the rest of its turn and the other
+/// side's reply, both played `normal`.
+fn rollout(battle: &Battle, order: &Order, side: Side) -> Option<i32> {
+    let mut copy = battle.clone();
+    copy.execute(order).ok()?;
+    if copy.state.active_side == side {
+        play(&mut copy, AiDifficulty::Normal);
+    }
+    play(&mut copy, AiDifficulty::Normal);
+    Some(evaluate(&copy, side))
+}
+
+/// How good the position of `battle` is for `side`.
+pub fn evaluate(battle: &Battle, side: Side) -> i32 {
+    let state = &battle.state;
+    if state.phase == BattlePhase::Resolved {
+        return match state.winner {
+            Some(winner) if winner == side => WIN,
+            Some(_) => -WIN,
+            None => 0,
+        };
+    }
+    let enemy = side.other();
+    let (own, other) = (state.side(side), state.side(enemy));
+    let mut score = FLAGSHIP_WEIGHT * (own.flagship.hull - other.flagship.hull)
+        + fleet_value(battle, side)
+        - fleet_value(battle, enemy)
+        + own.energy.current
+        + own.hand.len() as i32
+        - other.hand.len() as i32;
+
+    let threats = exchange(battle, enemy);
+    score -= threats.sunk + FLAGSHIP_WEIGHT * threats.flagship_damage;
+    if threats.flagship_damage >= own.flagship.hull {
+        score -= WIN / 2;
+    }
+    // The enemy moves first, so opportunities count for less.
+    let opportunities = exchange(battle, side);
+    score += (opportunities.sunk + FLAGSHIP_WEIGHT * opportunities.flagship_damage) / 2;
+    score
+}
+
+/// What the ships of `attackers` would do if they attacked now.
+struct Exchange {
+    /// Value of the ships they would sink.
+    sunk: i32,
+    flagship_damage: i32,
+}
+
+fn exchange(battle: &Battle, attackers: Side) -> Exchange {
+    let mut damage: Vec<(ShipRef, i32)> = Vec::new();
+    let mut flagship_damage = 0;
+    for at in battle.state.afloat(attackers) {
+        if battle
+            .state
+            .ship(at)
+            .is_none_or(|ship| ship.has_status(|effect| *effect == StatusEffect::Stunned))
+        {
+            continue;
+        }
+        match battle.lane_target(at) {
+            Target::Ship(defender) => {
+                let amount = (battle.attack(at) - battle.defense(defender)).max(1);
+                match damage.iter_mut().find(|(ship, _)| *ship == defender) {
+                    Some((_, total)) => *total += amount,
+                    None => damage.push((defender, amount)),
+                }
+            }
+            Target::Flagship { .. } => flagship_damage += battle.attack(at),
+        }
+    }
+    let sunk = damage
+        .into_iter()
+        .filter(|&(ship, amount)| {
+            battle
+                .state
+                .ship(ship)
+                .is_some_and(|ship| amount >= ship.hull)
+        })
+        .map(|(ship, _)| ship_value(battle, ship))
+        .sum();
+    Exchange {
+        sunk,
+        flagship_damage,
+    }
+}
+
+fn fleet_value(battle: &Battle, side: Side) -> i32 {
+    battle
+        .state
+        .afloat(side)
+        .into_iter()
+        .map(|at| ship_value(battle, at))
+        .sum()
+}
+
+/// Hull counts double: it is what keeps the ship's attack on the board.
+fn ship_value(battle: &Battle, at: ShipRef) -> i32 {
+    battle
+        .state
+        .ship(at)
+        .map_or(0, |ship| 2 * ship.hull + battle.attack(at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::state::{BattleState, Combatant, Energy, Ship};
+    use crate::game::types::Card;
+    use serde_json::json;
+
+    fn card(id: &str, attack: i32, hull: i32, agility: i32) -> Card {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "faction": "ashfall",
+            "attack": attack,
+            "defense": 1,
+            "hull": hull,
+            "agility": agility,
+            "energyCost": 2,
+            "abilities": [],
+        }))
+        .unwrap()
+    }
+
+    fn deck(prefix: &str, agility: i32) -> Vec<Card> {
+        (0..8)
+            .map(|i| card(&format!("{prefix}{i}"), 2 + i % 3, 3 + i % 4, agility))
+            .collect()
+    }
+
+    const DIFFICULTIES: [AiDifficulty; 3] =
+        [AiDifficulty::Easy, AiDifficulty::Normal, AiDifficulty::Hard];
+
+    #[test]
+    fn levels_are_easy_normal_and_hard() {
+        let levels: Vec<AiDifficulty> =
+            serde_json::from_value(serde_json::json!(["easy", "normal", "hard"])).unwrap();
+        assert_eq!(levels, DIFFICULTIES);
+    }
+
+    #[test]
+    fn orders_are_legal_and_end_the_turn() {
+        let battle = Battle::start("b1", deck("p", 2), deck("o", 4), AiDifficulty::Normal, 3);
+        for difficulty in DIFFICULTIES {
+            let orders = choose_opponent_orders(&battle, difficulty);
+            assert_eq!(orders.last(), Some(&Order::EndTurn));
+            assert_eq!(orders, choose_opponent_orders(&battle, difficulty));
+
+            let mut copy = battle.clone();
+            for order in &orders {
+                copy.execute(order).unwrap();
+            }
+            assert_eq!(copy.state.active_side, Side::Player);
+            assert!(choose_opponent_orders(&copy, difficulty).is_empty());
+        }
+    }
+
+    #[test]
+    fn normal_blocks_a_lane_that_threatens_the_flagship() {
+        let energy = Energy {
+            current: 3,
+            maximum: 3,
+            regeneration: 2,
+        };
+        let mut opponent = Combatant::new(Vec::new(), 4, energy);
+        opponent.hand.push(card("blocker", 1, 6, 2));
+        let mut state = BattleState::new("b1", Combatant::new(Vec::new(), 10, energy), opponent);
+        state.turn = 2;
+        state.active_side = Side::Opponent;
+        state.player.lanes[3] = Some(Ship::new(card("striker", 5, 4, 3)));
+        let battle = Battle::new(state, 1);
+
+        assert_eq!(
+            choose_opponent_orders(&battle, AiDifficulty::Normal),
+            [
+                Order::PlayCard {
+                    hand_index: 0,
+                    lane: 3
+                },
+                Order::EndTurn
+            ]
+        );
+    }
+
+    #[test]
+    fn harder_opponents_win_more() {
+        let wins = |difficulty| {
+            (0..12)
+                .filter(|&seed| {
+                    let mut battle =
+                        Battle::start("b1", deck("p", 3), deck("o", 3), AiDifficulty::Easy, seed);
+                    while battle.state.phase == BattlePhase::Playing {
+                        match battle.state.active_side {
+                            Side::Player => play(&mut battle, AiDifficulty::Easy),
+                            Side::Opponent => play(&mut battle, difficulty),
+                        };
+                    }
+                    battle.state.winner == Some(Side::Opponent)
+                })
+                .count()
+        };
+        let (easy, hard) = (wins(AiDifficulty::Easy), wins(AiDifficulty::Hard));
+        assert!(hard > easy, "hard won {hard}, easy won {easy}");
+    }
+}
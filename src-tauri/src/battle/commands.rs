@@ -0,0 +1,98 @@
+//! Tauri commands exposing tactical [`Battles`] to the webview, built with
+//! the `tactical` feature.
+
+use tauri::{AppHandle, State};
+
+use super::ai::{self, AiDifficulty};
+use super::turns::Order;
+use super::{Battle, BattleUpdate, Battles, Target};
+use crate::error::Result;
+use crate::event_store::{emit_appended, player_stream, EventStore, StoredEvent};
+use crate::game::types::Card;
+
+/// Starts a battle of `player_id`, seeded from the player's stream.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_tactical_battle(
+    app: AppHandle,
+    store: State<'_, EventStore>,
+    battles: State<'_, Battles>,
+    player_id: String,
+    battle_id: String,
+    player_deck: Vec<Card>,
+    opponent_deck: Vec<Card>,
+    difficulty: AiDifficulty,
+) -> Result<BattleUpdate> {
+    let stream_id = player_stream(&player_id);
+    let seed = store.draw_seed(&stream_id).await?;
+    let battle = Battle::start(&battle_id, player_deck, opponent_deck, difficulty, seed);
+    let recorded = battles.insert(&store, &stream_id, battle).await?;
+    Ok(notify(&app, recorded))
+}
+
+#[tauri::command]
+pub async fn play_card(
+    app: AppHandle,
+    store: State<'_, EventStore>,
+    battles: State<'_, Battles>,
+    battle_id: String,
+    hand_index: usize,
+    lane: usize,
+) -> Result<BattleUpdate> {
+    let recorded = battles
+        .update(&store, &battle_id, |battle| {
+            battle.play_card(hand_index, lane)
+        })
+        .await?;
+    Ok(notify(&app, recorded))
+}
+
+#[tauri::command]
+pub async fn activate_ability(
+    app: AppHandle,
+    store: State<'_, EventStore>,
+    battles: State<'_, Battles>,
+    battle_id: String,
+    lane: usize,
+    ability_id: String,
+    target: Option<Target>,
+) -> Result<BattleUpdate> {
+    let recorded = battles
+        .update(&store, &battle_id, |battle| {
+            battle.activate_ability(lane, &ability_id, target)
+        })
+        .await?;
+    Ok(notify(&app, recorded))
+}
+
+#[tauri::command]
+pub async fn end_turn(
+    app: AppHandle,
+    store: State<'_, EventStore>,
+    battles: State<'_, Battles>,
+    battle_id: String,
+) -> Result<BattleUpdate> {
+    let recorded = battles.update(&store, &battle_id, Battle::end_turn).await?;
+    Ok(notify(&app, recorded))
+}
+
+#[tauri::command]
+pub fn choose_opponent_orders(
+    battles: State<'_, Battles>,
+    battle_id: String,
+    difficulty: AiDifficulty,
+) -> Result<Vec<Order>> {
+    battles.read(&battle_id, |battle| {
+        ai::choose_opponent_orders(battle, difficulty)
+    })
+}
+
+#[tauri::command]
+pub fn close_tactical_battle(battles: State<'_, Battles>, battle_id: String) {
+    battles.close(&battle_id);
+}
+
+fn notify(app: &AppHandle, (update, stored): (BattleUpdate, Vec<StoredEvent>)) -> BattleUpdate {
+    emit_appended(app, &update.stream_id, &stored);
+    update
+}
@@ -0,0 +1,214 @@
+//! Events of a battle as it is resolved, named after the "New Event Types"
+//! of the redesign doc. Ships are identified by [`ShipRef`], with the card
+//! id alongside for the combat log.
+//!
+//! They are appended to the player's stream next to the [`GameEvent`]s,
+//! their data tagged with the battle's id.
+//!
+//! [`GameEvent`]: crate::game::GameEvent
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::state::{ShipRef, StatusEffect, Target};
+use crate::error::Result;
+use crate::event_store::NewEvent;
+use crate::game::types::{AbilityTrigger, Side};
+
+/// Every battle event type, in declaration order.
+pub const BATTLE_EVENT_TYPES: &[&str] = &[
+    "INITIATIVE_DETERMINED",
+    "TURN_STARTED",
+    "TURN_ENDED",
+    "ENERGY_SPENT",
+    "CARD_DEPLOYED",
+    "ABILITY_ACTIVATED",
+    "ATTACK_DECLARED",
+    "ABILITY_TRIGGERED",
+    "DAMAGE_DEALT",
+    "FLAGSHIP_DAMAGED",
+    "SHIP_REPAIRED",
+    "FLAGSHIP_REPAIRED",
+    "SHIP_DESTROYED",
+    "STATUS_APPLIED",
+    "STATUS_EXPIRED",
+    "ENERGY_GAINED",
+    "ENERGY_DRAINED",
+    "CARD_DRAWN",
+    "CARD_DISCARDED",
+    "SHIP_RETURNED",
+    "STATS_COPIED",
+    "FAVOR_GAINED",
+    "FLEET_ELIMINATED",
+    "FLAGSHIP_DESTROYED",
+    "BATTLE_TIMEOUT",
+    "BATTLE_ENDED",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "data",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    rename_all_fields = "camelCase"
+)]
+pub enum BattleEvent {
+    InitiativeDetermined {
+        first_side: Side,
+        /// Agility of the opening hands.
+        player_agility: i32,
+        opponent_agility: i32,
+    },
+    TurnStarted {
+        turn: u32,
+        side: Side,
+    },
+    TurnEnded {
+        turn: u32,
+        side: Side,
+    },
+    EnergySpent {
+        side: Side,
+        amount: i32,
+        energy: i32,
+    },
+    CardDeployed {
+        ship: ShipRef,
+        card_id: String,
+        energy_cost: i32,
+    },
+    /// Precedes the `ABILITY_TRIGGERED` of the activation.
+    AbilityActivated {
+        ship: ShipRef,
+        card_id: String,
+        ability_id: String,
+        energy_cost: i32,
+    },
+    AttackDeclared {
+        attacker: ShipRef,
+        card_id: String,
+        target: Target,
+    },
+    /// Precedes the events of the ability's effects.
+    AbilityTriggered {
+        ship: ShipRef,
+        card_id: String,
+        ability_id: String,
+        trigger: AbilityTrigger,
+        targets: Vec<Target>,
+    },
+    DamageDealt {
+        target: ShipRef,
+        card_id: String,
+        /// Damage that reached the hull.
+        amount: i32,
+        /// Damage taken by shields.
+        absorbed: i32,
+        hull: i32,
+    },
+    FlagshipDamaged {
+        side: Side,
+        amount: i32,
+        hull: i32,
+    },
+    ShipRepaired {
+        target: ShipRef,
+        card_id: String,
+        amount: i32,
+        hull: i32,
+    },
+    FlagshipRepaired {
+        side: Side,
+        amount: i32,
+        hull: i32,
+    },
+    ShipDestroyed {
+        target: ShipRef,
+        card_id: String,
+    },
+    StatusApplied {
+        target: ShipRef,
+        card_id: String,
+        status: StatusEffect,
+        duration: i32,
+    },
+    StatusExpired {
+        target: ShipRef,
+        card_id: String,
+        status: StatusEffect,
+    },
+    EnergyGained {
+        side: Side,
+        amount: i32,
+        energy: i32,
+    },
+    EnergyDrained {
+        side: Side,
+        amount: i32,
+        energy: i32,
+    },
+    CardDrawn {
+        side: Side,
+        card_id: String,
+        deck_remaining: usize,
+    },
+    CardDiscarded {
+        side: Side,
+        card_id: String,
+    },
+    ShipReturned {
+        target: ShipRef,
+        card_id: String,
+    },
+    StatsCopied {
+        target: ShipRef,
+        card_id: String,
+        from: ShipRef,
+        attack: i32,
+        defense: i32,
+    },
+    FavorGained {
+        side: Side,
+        faction: String,
+        amount: i32,
+    },
+    /// `side` starts a turn with neither ships nor cards in hand.
+    FleetEliminated {
+        side: Side,
+    },
+    FlagshipDestroyed {
+        side: Side,
+    },
+    /// The round limit was reached; the side with more hull left wins.
+    BattleTimeout {
+        rounds: u32,
+        player_hull: i32,
+        opponent_hull: i32,
+    },
+    /// Always the last event; `winner` is `None` for a draw.
+    BattleEnded {
+        winner: Option<Side>,
+    },
+}
+
+impl BattleEvent {
+    pub fn is_known_type(event_type: &str) -> bool {
+        BATTLE_EVENT_TYPES.contains(&event_type)
+    }
+
+    /// Parses an event from the `type`/`data` pair it is stored as; the
+    /// battle id in `data` is ignored.
+    pub fn from_parts(event_type: &str, data: &Value) -> serde_json::Result<Self> {
+        serde_json::from_value(json!({ "type": event_type, "data": data }))
+    }
+
+    /// The event as appended to a stream, with `battleId` added to its data.
+    pub fn to_new_event(&self, battle_id: &str) -> Result<NewEvent> {
+        let mut event = serde_json::to_value(self)?;
+        event["data"]["battleId"] = json!(battle_id);
+        Ok(NewEvent {
+            event_type: event["type"].as_str().unwrap_or_default().to_string(),
+            data: event["data"].take(),
+        })
+    }
+}
@@ -0,0 +1,264 @@
+//! Native battle engine for the Tactical Engagement rules of
+//! `docs/design/CARD-BATTLE-REDESIGN.md`.
+//!
+//! A [`Battle`] owns its state and a seeded [`Lcg`], and records a
+//! [`BattleEvent`] for every change it makes. Everything random is drawn from
+//! that generator in a fixed order, so a battle replays exactly from its seed
+//! and the same inputs.
+//!
+//! The webview plays battles through [`Battles`], whose commands are only
+//! registered when the crate is built with the `tactical` feature. Each
+//! battle belongs to a player's stream: its seed is drawn from the stream's
+//! generator and its events are appended to the stream.
+
+pub mod abilities;
+pub mod ai;
+#[cfg(feature = "tactical")]
+pub mod commands;
+pub mod events;
+pub mod state;
+pub mod turns;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+pub use events::BattleEvent;
+pub use state::{BattleState, ShipRef, Target};
+
+use crate::error::{Error, Result};
+use crate::event_store::{EventStore, StoredEvent};
+use crate::game::types::Side;
+use crate::game::Lcg;
+
+const SIDES: [Side; 2] = [Side::Player, Side::Opponent];
+
+#[derive(Debug, Clone)]
+pub struct Battle {
+    pub state: BattleState,
+    rng: Lcg,
+    events: Vec<BattleEvent>,
+}
+
+impl Battle {
+    pub fn new(state: BattleState, seed: i64) -> Self {
+        Self {
+            state,
+            rng: Lcg::new(seed),
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns the events recorded since the last call.
+    pub fn take_events(&mut self) -> Vec<BattleEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn emit(&mut self, event: BattleEvent) {
+        self.events.push(event);
+    }
+
+    /// A uniformly drawn element of `items`, or `None` when it is empty.
+    fn pick<T: Copy>(&mut self, items: &[T]) -> Option<T> {
+        if items.is_empty() {
+            return None;
+        }
+        Some(items[self.rng.below(items.len() as u64) as usize])
+    }
+}
+
+/// A battle's state with the events of the command that led to it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BattleUpdate {
+    pub state: BattleState,
+    pub events: Vec<BattleEvent>,
+    /// Stream the events belong to.
+    #[serde(skip)]
+    pub stream_id: String,
+}
+
+impl BattleUpdate {
+    /// Appends the update's events to its stream as one command.
+    pub async fn append_to(&self, store: &EventStore) -> Result<Vec<StoredEvent>> {
+        if self.events.is_empty() {
+            return Ok(Vec::new());
+        }
+        let events = self
+            .events
+            .iter()
+            .map(|event| event.to_new_event(&self.state.battle_id))
+            .collect::<Result<Vec<_>>>()?;
+        store
+            .append_events(&self.stream_id, None, None, &events)
+            .await
+    }
+}
+
+/// The tactical battles in progress by battle id, each with the stream it
+/// belongs to, managed as Tauri state.
+#[derive(Debug, Default)]
+pub struct Battles {
+    open: Mutex<HashMap<String, (String, Battle)>>,
+}
+
+impl Battles {
+    /// Appends the start of `battle` to `stream_id`, then keeps the battle
+    /// open, replacing a battle with the same id.
+    pub async fn insert(
+        &self,
+        store: &EventStore,
+        stream_id: &str,
+        mut battle: Battle,
+    ) -> Result<(BattleUpdate, Vec<StoredEvent>)> {
+        let update = BattleUpdate {
+            state: battle.state.clone(),
+            events: battle.take_events(),
+            stream_id: stream_id.to_string(),
+        };
+        let stored = update.append_to(store).await?;
+        self.open.lock().unwrap().insert(
+            update.state.battle_id.clone(),
+            (stream_id.to_string(), battle),
+        );
+        Ok((update, stored))
+    }
+
+    /// Runs `f` on a copy of the open battle `battle_id` and appends its
+    /// events to the battle's stream. The copy replaces the battle once they
+    /// are stored, so an illegal move or a failed append leaves the battle as
+    /// it was.
+    pub async fn update(
+        &self,
+        store: &EventStore,
+        battle_id: &str,
+        f: impl FnOnce(&mut Battle) -> Result<()>,
+    ) -> Result<(BattleUpdate, Vec<StoredEvent>)> {
+        let (stream_id, mut battle) = self
+            .open
+            .lock()
+            .unwrap()
+            .get(battle_id)
+            .cloned()
+            .ok_or_else(|| Error::BattleNotFound(battle_id.to_string()))?;
+        f(&mut battle)?;
+        let update = BattleUpdate {
+            state: battle.state.clone(),
+            events: battle.take_events(),
+            stream_id,
+        };
+        let stored = update.append_to(store).await?;
+        // A battle closed meanwhile stays closed.
+        if let Some((_, open)) = self.open.lock().unwrap().get_mut(battle_id) {
+            *open = battle;
+        }
+        Ok((update, stored))
+    }
+
+    /// Runs `f` on the open battle `battle_id` without changing it.
+    pub fn read<T>(&self, battle_id: &str, f: impl FnOnce(&Battle) -> T) -> Result<T> {
+        let open = self.open.lock().unwrap();
+        let (_, battle) = open
+            .get(battle_id)
+            .ok_or_else(|| Error::BattleNotFound(battle_id.to_string()))?;
+        Ok(f(battle))
+    }
+
+    pub fn close(&self, battle_id: &str) {
+        self.open.lock().unwrap().remove(battle_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ai::AiDifficulty;
+    use super::*;
+    use crate::db;
+    use crate::event_store::{EventMetadata, RngRecord};
+    use crate::game::types::Card;
+    use serde_json::json;
+
+    fn deck(prefix: &str) -> Vec<Card> {
+        (0..8)
+            .map(|i| {
+                serde_json::from_value(json!({
+                    "id": format!("{prefix}{i}"),
+                    "name": "Corvette",
+                    "faction": "ironveil",
+                    "attack": 3,
+                    "defense": 1,
+                    "hull": 4,
+                    "agility": 2,
+                    "energyCost": 2,
+                    "abilities": [],
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn battles_are_seeded_from_and_recorded_in_the_stream() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let battles = Battles::default();
+            let seed = store.draw_seed("player-1").await.unwrap();
+            let start =
+                |seed| Battle::start("b1", deck("p"), deck("o"), AiDifficulty::Normal, seed);
+
+            let (update, stored) = battles
+                .insert(&store, "player-1", start(seed))
+                .await
+                .unwrap();
+            assert_eq!(stored.len(), update.events.len());
+            battles
+                .update(&store, "b1", Battle::end_turn)
+                .await
+                .unwrap();
+
+            let stream = store.read_stream("player-1").await.unwrap();
+            let replayed: Vec<BattleEvent> = stream
+                .iter()
+                .map(|event| {
+                    let data: serde_json::Value = serde_json::from_str(&event.event_data).unwrap();
+                    assert_eq!(data["battleId"], "b1");
+                    BattleEvent::from_parts(&event.event_type, &data).unwrap()
+                })
+                .collect();
+            let mut again = start(seed);
+            again.end_turn().unwrap();
+            assert_eq!(replayed, again.take_events());
+
+            let metadata: EventMetadata =
+                serde_json::from_str(stream[0].metadata.as_deref().unwrap()).unwrap();
+            let RngRecord { draws, .. } = metadata.rng.unwrap();
+            assert_eq!(
+                serde_json::to_value(draws).unwrap(),
+                json!([{ "kind": "seed", "index": 0, "seed": seed }])
+            );
+        });
+    }
+
+    #[test]
+    fn a_failed_append_leaves_the_battle_as_it_was() {
+        tauri::async_runtime::block_on(async {
+            let pool = db::connect_in_memory().await.unwrap();
+            let store = EventStore::new(pool.clone());
+            let battles = Battles::default();
+            let battle = Battle::start("b1", deck("p"), deck("o"), AiDifficulty::Normal, 1);
+            battles.insert(&store, "player-1", battle).await.unwrap();
+            let before = battles.read("b1", |battle| battle.state.clone()).unwrap();
+
+            pool.close().await;
+            assert!(battles
+                .update(&store, "b1", Battle::end_turn)
+                .await
+                .is_err());
+            assert_eq!(
+                battles.read("b1", |battle| battle.state.clone()).unwrap(),
+                before
+            );
+        });
+    }
+}
@@ -0,0 +1,247 @@
+//! Battle state, shaped like `TacticalBattleState` in the redesign doc.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::types::{Card, ForcedTarget, RedirectTarget, Side};
+
+/// Lanes on each side of the battlefield.
+pub const LANES: usize = 5;
+
+/// A lane on one side, which is how ships are addressed: card ids repeat
+/// when a fleet holds two copies of a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShipRef {
+    pub side: Side,
+    pub lane: usize,
+}
+
+/// What an ability or attack is aimed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Target {
+    Ship(ShipRef),
+    Flagship { side: Side },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BattleState {
+    pub battle_id: String,
+    /// Turns started so far, counting both sides' turns.
+    pub turn: u32,
+    pub active_side: Side,
+    pub player: Combatant,
+    pub opponent: Combatant,
+    /// Ships destroyed since the current turn started.
+    pub destroyed_this_turn: u32,
+    pub phase: BattlePhase,
+    /// Set once the battle is resolved, unless it ended in a draw.
+    pub winner: Option<Side>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BattlePhase {
+    Playing,
+    Resolved,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Combatant {
+    pub flagship: Flagship,
+    pub energy: Energy,
+    /// [`LANES`] slots, `None` where no ship is deployed.
+    pub lanes: Vec<Option<Ship>>,
+    pub hand: Vec<Card>,
+    /// Top of the deck first.
+    pub deck: Vec<Card>,
+    pub discard: Vec<Card>,
+    /// Cards deployed and attacks made since the side's turn started.
+    pub actions_this_turn: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Flagship {
+    pub hull: i32,
+    pub max_hull: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Energy {
+    pub current: i32,
+    pub maximum: i32,
+    pub regeneration: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ship {
+    pub card: Card,
+    pub hull: i32,
+    pub exhausted: bool,
+    pub statuses: Vec<Status>,
+    pub cooldowns: Vec<Cooldown>,
+    /// Set once its `onDestroyed` abilities have fired; they fire once per
+    /// battle, so a ship that revives itself does so only once.
+    pub destroyed_triggered: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub effect: StatusEffect,
+    /// Turns of the ship's owner left before it expires.
+    pub remaining: i32,
+    /// Turn it was applied in, which does not count towards its duration.
+    pub applied_turn: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum StatusEffect {
+    /// Absorbs up to `amount` damage, then breaks.
+    Shield {
+        amount: i32,
+    },
+    /// Cannot attack or use abilities.
+    Stunned,
+    AbilitiesDisabled,
+    ForcedTarget {
+        target: ForcedTarget,
+    },
+    Taunt,
+    /// Added to attack; negative for a reduction.
+    Attack {
+        amount: i32,
+    },
+    /// Added to defense; negative for a reduction.
+    Defense {
+        amount: i32,
+    },
+    Redirect {
+        to: RedirectTarget,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cooldown {
+    pub ability_id: String,
+    /// Turns of the ship's owner left before the ability can be used again.
+    pub remaining: i32,
+}
+
+impl BattleState {
+    pub fn new(battle_id: &str, player: Combatant, opponent: Combatant) -> Self {
+        Self {
+            battle_id: battle_id.to_string(),
+            turn: 0,
+            active_side: Side::Player,
+            player,
+            opponent,
+            destroyed_this_turn: 0,
+            phase: BattlePhase::Playing,
+            winner: None,
+        }
+    }
+
+    pub fn side(&self, side: Side) -> &Combatant {
+        match side {
+            Side::Player => &self.player,
+            Side::Opponent => &self.opponent,
+        }
+    }
+
+    pub fn side_mut(&mut self, side: Side) -> &mut Combatant {
+        match side {
+            Side::Player => &mut self.player,
+            Side::Opponent => &mut self.opponent,
+        }
+    }
+
+    pub fn ship(&self, at: ShipRef) -> Option<&Ship> {
+        self.side(at.side).lanes.get(at.lane)?.as_ref()
+    }
+
+    pub fn ship_mut(&mut self, at: ShipRef) -> Option<&mut Ship> {
+        self.side_mut(at.side).lanes.get_mut(at.lane)?.as_mut()
+    }
+
+    /// The ships of `side` in lane order, including ones at 0 hull that are
+    /// being destroyed.
+    pub fn ships(&self, side: Side) -> Vec<ShipRef> {
+        self.side(side)
+            .lanes
+            .iter()
+            .enumerate()
+            .filter(|(_, ship)| ship.is_some())
+            .map(|(lane, _)| ShipRef { side, lane })
+            .collect()
+    }
+
+    /// The ships of `side` still afloat, in lane order.
+    pub fn afloat(&self, side: Side) -> Vec<ShipRef> {
+        self.ships(side)
+            .into_iter()
+            .filter(|&at| self.ship(at).is_some_and(|ship| ship.hull > 0))
+            .collect()
+    }
+}
+
+impl Combatant {
+    /// A side with `deck` undrawn and its flagship at `flagship_hull`.
+    pub fn new(deck: Vec<Card>, flagship_hull: i32, energy: Energy) -> Self {
+        Self {
+            flagship: Flagship {
+                hull: flagship_hull,
+                max_hull: flagship_hull,
+            },
+            energy,
+            lanes: vec![None; LANES],
+            hand: Vec::new(),
+            deck,
+            discard: Vec::new(),
+            actions_this_turn: 0,
+        }
+    }
+}
+
+impl Ship {
+    pub fn new(card: Card) -> Self {
+        Self {
+            hull: card.hull,
+            card,
+            exhausted: false,
+            statuses: Vec::new(),
+            cooldowns: Vec::new(),
+            destroyed_triggered: false,
+        }
+    }
+
+    pub fn has_status(&self, matches: impl Fn(&StatusEffect) -> bool) -> bool {
+        self.statuses.iter().any(|status| matches(&status.effect))
+    }
+
+    /// Stunned and disabled ships neither trigger nor activate abilities,
+    /// and their passive abilities lapse.
+    pub fn abilities_active(&self) -> bool {
+        !self.has_status(|effect| {
+            matches!(
+                effect,
+                StatusEffect::Stunned | StatusEffect::AbilitiesDisabled
+            )
+        })
+    }
+}
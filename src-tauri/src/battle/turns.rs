@@ -0,0 +1,639 @@
+//! Turn structure and victory conditions of Tactical Engagement.
+//!
+//! The main phase of a turn is made of [`play_card`](Battle::play_card) and
+//! [`activate_ability`](Battle::activate_ability) in any order, and
+//! [`end_turn`](Battle::end_turn) closes it: every ready ship of the active
+//! side attacks, then the end phase runs and the other side's turn starts.
+//! Ships are deployed exhausted and readied in their owner's end phase, so
+//! they attack from the turn after they were deployed.
+//!
+//! A ship attacks the enemy in the opposing lane, or the enemy flagship when
+//! that lane is empty, unless an enemy taunts or a status forces its target.
+//! Ship damage is attack minus defense, at least 1; flagships take the full
+//! attack.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use super::ai::AiDifficulty;
+use super::events::BattleEvent;
+use super::state::{
+    BattlePhase, BattleState, Combatant, Cooldown, Energy, Ship, ShipRef, StatusEffect, Target,
+    LANES,
+};
+use super::{Battle, SIDES};
+use crate::error::{Error, IllegalMove, Result};
+use crate::game::types::{AbilityTrigger, Card, ForcedTarget, Side};
+
+/// Cards drawn before the first turn.
+pub const OPENING_HAND: usize = 4;
+/// Cards a side may keep at the end of its turn.
+pub const HAND_LIMIT: usize = 5;
+/// Energy and energy maximum at the start; the side going second gets one
+/// more energy on its first turn.
+pub const STARTING_ENERGY: i32 = 3;
+/// Energy gained at the start of each turn after a side's first.
+pub const ENERGY_REGENERATION: i32 = 2;
+/// The energy maximum grows by one each turn up to this.
+pub const ENERGY_CAP: i32 = 10;
+/// Hull of the player's flagship; the opponent's gets 2 more per difficulty
+/// level above easy.
+pub const FLAGSHIP_HULL: i32 = 10;
+/// Damage a flagship takes at the start of each turn its side has neither
+/// ships nor cards in hand.
+pub const ELIMINATION_DAMAGE: i32 = 2;
+/// Rounds, each a turn of both sides, before the battle goes to attrition.
+pub const MAX_ROUNDS: u32 = 5;
+
+/// A move of the side whose turn it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Order {
+    PlayCard {
+        hand_index: usize,
+        lane: usize,
+    },
+    ActivateAbility {
+        lane: usize,
+        ability_id: String,
+        target: Option<Target>,
+    },
+    EndTurn,
+}
+
+fn opponent_flagship_hull(difficulty: AiDifficulty) -> i32 {
+    match difficulty {
+        AiDifficulty::Easy => FLAGSHIP_HULL,
+        AiDifficulty::Normal => FLAGSHIP_HULL + 2,
+        AiDifficulty::Hard => FLAGSHIP_HULL + 4,
+    }
+}
+
+impl Battle {
+    /// Sets up a battle between two decks and starts the first turn. Both
+    /// decks are shuffled and opening hands drawn; the side with more
+    /// agility in hand goes first, ties broken at random.
+    pub fn start(
+        battle_id: &str,
+        player_deck: Vec<Card>,
+        opponent_deck: Vec<Card>,
+        difficulty: AiDifficulty,
+        seed: i64,
+    ) -> Self {
+        let energy = Energy {
+            current: STARTING_ENERGY,
+            maximum: STARTING_ENERGY,
+            regeneration: ENERGY_REGENERATION,
+        };
+        let player = Combatant::new(player_deck, FLAGSHIP_HULL, energy);
+        let opponent = Combatant::new(opponent_deck, opponent_flagship_hull(difficulty), energy);
+        let mut battle = Battle::new(BattleState::new(battle_id, player, opponent), seed);
+
+        for side in SIDES {
+            let mut deck = std::mem::take(&mut battle.state.side_mut(side).deck);
+            battle.rng.shuffle(&mut deck);
+            battle.state.side_mut(side).deck = deck;
+            for _ in 0..OPENING_HAND {
+                battle.draw(side);
+            }
+        }
+        let agility = |side| -> i32 {
+            let hand = &battle.state.side(side).hand;
+            hand.iter().map(|card| card.agility).sum()
+        };
+        let (player_agility, opponent_agility) = (agility(Side::Player), agility(Side::Opponent));
+        let first_side = match player_agility.cmp(&opponent_agility) {
+            Ordering::Greater => Side::Player,
+            Ordering::Less => Side::Opponent,
+            Ordering::Equal => SIDES[battle.rng.below(2) as usize],
+        };
+        battle.state.side_mut(first_side.other()).energy.current += 1;
+        battle.emit(BattleEvent::InitiativeDetermined {
+            first_side,
+            player_agility,
+            opponent_agility,
+        });
+        battle.start_turn(first_side);
+        battle
+    }
+
+    /// Deploys the card at `hand_index` of the active side's hand to
+    /// `lane`, paying its energy cost, and fires its `onDeploy` abilities.
+    pub fn play_card(&mut self, hand_index: usize, lane: usize) -> Result<()> {
+        self.ensure_playing()?;
+        let side = self.state.active_side;
+        let combatant = self.state.side(side);
+        let Some(card) = combatant.hand.get(hand_index) else {
+            return Err(Error::IllegalMove(IllegalMove::NotInHand));
+        };
+        if lane >= LANES {
+            return Err(Error::IllegalMove(IllegalMove::NoSuchLane));
+        }
+        if combatant.lanes[lane].is_some() {
+            return Err(Error::IllegalMove(IllegalMove::LaneOccupied));
+        }
+        let energy_cost = card.energy_cost;
+        self.spend(side, energy_cost)?;
+
+        let combatant = self.state.side_mut(side);
+        let card = combatant.hand.remove(hand_index);
+        let card_id = card.id.clone();
+        let mut ship = Ship::new(card);
+        ship.exhausted = true;
+        combatant.lanes[lane] = Some(ship);
+        let at = ShipRef { side, lane };
+        self.emit(BattleEvent::CardDeployed {
+            ship: at,
+            card_id,
+            energy_cost,
+        });
+        self.trigger(at, AbilityTrigger::OnDeploy, None);
+        self.state.side_mut(side).actions_this_turn += 1;
+        self.check_flagships();
+        Ok(())
+    }
+
+    /// Activates the ability `ability_id` of the active side's ship in
+    /// `lane`, paying its energy cost; `target` picks the target of a
+    /// single-target ability. The ability then cools down for its
+    /// `cooldown` turns, and at least until the side's next turn.
+    pub fn activate_ability(
+        &mut self,
+        lane: usize,
+        ability_id: &str,
+        target: Option<Target>,
+    ) -> Result<()> {
+        self.ensure_playing()?;
+        let at = ShipRef {
+            side: self.state.active_side,
+            lane,
+        };
+        let Some(ship) = self.state.ship(at) else {
+            return Err(Error::IllegalMove(IllegalMove::NoShip));
+        };
+        let Some(ability) = ship
+            .card
+            .abilities
+            .iter()
+            .find(|ability| {
+                ability.id == ability_id && ability.trigger == AbilityTrigger::Activated
+            })
+            .cloned()
+        else {
+            return Err(Error::IllegalMove(IllegalMove::NoSuchAbility));
+        };
+        if !ship.abilities_active() {
+            return Err(Error::IllegalMove(IllegalMove::AbilitiesInactive));
+        }
+        if ship
+            .cooldowns
+            .iter()
+            .any(|cooldown| cooldown.ability_id == ability.id)
+        {
+            return Err(Error::IllegalMove(IllegalMove::OnCooldown));
+        }
+        let energy_cost = ability.energy_cost.unwrap_or(0);
+        self.spend(at.side, energy_cost)?;
+
+        let Some(ship) = self.state.ship_mut(at) else {
+            return Ok(());
+        };
+        ship.cooldowns.push(Cooldown {
+            ability_id: ability.id.clone(),
+            remaining: ability.cooldown.unwrap_or(1).max(1),
+        });
+        let card_id = ship.card.id.clone();
+        self.emit(BattleEvent::AbilityActivated {
+            ship: at,
+            card_id,
+            ability_id: ability.id.clone(),
+            energy_cost,
+        });
+        self.resolve(at, &ability, AbilityTrigger::Activated, target);
+        self.state.side_mut(at.side).actions_this_turn += 1;
+        self.check_flagships();
+        Ok(())
+    }
+
+    pub fn execute(&mut self, order: &Order) -> Result<()> {
+        match order {
+            Order::PlayCard { hand_index, lane } => self.play_card(*hand_index, *lane),
+            Order::ActivateAbility {
+                lane,
+                ability_id,
+                target,
+            } => self.activate_ability(*lane, ability_id, *target),
+            Order::EndTurn => self.end_turn(),
+        }
+    }
+
+    /// Ends the active side's turn. Its ready ships attack in lane order,
+    /// then it discards down to [`HAND_LIMIT`] from the most recently drawn
+    /// card, fires its `endTurn` abilities, counts down its statuses and
+    /// readies its ships. After [`MAX_ROUNDS`] the battle is decided by the
+    /// hull left; otherwise the other side's turn starts.
+    pub fn end_turn(&mut self) -> Result<()> {
+        self.ensure_playing()?;
+        let side = self.state.active_side;
+        for attacker in self.state.afloat(side) {
+            self.attack_with(attacker);
+            if self.state.phase == BattlePhase::Resolved {
+                return Ok(());
+            }
+        }
+
+        while self.state.side(side).hand.len() > HAND_LIMIT {
+            let combatant = self.state.side_mut(side);
+            let Some(card) = combatant.hand.pop() else {
+                break;
+            };
+            let card_id = card.id.clone();
+            combatant.discard.push(card);
+            self.emit(BattleEvent::CardDiscarded { side, card_id });
+        }
+        self.trigger_side(side, AbilityTrigger::EndTurn);
+        self.expire_statuses(side);
+        for ship in self.state.side_mut(side).lanes.iter_mut().flatten() {
+            ship.exhausted = false;
+        }
+        let turn = self.state.turn;
+        self.emit(BattleEvent::TurnEnded { turn, side });
+
+        self.check_flagships();
+        if self.state.phase == BattlePhase::Resolved {
+            return Ok(());
+        }
+        if turn >= 2 * MAX_ROUNDS {
+            self.attrition();
+        } else {
+            self.start_turn(side.other());
+        }
+        Ok(())
+    }
+
+    /// Runs the start phase of `side`'s turn. A side's first turn is played
+    /// with its starting energy and opening hand.
+    fn start_turn(&mut self, side: Side) {
+        self.state.turn += 1;
+        self.state.active_side = side;
+        self.state.destroyed_this_turn = 0;
+        self.state.side_mut(side).actions_this_turn = 0;
+        let turn = self.state.turn;
+        self.emit(BattleEvent::TurnStarted { turn, side });
+        if turn > 2 {
+            let energy = &mut self.state.side_mut(side).energy;
+            energy.maximum = (energy.maximum + 1).min(ENERGY_CAP);
+            let regeneration = energy.regeneration;
+            self.gain_energy(side, regeneration);
+            self.draw(side);
+        }
+        self.trigger_side(side, AbilityTrigger::StartTurn);
+
+        if self.state.afloat(side).is_empty() && self.state.side(side).hand.is_empty() {
+            self.emit(BattleEvent::FleetEliminated { side });
+            self.damage(Target::Flagship { side }, ELIMINATION_DAMAGE, None);
+        }
+        self.check_flagships();
+    }
+
+    /// Has the ship at `at` attack, if it is ready and not stunned.
+    fn attack_with(&mut self, at: ShipRef) {
+        let Some(ship) = self.state.ship(at) else {
+            return;
+        };
+        if ship.hull <= 0
+            || ship.exhausted
+            || ship.has_status(|effect| *effect == StatusEffect::Stunned)
+        {
+            return;
+        }
+        let forced = ship.statuses.iter().find_map(|status| match status.effect {
+            StatusEffect::ForcedTarget { target } => Some(target),
+            _ => None,
+        });
+        let card_id = ship.card.id.clone();
+        let target = self.attack_target(at, forced);
+        self.emit(BattleEvent::AttackDeclared {
+            attacker: at,
+            card_id,
+            target,
+        });
+        self.trigger(at, AbilityTrigger::OnAttack, Some(target));
+        if let Target::Ship(defender) = target {
+            self.trigger(defender, AbilityTrigger::OnDefend, Some(Target::Ship(at)));
+        }
+
+        // The defender's abilities may have sunk or sent back the attacker.
+        if self.state.ship(at).is_none_or(|ship| ship.hull <= 0) {
+            return;
+        }
+        let amount = match target {
+            Target::Ship(defender) => (self.attack(at) - self.defense(defender)).max(1),
+            Target::Flagship { .. } => self.attack(at),
+        };
+        self.damage(target, amount, Some(at));
+        if let Some(ship) = self.state.ship_mut(at) {
+            ship.exhausted = true;
+        }
+        self.state.side_mut(at.side).actions_this_turn += 1;
+        self.check_flagships();
+    }
+
+    /// What the ship at `at` attacks: the target its status forces, else
+    /// its [`lane_target`](Self::lane_target).
+    fn attack_target(&mut self, at: ShipRef, forced: Option<ForcedTarget>) -> Target {
+        match forced {
+            Some(ForcedTarget::Flagship) => {
+                return Target::Flagship {
+                    side: at.side.other(),
+                }
+            }
+            Some(ForcedTarget::Ally) => {
+                let mut allies: Vec<_> = self
+                    .state
+                    .afloat(at.side)
+                    .into_iter()
+                    .map(Target::Ship)
+                    .collect();
+                allies.retain(|&ally| ally != Target::Ship(at));
+                if let Some(ally) = self.pick(&allies) {
+                    return ally;
+                }
+            }
+            None => {}
+        }
+        self.lane_target(at)
+    }
+
+    /// The taunting enemy nearest the lane of the ship at `at`, else the
+    /// enemy in its lane, else the enemy flagship.
+    pub fn lane_target(&self, at: ShipRef) -> Target {
+        let enemy = at.side.other();
+        let enemies = self.state.afloat(enemy);
+        let taunting = enemies
+            .iter()
+            .copied()
+            .filter(|&ship| self.taunting(ship))
+            .min_by_key(|ship| ship.lane.abs_diff(at.lane));
+        if let Some(ship) = taunting {
+            return Target::Ship(ship);
+        }
+        let opposing = ShipRef {
+            side: enemy,
+            lane: at.lane,
+        };
+        if enemies.contains(&opposing) {
+            Target::Ship(opposing)
+        } else {
+            Target::Flagship { side: enemy }
+        }
+    }
+
+    fn spend(&mut self, side: Side, amount: i32) -> Result<()> {
+        let energy = &mut self.state.side_mut(side).energy;
+        if energy.current < amount {
+            return Err(Error::IllegalMove(IllegalMove::NotEnoughEnergy));
+        }
+        energy.current -= amount;
+        let energy = energy.current;
+        if amount > 0 {
+            self.emit(BattleEvent::EnergySpent {
+                side,
+                amount,
+                energy,
+            });
+        }
+        Ok(())
+    }
+
+    fn ensure_playing(&self) -> Result<()> {
+        match self.state.phase {
+            BattlePhase::Playing => Ok(()),
+            BattlePhase::Resolved => Err(Error::IllegalMove(IllegalMove::BattleOver)),
+        }
+    }
+
+    /// Ends the battle once a flagship is destroyed; a draw if both are.
+    fn check_flagships(&mut self) {
+        if self.state.phase == BattlePhase::Resolved {
+            return;
+        }
+        let destroyed: Vec<_> = SIDES
+            .into_iter()
+            .filter(|&side| self.state.side(side).flagship.hull <= 0)
+            .collect();
+        for &side in &destroyed {
+            self.emit(BattleEvent::FlagshipDestroyed { side });
+        }
+        match destroyed[..] {
+            [] => {}
+            [side] => self.finish(Some(side.other())),
+            _ => self.finish(None),
+        }
+    }
+
+    /// Decides the battle on the hull left, flagship and ships together.
+    fn attrition(&mut self) {
+        let hull = |side| -> i32 {
+            let ships: i32 = self
+                .state
+                .afloat(side)
+                .into_iter()
+                .filter_map(|at| self.state.ship(at))
+                .map(|ship| ship.hull)
+                .sum();
+            self.state.side(side).flagship.hull + ships
+        };
+        let (player_hull, opponent_hull) = (hull(Side::Player), hull(Side::Opponent));
+        self.emit(BattleEvent::BattleTimeout {
+            rounds: MAX_ROUNDS,
+            player_hull,
+            opponent_hull,
+        });
+        self.finish(match player_hull.cmp(&opponent_hull) {
+            Ordering::Greater => Some(Side::Player),
+            Ordering::Less => Some(Side::Opponent),
+            Ordering::Equal => None,
+        });
+    }
+
+    fn finish(&mut self, winner: Option<Side>) {
+        self.state.phase = BattlePhase::Resolved;
+        self.state.winner = winner;
+        self.emit(BattleEvent::BattleEnded { winner });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn card(id: &str, attack: i32, agility: i32, abilities: Value) -> Card {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "faction": "ironveil",
+            "attack": attack,
+            "defense": 1,
+            "hull": 4,
+            "agility": agility,
+            "energyCost": 2,
+            "abilities": abilities,
+        }))
+        .unwrap()
+    }
+
+    fn deck(prefix: &str, agility: i32) -> Vec<Card> {
+        (0..8)
+            .map(|i| card(&format!("{prefix}{i}"), 3, agility, json!([])))
+            .collect()
+    }
+
+    fn ship(side: Side, lane: usize) -> ShipRef {
+        ShipRef { side, lane }
+    }
+
+    #[test]
+    fn setup_deals_hands_and_compensates_the_second_side() {
+        let mut battle = Battle::start("b1", deck("p", 2), deck("o", 4), AiDifficulty::Hard, 7);
+        let state = &battle.state;
+        assert_eq!(state.turn, 1);
+        assert_eq!(state.active_side, Side::Opponent);
+        assert_eq!(state.player.hand.len(), OPENING_HAND);
+        assert_eq!(state.opponent.deck.len(), 4);
+        assert_eq!(state.player.energy.current, STARTING_ENERGY + 1);
+        assert_eq!(state.opponent.energy.current, STARTING_ENERGY);
+        assert_eq!(state.opponent.flagship.hull, 14);
+
+        let events = battle.take_events();
+        assert!(events.contains(&BattleEvent::InitiativeDetermined {
+            first_side: Side::Opponent,
+            player_agility: 8,
+            opponent_agility: 16,
+        }));
+        assert_eq!(
+            Battle::start("b1", deck("p", 2), deck("o", 4), AiDifficulty::Hard, 7).take_events(),
+            events
+        );
+    }
+
+    #[test]
+    fn illegal_moves_leave_the_battle_alone() {
+        let mut battle = Battle::start("b1", deck("p", 4), deck("o", 2), AiDifficulty::Easy, 1);
+        let before = battle.state.clone();
+        let illegal = |result: Result<()>| match result {
+            Err(Error::IllegalMove(reason)) => reason,
+            other => panic!("expected an illegal move, got {other:?}"),
+        };
+        assert_eq!(illegal(battle.play_card(9, 0)), IllegalMove::NotInHand);
+        assert_eq!(illegal(battle.play_card(0, LANES)), IllegalMove::NoSuchLane);
+        assert_eq!(
+            illegal(battle.activate_ability(0, "none", None)),
+            IllegalMove::NoShip
+        );
+        battle.play_card(0, 0).unwrap();
+        let after_deploy = battle.state.clone();
+        assert_eq!(illegal(battle.play_card(0, 0)), IllegalMove::LaneOccupied);
+        assert_eq!(
+            illegal(battle.play_card(0, 1)),
+            IllegalMove::NotEnoughEnergy
+        );
+        assert_eq!(battle.state, after_deploy);
+        assert_ne!(battle.state, before);
+    }
+
+    #[test]
+    fn ships_attack_from_the_turn_after_deployment() {
+        let mut battle = Battle::start("b1", deck("p", 4), deck("o", 2), AiDifficulty::Easy, 1);
+        battle.play_card(0, 2).unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.opponent.flagship.hull, FLAGSHIP_HULL);
+
+        battle.play_card(0, 2).unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.turn, 3);
+        assert_eq!(battle.state.player.energy.current, STARTING_ENERGY - 2 + 2);
+        assert_eq!(battle.state.player.energy.maximum, STARTING_ENERGY + 1);
+        assert_eq!(battle.state.player.hand.len(), OPENING_HAND);
+
+        // Lane 2 faces a ship, lane 3 the empty lane and so the flagship.
+        battle.play_card(0, 3).unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.ship(ship(Side::Opponent, 2)).unwrap().hull, 2);
+        assert_eq!(battle.state.opponent.flagship.hull, FLAGSHIP_HULL);
+        battle.end_turn().unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.opponent.flagship.hull, FLAGSHIP_HULL - 3);
+    }
+
+    #[test]
+    fn taunt_draws_attacks_and_activated_abilities_cool_down() {
+        let taunt = json!({
+            "id": "bulwark",
+            "name": "Bulwark",
+            "trigger": "activated",
+            "energyCost": 1,
+            "targetType": "self",
+            "effect": { "type": "taunt", "duration": 1 },
+            "description": "",
+        });
+        let opponent = vec![card("warden", 0, 2, json!([taunt])); 8];
+        let mut battle = Battle::start("b1", deck("p", 4), opponent, AiDifficulty::Easy, 1);
+        battle.play_card(0, 0).unwrap();
+        battle.end_turn().unwrap();
+        battle.play_card(0, 4).unwrap();
+        battle.end_turn().unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.opponent.flagship.hull, FLAGSHIP_HULL - 3);
+
+        battle.activate_ability(4, "bulwark", None).unwrap();
+        assert!(matches!(
+            battle.activate_ability(4, "bulwark", None),
+            Err(Error::IllegalMove(IllegalMove::OnCooldown))
+        ));
+        battle.end_turn().unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.ship(ship(Side::Opponent, 4)).unwrap().hull, 2);
+        assert_eq!(battle.state.opponent.flagship.hull, FLAGSHIP_HULL - 3);
+
+        // The cooldown ran out at the end of the turn it was used in.
+        battle.activate_ability(4, "bulwark", None).unwrap();
+    }
+
+    #[test]
+    fn the_battle_ends_with_a_flagship_or_on_attrition() {
+        let mut battle = Battle::start("b1", deck("p", 4), deck("o", 2), AiDifficulty::Easy, 1);
+        battle.state.opponent.flagship.hull = 3;
+        battle.play_card(0, 0).unwrap();
+        battle.end_turn().unwrap();
+        battle.end_turn().unwrap();
+        battle.end_turn().unwrap();
+        assert_eq!(battle.state.phase, BattlePhase::Resolved);
+        assert_eq!(battle.state.winner, Some(Side::Player));
+        assert_eq!(
+            battle.take_events().last(),
+            Some(&BattleEvent::BattleEnded {
+                winner: Some(Side::Player)
+            })
+        );
+        assert!(matches!(
+            battle.end_turn(),
+            Err(Error::IllegalMove(IllegalMove::BattleOver))
+        ));
+
+        let mut battle = Battle::start("b1", deck("p", 4), deck("o", 2), AiDifficulty::Easy, 1);
+        battle.state.opponent.flagship.hull = 20;
+        battle.play_card(0, 0).unwrap();
+        while battle.state.phase == BattlePhase::Playing {
+            battle.end_turn().unwrap();
+        }
+        assert_eq!(battle.state.turn, 2 * MAX_ROUNDS);
+        assert_eq!(battle.state.winner, Some(Side::Player));
+    }
+}
@@ -0,0 +1,84 @@
+//! Plays seeded d20 battles of exported card content and prints win rates
+//! per card, faction and fleet template.
+//!
+//! ```sh
+//! cargo run --release --bin balance-sim -- cards.json fleet-templates.json \
+//!     --battles 5000000 --seed 42
+//! ```
+//!
+//! `cards.json` holds `allCards` and `fleet-templates.json` the result of
+//! `getFleetTemplates()`, each as `JSON.stringify` writes them.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use serde::de::DeserializeOwned;
+use space_fortress_lib::balance;
+use space_fortress_lib::game::combat::FLEET_SIZE;
+use space_fortress_lib::game::opponents::FleetTemplate;
+use space_fortress_lib::game::types::Card;
+
+const USAGE: &str =
+    "usage: balance-sim <cards.json> <fleet-templates.json> [--battles N] [--seed S]";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut battles: u64 = 1_000_000;
+    let mut seed: i64 = 1;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--battles" => battles = value(&arg, args.next())?,
+            "--seed" => seed = value(&arg, args.next())?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => paths.push(arg),
+        }
+    }
+    let [cards, templates] = paths.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let cards: Vec<Card> = load(Path::new(cards))?;
+    let templates: Vec<FleetTemplate> = load(Path::new(templates))?;
+    if cards.len() < FLEET_SIZE {
+        return Err(format!(
+            "need at least {FLEET_SIZE} cards, got {}",
+            cards.len()
+        ));
+    }
+    if templates.is_empty() {
+        return Err("need at least one fleet template".to_string());
+    }
+    let short = balance::short_templates(&templates);
+    if !short.is_empty() {
+        return Err(format!(
+            "fleet templates cannot field {FLEET_SIZE} cards: {}",
+            short.join(", ")
+        ));
+    }
+
+    print!("{}", balance::simulate(&cards, &templates, battles, seed));
+    Ok(())
+}
+
+fn value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{flag} needs a number\n{USAGE}"))
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+    serde_json::from_str(&json).map_err(|err| format!("cannot parse {}: {err}", path.display()))
+}
@@ -0,0 +1,52 @@
+//! Connection setup for `game.db`. The schema itself is managed by
+//! `migrations`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+
+use crate::error::Result;
+use crate::migrations;
+
+/// Database file name, resolved against the app config dir like the SQL plugin does.
+pub const DB_FILE: &str = "game.db";
+
+/// Connection string used by the SQL plugin for the same file.
+pub const DB_URL: &str = "sqlite:game.db";
+
+/// Opens (creating if needed) the database at `path` and brings it up to date.
+pub async fn connect(path: &Path) -> Result<SqlitePool> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(5));
+
+    let pool = SqlitePoolOptions::new().connect_with(options).await?;
+    migrations::migrate(&pool).await?;
+    Ok(pool)
+}
+
+/// Opens a fresh, fully migrated in-memory database.
+#[cfg(test)]
+pub async fn connect_in_memory() -> Result<SqlitePool> {
+    let pool = open_in_memory().await?;
+    migrations::migrate(&pool).await?;
+    Ok(pool)
+}
+
+/// Opens a fresh in-memory database without any schema.
+///
+/// The pool is pinned to a single connection that never expires, since every
+/// SQLite connection to `:memory:` sees its own database.
+#[cfg(test)]
+pub async fn open_in_memory() -> Result<SqlitePool> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(SqliteConnectOptions::new().in_memory(true))
+        .await?;
+    Ok(pool)
+}
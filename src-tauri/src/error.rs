@@ -0,0 +1,185 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// Errors returned by the native backend.
+///
+/// Commands serialize these as `{ kind, message }` so the webview can branch on
+/// `kind` instead of parsing SQLite error strings.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("wrong expected version: expected {expected}, stream is at {actual}")]
+    WrongExpectedVersion { expected: i64, actual: i64 },
+    #[error("save game not found: {0}")]
+    SaveNotFound(String),
+    #[error("{events_after} events were recorded after this save")]
+    UnconfirmedDivergence { events_after: i64 },
+    #[error("sequence {sequence} is outside the stream (last sequence {last})")]
+    SequenceOutOfRange { sequence: i64, last: i64 },
+    #[error("stream already exists: {0}")]
+    StreamAlreadyExists(String),
+    #[error("event {index} has unknown type {event_type}")]
+    UnknownEventType { index: usize, event_type: String },
+    #[error("event {index} ({event_type}) is malformed: {reason}")]
+    InvalidEvent {
+        index: usize,
+        event_type: String,
+        reason: String,
+    },
+    #[error("cannot undo: {0}")]
+    UndoRefused(UndoRefusal),
+    #[error("profile not found: {0}")]
+    ProfileNotFound(String),
+    #[error("invalid profile settings: {0}")]
+    InvalidProfileSettings(String),
+    #[error("not a browser database: {0}")]
+    InvalidImport(String),
+    #[error("invalid save archive: {0}")]
+    InvalidArchive(String),
+    #[error("backup failed: {0}")]
+    Backup(String),
+    #[error("backup not found: {0}")]
+    BackupNotFound(String),
+    #[error("invalid backup settings: {0}")]
+    InvalidBackupSettings(String),
+    #[error("no replay for battle: {0}")]
+    ReplayNotFound(String),
+    #[error("no battle in progress: {0}")]
+    BattleNotFound(String),
+    #[error("illegal move: {0}")]
+    IllegalMove(IllegalMove),
+}
+
+/// Why `undo_last_command`, or a `load_game` truncating the stream, left the
+/// stream alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UndoRefusal {
+    EmptyStream,
+    /// The last event predates command ids, so the command is unknown.
+    UntaggedEvent,
+    Ironman,
+    /// The last command changed phase; undo stays within the current phase.
+    PhaseBoundary,
+}
+
+impl fmt::Display for UndoRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UndoRefusal::EmptyStream => "the stream is empty",
+            UndoRefusal::UntaggedEvent => "the last event has no command id",
+            UndoRefusal::Ironman => "ironman runs cannot go back",
+            UndoRefusal::PhaseBoundary => "the last command left the previous phase",
+        })
+    }
+}
+
+/// Why a tactical battle refused a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IllegalMove {
+    BattleOver,
+    NotInHand,
+    NoSuchLane,
+    LaneOccupied,
+    NoShip,
+    NoSuchAbility,
+    /// The ship is stunned or its abilities are disabled.
+    AbilitiesInactive,
+    OnCooldown,
+    NotEnoughEnergy,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IllegalMove::BattleOver => "the battle is over",
+            IllegalMove::NotInHand => "the card is not in hand",
+            IllegalMove::NoSuchLane => "there is no such lane",
+            IllegalMove::LaneOccupied => "the lane is occupied",
+            IllegalMove::NoShip => "there is no ship in the lane",
+            IllegalMove::NoSuchAbility => "the ship has no such activated ability",
+            IllegalMove::AbilitiesInactive => "the ship cannot use abilities",
+            IllegalMove::OnCooldown => "the ability is on cooldown",
+            IllegalMove::NotEnoughEnergy => "not enough energy",
+        })
+    }
+}
+
+impl Error {
+    /// Stable identifier for the error, used as `kind` on the wire.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Database(_) => "database",
+            Error::Migrate(_) => "migrate",
+            Error::Json(_) => "json",
+            Error::Io(_) => "io",
+            Error::WrongExpectedVersion { .. } => "wrongExpectedVersion",
+            Error::SaveNotFound(_) => "saveNotFound",
+            Error::UnconfirmedDivergence { .. } => "unconfirmedDivergence",
+            Error::SequenceOutOfRange { .. } => "sequenceOutOfRange",
+            Error::StreamAlreadyExists(_) => "streamAlreadyExists",
+            Error::UnknownEventType { .. } => "unknownEventType",
+            Error::InvalidEvent { .. } => "invalidEvent",
+            Error::UndoRefused(_) => "undoRefused",
+            Error::ProfileNotFound(_) => "profileNotFound",
+            Error::InvalidProfileSettings(_) => "invalidProfileSettings",
+            Error::InvalidImport(_) => "invalidImport",
+            Error::InvalidArchive(_) => "invalidArchive",
+            Error::Backup(_) => "backup",
+            Error::BackupNotFound(_) => "backupNotFound",
+            Error::InvalidBackupSettings(_) => "invalidBackupSettings",
+            Error::ReplayNotFound(_) => "replayNotFound",
+            Error::BattleNotFound(_) => "battleNotFound",
+            Error::IllegalMove(_) => "illegalMove",
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            Error::WrongExpectedVersion { expected, actual } => {
+                map.serialize_entry("expected", expected)?;
+                map.serialize_entry("actual", actual)?;
+            }
+            Error::UnconfirmedDivergence { events_after } => {
+                map.serialize_entry("eventsAfter", events_after)?;
+            }
+            Error::UnknownEventType { index, event_type } => {
+                map.serialize_entry("index", index)?;
+                map.serialize_entry("eventType", event_type)?;
+            }
+            Error::InvalidEvent {
+                index,
+                event_type,
+                reason,
+            } => {
+                map.serialize_entry("index", index)?;
+                map.serialize_entry("eventType", event_type)?;
+                map.serialize_entry("reason", reason)?;
+            }
+            Error::UndoRefused(reason) => {
+                map.serialize_entry("reason", reason)?;
+            }
+            Error::IllegalMove(reason) => {
+                map.serialize_entry("reason", reason)?;
+            }
+            _ => {}
+        }
+        map.end()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
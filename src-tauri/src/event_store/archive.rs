@@ -0,0 +1,691 @@
+//! Portable save archives: one profile or save slot in a single file that
+//! can be shared or attached to a bug report.
+//!
+//! The archive is a JSON document holding a manifest, its signature and the
+//! archived files:
+//!
+//! - `events.ndjson`, the raw rows of the exported stream prefix, one per line;
+//! - `saves.json`, the exported saves with their previews;
+//! - `snapshot.json`, the stream's snapshot, if one falls within the prefix.
+//!
+//! A profile export also holds every other stream the profile owns (see
+//! `profiles`), each with the same three files under `forks/<n>/`, and
+//! `lineage.json` recording where each of them branched off, if it did.
+//!
+//! The manifest records the app version and a SHA-256 checksum of every
+//! file, and is signed with HMAC-SHA256. The key ships with the app, so the
+//! signature catches edits and corruption but does not prove who made the
+//! archive. Import checks all of it, and that the events form a valid
+//! stream, before writing anything; the archive then becomes a new profile.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::chain::event_hash;
+use super::lineage::new_fork_player_id;
+use super::profiles::{insert_profile, owned_streams, Profile};
+use super::{now, player_stream, validate, EventStore, NewEvent, SaveSlot, StoredEvent};
+use crate::error::{Error, Result};
+
+/// Version of the archive layout written by this build.
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+const SIGNING_KEY: &[u8] = b"space-fortress/save-archive";
+
+const EVENTS_FILE: &str = "events.ndjson";
+const SAVES_FILE: &str = "saves.json";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const LINEAGE_FILE: &str = "lineage.json";
+
+/// What to export.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ExportScope {
+    /// Every stream of the profile, forks included, with their saves.
+    Profile { profile_id: String },
+    /// The stream prefix recorded by one save.
+    Save {
+        profile_id: String,
+        save_name: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub format: u32,
+    pub app_version: String,
+    pub exported_at: String,
+    pub profile_name: String,
+    /// Set when a single save was exported.
+    pub save_name: Option<String>,
+    pub event_count: i64,
+    /// SHA-256 of each file, hex-encoded.
+    pub checksums: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedArchive {
+    pub profile: Profile,
+    /// Events imported across all streams.
+    pub events: i64,
+    pub saves: u64,
+    pub snapshots: u64,
+    /// Forked streams imported along with the main one.
+    pub forks: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    manifest: ArchiveManifest,
+    signature: String,
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedEvent {
+    sequence: i64,
+    event_type: String,
+    event_data: String,
+    metadata: Option<String>,
+    created_at: String,
+}
+
+/// Saves point at a sequence rather than an event id, since ids are
+/// reassigned on import.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedSave {
+    save_name: String,
+    sequence: i64,
+    preview: Value,
+    saved_at: String,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedSnapshot {
+    sequence: i64,
+    state_data: String,
+    schema_version: i64,
+}
+
+/// Stream `stream` of the archive, branched off stream `parent` where 0 is
+/// the main stream, or a game of its own when `parent` is `None`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedLineage {
+    stream: usize,
+    parent: Option<usize>,
+    forked_at_sequence: i64,
+}
+
+struct ArchivedStream {
+    events: Vec<ArchivedEvent>,
+    saves: Vec<ArchivedSave>,
+    snapshot: Option<ArchivedSnapshot>,
+}
+
+impl EventStore {
+    /// Writes `scope` to a new archive at `path`.
+    pub async fn export_save_archive(
+        &self,
+        scope: &ExportScope,
+        path: &Path,
+    ) -> Result<ArchiveManifest> {
+        let (profile_id, save_name) = match scope {
+            ExportScope::Profile { profile_id } => (profile_id, None),
+            ExportScope::Save {
+                profile_id,
+                save_name,
+            } => (profile_id, Some(save_name)),
+        };
+        let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE profile_id = ?")
+            .bind(profile_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| Error::ProfileNotFound(profile_id.clone()))?;
+
+        let mut saves = self.list_save_games(profile_id).await?;
+        // Each exported stream with the sequence it is exported up to and its saves.
+        let mut streams: Vec<(String, i64, Vec<SaveSlot>)> = Vec::new();
+        let mut lineage = Vec::new();
+        match save_name {
+            Some(name) => {
+                let save = saves
+                    .into_iter()
+                    .find(|save| save.save_name == *name)
+                    .ok_or_else(|| Error::SaveNotFound(name.clone()))?;
+                streams.push((player_stream(&save.player_id), save.sequence, vec![save]));
+            }
+            None => {
+                // Parents come before the streams forked from them.
+                for stream_id in owned_streams(&self.pool, profile_id).await? {
+                    let last = self.stream_version(&stream_id).await?;
+                    let (own, rest) = saves
+                        .into_iter()
+                        .partition(|save| player_stream(&save.player_id) == stream_id);
+                    saves = rest;
+                    streams.push((stream_id, last, own));
+                }
+                if streams.is_empty() {
+                    streams.push((player_stream(profile_id), 0, Vec::new()));
+                }
+                let links = self.stream_lineage().await?;
+                for (stream, (stream_id, _, _)) in streams.iter().enumerate().skip(1) {
+                    let link = links.iter().find(|link| link.stream_id == *stream_id);
+                    lineage.push(ArchivedLineage {
+                        stream,
+                        parent: link.and_then(|link| {
+                            streams.iter().position(|s| s.0 == link.parent_stream_id)
+                        }),
+                        forked_at_sequence: link.map_or(0, |link| link.forked_at_sequence),
+                    });
+                }
+            }
+        }
+
+        let mut files = BTreeMap::new();
+        let mut event_count = 0;
+        for (index, (stream_id, last, saves)) in streams.into_iter().enumerate() {
+            let events = sqlx::query_as::<_, StoredEvent>(
+                "SELECT * FROM events WHERE stream_id = ? AND sequence <= ? ORDER BY sequence",
+            )
+            .bind(&stream_id)
+            .bind(last)
+            .fetch_all(&self.pool)
+            .await?;
+            let snapshot = sqlx::query_as::<_, ArchivedSnapshot>(
+                "SELECT sequence, state_data, schema_version FROM snapshots
+                 WHERE stream_id = ? AND sequence <= ?",
+            )
+            .bind(&stream_id)
+            .bind(last)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            let mut ndjson = String::new();
+            for event in &events {
+                ndjson += &serde_json::to_string(&ArchivedEvent {
+                    sequence: event.sequence,
+                    event_type: event.event_type.clone(),
+                    event_data: event.event_data.clone(),
+                    metadata: event.metadata.clone(),
+                    created_at: event.created_at.clone(),
+                })?;
+                ndjson.push('\n');
+            }
+            files.insert(stream_file(index, EVENTS_FILE), ndjson);
+            let saves: Vec<_> = saves
+                .into_iter()
+                .map(|save| ArchivedSave {
+                    save_name: save.save_name,
+                    sequence: save.sequence,
+                    preview: save.preview,
+                    saved_at: save.saved_at,
+                })
+                .collect();
+            files.insert(
+                stream_file(index, SAVES_FILE),
+                serde_json::to_string(&saves)?,
+            );
+            if let Some(snapshot) = &snapshot {
+                files.insert(
+                    stream_file(index, SNAPSHOT_FILE),
+                    serde_json::to_string(snapshot)?,
+                );
+            }
+            event_count += events.len() as i64;
+        }
+        if !lineage.is_empty() {
+            files.insert(LINEAGE_FILE.to_string(), serde_json::to_string(&lineage)?);
+        }
+
+        let manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: now(),
+            profile_name: profile.name,
+            save_name: save_name.cloned(),
+            event_count,
+            checksums: files
+                .iter()
+                .map(|(name, contents)| (name.clone(), checksum(contents)))
+                .collect(),
+        };
+        let archive = Archive {
+            signature: hex::encode(sign(&manifest)?.finalize().into_bytes()),
+            manifest,
+            files,
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&archive)?)?;
+        Ok(archive.manifest)
+    }
+
+    /// Checks the archive at `path` and imports it as a new profile.
+    ///
+    /// Fails with [`Error::InvalidArchive`] when the archive is unreadable,
+    /// of an unknown format, or does not match its manifest, and with the
+    /// errors of [`append_events`](Self::append_events) when an event is not
+    /// a valid `GameEvent`. Nothing is written in either case.
+    pub async fn import_save_archive(&self, path: &Path) -> Result<ImportedArchive> {
+        let archive: Archive = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|err| invalid(format!("unreadable archive: {err}")))?;
+        archive.verify()?;
+
+        let lineage: Vec<ArchivedLineage> = archive.parse(LINEAGE_FILE)?.unwrap_or_default();
+        let mut streams = vec![archive.stream(0)?];
+        for (index, link) in (1..).zip(&lineage) {
+            if link.stream != index {
+                return Err(invalid(format!("fork {} is out of order", link.stream)));
+            }
+            if let Some(parent) = link.parent {
+                let parent_last = streams
+                    .get(parent)
+                    .map(|parent| parent.events.len() as i64)
+                    .ok_or_else(|| invalid(format!("fork {index} has no parent")))?;
+                if !(0..=parent_last).contains(&link.forked_at_sequence) {
+                    return Err(invalid(format!(
+                        "fork {index} starts past its parent's last event"
+                    )));
+                }
+            }
+            streams.push(archive.stream(index)?);
+        }
+        let event_count: i64 = streams.iter().map(|s| s.events.len() as i64).sum();
+        if event_count != archive.manifest.event_count {
+            return Err(invalid(format!(
+                "manifest lists {} events, found {event_count}",
+                archive.manifest.event_count
+            )));
+        }
+        for stream in &streams {
+            self.validate_archived(&stream.events)?;
+        }
+
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let profile = insert_profile(&mut *tx, &archive.manifest.profile_name).await?;
+        let mut player_ids = vec![profile.profile_id.clone()];
+        for _ in &lineage {
+            player_ids.push(new_fork_player_id(&mut *tx).await?);
+        }
+        for link in &lineage {
+            let Some(parent) = link.parent else {
+                continue;
+            };
+            sqlx::query(
+                "INSERT INTO stream_lineage (stream_id, parent_stream_id, forked_at_sequence)
+                 VALUES (?, ?, ?)",
+            )
+            .bind(player_stream(&player_ids[link.stream]))
+            .bind(player_stream(&player_ids[parent]))
+            .bind(link.forked_at_sequence)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut imported = ImportedArchive {
+            profile,
+            events: event_count,
+            saves: 0,
+            snapshots: 0,
+            forks: lineage.iter().filter(|link| link.parent.is_some()).count() as u64,
+        };
+        for (player_id, stream) in player_ids.iter().zip(&streams) {
+            let stream_id = player_stream(player_id);
+            sqlx::query("INSERT INTO stream_profiles (stream_id, profile_id) VALUES (?, ?)")
+                .bind(&stream_id)
+                .bind(&imported.profile.profile_id)
+                .execute(&mut *tx)
+                .await?;
+            let mut event_ids = Vec::with_capacity(stream.events.len());
+            let mut prev_hash = String::new();
+            for event in &stream.events {
+                let hash = event_hash(
+                    &prev_hash,
+                    event.sequence,
+                    &event.event_type,
+                    &event.event_data,
+                    event.metadata.as_deref(),
+                );
+                let event_id: String = sqlx::query_scalar(
+                    "INSERT INTO events
+                         (stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                     RETURNING event_id",
+                )
+                .bind(&stream_id)
+                .bind(&event.event_type)
+                .bind(&event.event_data)
+                .bind(&event.metadata)
+                .bind(event.sequence)
+                .bind(&event.created_at)
+                .bind(&prev_hash)
+                .bind(&hash)
+                .fetch_one(&mut *tx)
+                .await?;
+                event_ids.push(event_id);
+                prev_hash = hash;
+            }
+
+            for save in &stream.saves {
+                let last_event_id = match save.sequence {
+                    0 => "",
+                    sequence => &event_ids[sequence as usize - 1],
+                };
+                sqlx::query(
+                    "INSERT INTO save_games
+                         (profile_id, save_name, player_id, last_event_id, preview_data, saved_at)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(&imported.profile.profile_id)
+                .bind(&save.save_name)
+                .bind(player_id)
+                .bind(last_event_id)
+                .bind(save.preview.to_string())
+                .bind(&save.saved_at)
+                .execute(&mut *tx)
+                .await?;
+                imported.saves += 1;
+            }
+
+            if let Some(snapshot) = &stream.snapshot {
+                sqlx::query(
+                    "INSERT INTO snapshots (stream_id, sequence, state_data, schema_version)
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind(&stream_id)
+                .bind(snapshot.sequence)
+                .bind(&snapshot.state_data)
+                .bind(snapshot.schema_version)
+                .execute(&mut *tx)
+                .await?;
+                imported.snapshots += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(imported)
+    }
+
+    /// Checks the archived events the way `append_events` checks new ones,
+    /// after bringing them up to their current shapes.
+    fn validate_archived(&self, events: &[ArchivedEvent]) -> Result<()> {
+        let mut current = Vec::with_capacity(events.len());
+        for event in events {
+            let event = self.upcasters.upcast(StoredEvent {
+                event_id: String::new(),
+                stream_id: String::new(),
+                event_type: event.event_type.clone(),
+                event_data: event.event_data.clone(),
+                metadata: event.metadata.clone(),
+                sequence: event.sequence,
+                created_at: event.created_at.clone(),
+            });
+            current.push(NewEvent {
+                data: serde_json::from_str(&event.event_data).map_err(|err| {
+                    invalid(format!("event {} is not JSON: {err}", event.sequence))
+                })?,
+                event_type: event.event_type,
+            });
+        }
+        validate(&current)
+    }
+}
+
+impl Archive {
+    /// The files of stream `index`, checked to form a valid stream with its
+    /// saves and snapshot inside it.
+    fn stream(&self, index: usize) -> Result<ArchivedStream> {
+        let events_file = stream_file(index, EVENTS_FILE);
+        let events = self
+            .files
+            .get(&events_file)
+            .map(String::as_str)
+            .unwrap_or_default()
+            .lines()
+            .map(serde_json::from_str::<ArchivedEvent>)
+            .collect::<serde_json::Result<Vec<_>>>()
+            .map_err(|err| invalid(format!("{events_file}: {err}")))?;
+        let saves: Vec<ArchivedSave> = self
+            .parse(&stream_file(index, SAVES_FILE))?
+            .unwrap_or_default();
+        let snapshot: Option<ArchivedSnapshot> = self.parse(&stream_file(index, SNAPSHOT_FILE))?;
+
+        let last = events.len() as i64;
+        if let Some((_, event)) = (1..).zip(&events).find(|(n, event)| event.sequence != *n) {
+            return Err(invalid(format!(
+                "event {} of {events_file} is out of sequence",
+                event.sequence
+            )));
+        }
+        let mut sequences = saves
+            .iter()
+            .map(|save| save.sequence)
+            .chain(snapshot.iter().map(|snapshot| snapshot.sequence));
+        if let Some(sequence) = sequences.find(|sequence| !(0..=last).contains(sequence)) {
+            return Err(invalid(format!(
+                "sequence {sequence} is past the last event of {events_file}"
+            )));
+        }
+        Ok(ArchivedStream {
+            events,
+            saves,
+            snapshot,
+        })
+    }
+
+    fn verify(&self) -> Result<()> {
+        if self.manifest.format != ARCHIVE_FORMAT {
+            return Err(invalid(format!(
+                "unsupported format {}",
+                self.manifest.format
+            )));
+        }
+        let signature =
+            hex::decode(&self.signature).map_err(|_| invalid("malformed signature".into()))?;
+        sign(&self.manifest)?
+            .verify_slice(&signature)
+            .map_err(|_| invalid("signature does not match the manifest".into()))?;
+
+        if !self.files.keys().eq(self.manifest.checksums.keys()) {
+            return Err(invalid("files do not match the manifest".into()));
+        }
+        for (name, contents) in &self.files {
+            if checksum(contents) != self.manifest.checksums[name] {
+                return Err(invalid(format!("checksum mismatch for {name}")));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse<T: for<'de> Deserialize<'de>>(&self, name: &str) -> Result<Option<T>> {
+        self.files
+            .get(name)
+            .map(|contents| serde_json::from_str(contents))
+            .transpose()
+            .map_err(|err| invalid(format!("{name}: {err}")))
+    }
+}
+
+/// Name of `file` for stream `index`: the main stream's files sit at the
+/// top, each fork's under `forks/<index>/`.
+fn stream_file(index: usize, file: &str) -> String {
+    match index {
+        0 => file.to_string(),
+        index => format!("forks/{index}/{file}"),
+    }
+}
+
+fn sign(manifest: &ArchiveManifest) -> Result<Hmac<Sha256>> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(SIGNING_KEY).expect("HMAC accepts keys of any length");
+    mac.update(&serde_json::to_vec(manifest)?);
+    Ok(mac)
+}
+
+fn checksum(contents: &str) -> String {
+    hex::encode(Sha256::digest(contents.as_bytes()))
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidArchive(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::bounty_modified;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn archive_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "space-fortress-{}-{name}.sfsave",
+            std::process::id()
+        ))
+    }
+
+    async fn store() -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        let events: Vec<_> = (1..=3).map(bounty_modified).collect();
+        store
+            .append_events("player-1", None, None, &events)
+            .await
+            .unwrap();
+        store
+            .save_game("1", "before the ambush", &json!({ "bounty": 6 }))
+            .await
+            .unwrap();
+        store
+            .save_snapshot("player-1", 2, &json!({ "bounty": 3 }), 1)
+            .await
+            .unwrap();
+        store
+            .append_events("player-1", None, None, &[bounty_modified(4)])
+            .await
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn a_save_round_trips_into_a_new_profile() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let path = archive_path("round-trip");
+            let scope = ExportScope::Save {
+                profile_id: "1".to_string(),
+                save_name: "before the ambush".to_string(),
+            };
+
+            let manifest = store.export_save_archive(&scope, &path).await.unwrap();
+            assert_eq!(manifest.event_count, 3);
+            assert_eq!(manifest.profile_name, "Player 1");
+            let imported = store.import_save_archive(&path).await.unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert_eq!(imported.events, 3);
+            assert_eq!(imported.snapshots, 1);
+            let profile_id = &imported.profile.profile_id;
+            let stream_id = player_stream(profile_id);
+            assert!(store
+                .verify_stream(&stream_id)
+                .await
+                .unwrap()
+                .first_broken
+                .is_none());
+            let saves = store.list_save_games(profile_id).await.unwrap();
+            assert_eq!(saves[0].save_name, "before the ambush");
+            assert_eq!(saves[0].sequence, 3);
+            let state = store.load_stream_state(&stream_id, 1).await.unwrap();
+            assert_eq!(state.snapshot.unwrap().sequence, 2);
+            assert_eq!(state.events.len(), 1);
+        });
+    }
+
+    #[test]
+    fn tampered_archives_are_rejected_before_writing() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let path = archive_path("tampered");
+            let scope = ExportScope::Profile {
+                profile_id: "1".to_string(),
+            };
+            store.export_save_archive(&scope, &path).await.unwrap();
+            let original: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+
+            let mut edited_file = original.clone();
+            edited_file["files"][SAVES_FILE] = json!("[]");
+            let mut edited_manifest = original;
+            edited_manifest["manifest"]["eventCount"] = json!(1);
+
+            for (archive, reason) in [
+                (edited_file, "checksum mismatch"),
+                (edited_manifest, "signature"),
+            ] {
+                std::fs::write(&path, archive.to_string()).unwrap();
+                match store.import_save_archive(&path).await {
+                    Err(Error::InvalidArchive(message)) => {
+                        assert!(message.contains(reason), "{message}")
+                    }
+                    other => panic!("expected an invalid archive, got {other:?}"),
+                }
+            }
+            let _ = std::fs::remove_file(&path);
+            assert_eq!(store.list_profiles().await.unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn a_profile_exports_with_its_forks() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            store.fork_stream("player-1", 2, "player-2").await.unwrap();
+            store
+                .append_events("player-2", None, None, &[bounty_modified(9)])
+                .await
+                .unwrap();
+            store
+                .save_game("2", "what if", &json!({ "bounty": 9 }))
+                .await
+                .unwrap();
+            let path = archive_path("forks");
+            let scope = ExportScope::Profile {
+                profile_id: "1".to_string(),
+            };
+
+            let manifest = store.export_save_archive(&scope, &path).await.unwrap();
+            assert_eq!(manifest.event_count, 4 + 3);
+            let imported = store.import_save_archive(&path).await.unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert_eq!((imported.forks, imported.saves), (1, 2));
+            let profile_id = &imported.profile.profile_id;
+            let tree = store.save_tree(profile_id).await.unwrap();
+            assert_eq!(tree[0].stream_id, player_stream(profile_id));
+            assert_eq!(tree[0].saves[0].save_name, "before the ambush");
+            let fork = &tree[0].branches[0];
+            assert_eq!(fork.forked_at_sequence, Some(2));
+            assert_eq!(fork.saves[0].save_name, "what if");
+            assert_eq!(fork.saves[0].sequence, 3);
+            assert!(store
+                .verify_stream(&fork.stream_id)
+                .await
+                .unwrap()
+                .first_broken
+                .is_none());
+        });
+    }
+}
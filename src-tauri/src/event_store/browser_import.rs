@@ -0,0 +1,591 @@
+//! Import of the database kept by the web build.
+//!
+//! The web build runs sql.js and persists the whole database as one blob in
+//! IndexedDB under `space_fortress_db`. Exported, that blob is an ordinary
+//! SQLite file with the `events`, `snapshots` and `save_games` tables of
+//! `BrowserEventStore.ts`. Importing merges those rows into `game.db` in one
+//! transaction, without touching anything already there:
+//!
+//! - every browser player becomes a new profile, and its `player-<id>`
+//!   stream moves to that profile's main stream;
+//! - any other stream keeps its id unless the id is taken, in which case it
+//!   is renamed, and is claimed like a new stream (see `profiles`);
+//! - an event id that is taken is replaced by a fresh one;
+//! - an event that fails the checks of `append_events` is left out with the
+//!   rest of its stream, and so are saves and snapshots past what remains;
+//! - the web build's ISO timestamps are stored the way SQLite's `datetime()`
+//!   writes them, like native rows, so date queries cover imported rows.
+//!
+//! Each rename or omission is reported as an [`ImportConflict`]. Imported
+//! events are linked into hash chains as they are inserted, so the streams
+//! verify.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+
+use super::chain::event_hash;
+use super::profiles::{claim_stream, insert_profile, Profile};
+use super::{player_stream, stream_player_id, validate, EventStore, NewEvent, StoredEvent};
+use crate::error::{Error, Result};
+
+const BROWSER_TABLES: [&str; 3] = ["events", "snapshots", "save_games"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ImportConflict {
+    /// The stream id was in use, so the stream was imported under another.
+    StreamRenamed {
+        stream_id: String,
+        new_stream_id: String,
+    },
+    /// The event id was in use, so the event was imported under another.
+    EventRenamed {
+        event_id: String,
+        new_event_id: String,
+    },
+    /// The event is not a valid `GameEvent`, so it was left out together
+    /// with the events after it in its stream.
+    EventRejected {
+        stream_id: String,
+        event_id: String,
+        sequence: i64,
+        reason: String,
+    },
+    /// The save points at an event that was not imported, so it was left out.
+    SaveSkipped {
+        save_name: String,
+        last_event_id: String,
+    },
+    /// The snapshot is past the last imported event of its stream, so it
+    /// was left out.
+    SnapshotSkipped { stream_id: String, sequence: i64 },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    /// One new profile per browser player.
+    pub profiles: Vec<Profile>,
+    pub events: u64,
+    pub snapshots: u64,
+    pub saves: u64,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+#[derive(sqlx::FromRow)]
+struct BrowserSnapshot {
+    stream_id: String,
+    sequence: i64,
+    state_data: String,
+    schema_version: i64,
+    created_at: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct BrowserSave {
+    save_name: String,
+    player_id: String,
+    last_event_id: String,
+    preview_data: String,
+    saved_at: String,
+}
+
+impl EventStore {
+    /// Merges the sql.js database exported to `path` into the store.
+    ///
+    /// Fails with [`Error::InvalidImport`] if the file lacks one of the
+    /// browser store's tables; nothing is written in that case.
+    pub async fn import_browser_database(&self, path: &Path) -> Result<ImportReport> {
+        let mut source = open_browser_database(path).await?;
+        let events = sqlx::query_as::<_, StoredEvent>(
+            "SELECT event_id, stream_id, event_type, event_data, metadata, sequence, created_at
+             FROM events ORDER BY stream_id, sequence",
+        )
+        .fetch_all(&mut source)
+        .await?;
+        let snapshots = sqlx::query_as::<_, BrowserSnapshot>(
+            "SELECT stream_id, sequence, state_data, schema_version, created_at FROM snapshots",
+        )
+        .fetch_all(&mut source)
+        .await?;
+        let saves = sqlx::query_as::<_, BrowserSave>(
+            "SELECT save_name, player_id, last_event_id, preview_data, saved_at FROM save_games
+             ORDER BY save_name",
+        )
+        .fetch_all(&mut source)
+        .await?;
+        source.close().await?;
+
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut report = ImportReport::default();
+
+        let players: BTreeSet<&str> = events
+            .iter()
+            .filter_map(|event| stream_player_id(&event.stream_id))
+            .chain(saves.iter().map(|save| save.player_id.as_str()))
+            .collect();
+        let mut profiles = HashMap::new();
+        for player_id in players {
+            let profile = insert_profile(&mut *tx, &format!("Browser player {player_id}")).await?;
+            profiles.insert(player_id, profile.profile_id.clone());
+            report.profiles.push(profile);
+        }
+
+        let source_streams: BTreeSet<&str> = events
+            .iter()
+            .map(|event| event.stream_id.as_str())
+            .collect();
+        let mut streams = HashMap::new();
+        let mut assigned = HashSet::new();
+        for stream_id in source_streams {
+            let target = match stream_player_id(stream_id) {
+                Some(player_id) => player_stream(&profiles[player_id]),
+                None => {
+                    let target = free_stream_id(&mut tx, stream_id, &assigned).await?;
+                    if target != stream_id {
+                        report.conflicts.push(ImportConflict::StreamRenamed {
+                            stream_id: stream_id.to_string(),
+                            new_stream_id: target.clone(),
+                        });
+                    }
+                    target
+                }
+            };
+            assigned.insert(target.clone());
+            streams.insert(stream_id, target);
+        }
+
+        let mut event_ids = HashMap::new();
+        let mut imported: HashMap<&str, i64> = HashMap::new();
+        let mut rejected = HashSet::new();
+        let mut chained_stream = "";
+        let mut prev_hash = String::new();
+        for event in &events {
+            if rejected.contains(event.stream_id.as_str()) {
+                continue;
+            }
+            if let Some(reason) = self.rejection(event) {
+                rejected.insert(event.stream_id.as_str());
+                report.conflicts.push(ImportConflict::EventRejected {
+                    stream_id: event.stream_id.clone(),
+                    event_id: event.event_id.clone(),
+                    sequence: event.sequence,
+                    reason,
+                });
+                continue;
+            }
+            let stream_id = streams[event.stream_id.as_str()].as_str();
+            if stream_id != chained_stream {
+                chained_stream = stream_id;
+                prev_hash.clear();
+            }
+            let taken: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM events WHERE event_id = ?)")
+                    .bind(&event.event_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let event_id = if taken {
+                let fresh: String = sqlx::query_scalar("SELECT lower(hex(randomblob(16)))")
+                    .fetch_one(&mut *tx)
+                    .await?;
+                report.conflicts.push(ImportConflict::EventRenamed {
+                    event_id: event.event_id.clone(),
+                    new_event_id: fresh.clone(),
+                });
+                fresh
+            } else {
+                event.event_id.clone()
+            };
+
+            let hash = event_hash(
+                &prev_hash,
+                event.sequence,
+                &event.event_type,
+                &event.event_data,
+                event.metadata.as_deref(),
+            );
+            sqlx::query(
+                "INSERT INTO events
+                     (event_id, stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash)
+                 VALUES (?, ?, ?, ?, ?, ?, COALESCE(datetime(?), ?), ?, ?)",
+            )
+            .bind(&event_id)
+            .bind(stream_id)
+            .bind(&event.event_type)
+            .bind(&event.event_data)
+            .bind(&event.metadata)
+            .bind(event.sequence)
+            .bind(&event.created_at)
+            .bind(&event.created_at)
+            .bind(&prev_hash)
+            .bind(&hash)
+            .execute(&mut *tx)
+            .await?;
+            prev_hash = hash;
+            event_ids.insert(event.event_id.as_str(), event_id);
+            imported.insert(event.stream_id.as_str(), event.sequence);
+            report.events += 1;
+        }
+        for stream_id in imported.keys() {
+            claim_stream(&mut *tx, &streams[stream_id]).await?;
+        }
+
+        for snapshot in &snapshots {
+            let stream_id = snapshot.stream_id.as_str();
+            let last = imported.get(stream_id).copied().unwrap_or(0);
+            let Some(target) = streams.get(stream_id).filter(|_| snapshot.sequence <= last) else {
+                report.conflicts.push(ImportConflict::SnapshotSkipped {
+                    stream_id: snapshot.stream_id.clone(),
+                    sequence: snapshot.sequence,
+                });
+                continue;
+            };
+            sqlx::query(
+                "INSERT OR REPLACE INTO snapshots
+                     (stream_id, sequence, state_data, schema_version, created_at)
+                 VALUES (?, ?, ?, ?, COALESCE(datetime(?), ?))",
+            )
+            .bind(target)
+            .bind(snapshot.sequence)
+            .bind(&snapshot.state_data)
+            .bind(snapshot.schema_version)
+            .bind(&snapshot.created_at)
+            .bind(&snapshot.created_at)
+            .execute(&mut *tx)
+            .await?;
+            report.snapshots += 1;
+        }
+
+        for save in &saves {
+            let last_event_id = if save.last_event_id.is_empty() {
+                String::new()
+            } else if let Some(event_id) = event_ids.get(save.last_event_id.as_str()) {
+                event_id.clone()
+            } else {
+                report.conflicts.push(ImportConflict::SaveSkipped {
+                    save_name: save.save_name.clone(),
+                    last_event_id: save.last_event_id.clone(),
+                });
+                continue;
+            };
+            // The profile id is also the player id of its main stream.
+            let profile_id = &profiles[save.player_id.as_str()];
+            sqlx::query(
+                "INSERT INTO save_games
+                     (profile_id, save_name, player_id, last_event_id, preview_data, saved_at)
+                 VALUES (?, ?, ?, ?, ?, COALESCE(datetime(?), ?))",
+            )
+            .bind(profile_id)
+            .bind(&save.save_name)
+            .bind(profile_id)
+            .bind(last_event_id)
+            .bind(&save.preview_data)
+            .bind(&save.saved_at)
+            .bind(&save.saved_at)
+            .execute(&mut *tx)
+            .await?;
+            report.saves += 1;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+
+    /// Why `event` would fail the checks of `append_events` once upcast, if
+    /// it would.
+    fn rejection(&self, event: &StoredEvent) -> Option<String> {
+        let event = self.upcasters.upcast(event.clone());
+        let data = match serde_json::from_str(&event.event_data) {
+            Ok(data) => data,
+            Err(err) => return Some(format!("not JSON: {err}")),
+        };
+        let checked = validate(&[NewEvent {
+            event_type: event.event_type,
+            data,
+        }]);
+        match checked {
+            Ok(()) => None,
+            Err(Error::UnknownEventType { event_type, .. }) => {
+                Some(format!("unknown event type {event_type}"))
+            }
+            Err(Error::InvalidEvent { reason, .. }) => Some(reason),
+            Err(err) => Some(err.to_string()),
+        }
+    }
+}
+
+async fn open_browser_database(path: &Path) -> Result<SqliteConnection> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    let tables: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .fetch_all(&mut conn)
+            .await?;
+    if let Some(missing) = BROWSER_TABLES
+        .iter()
+        .find(|table| !tables.iter().any(|name| name == *table))
+    {
+        return Err(Error::InvalidImport(format!("missing table {missing}")));
+    }
+    Ok(conn)
+}
+
+/// `stream_id` if nothing in the store uses it yet, otherwise the first free
+/// `<stream_id>-browser-<n>`.
+async fn free_stream_id(
+    conn: &mut SqliteConnection,
+    stream_id: &str,
+    assigned: &HashSet<String>,
+) -> Result<String> {
+    let mut candidate = stream_id.to_string();
+    for n in 1.. {
+        let taken: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM events WHERE stream_id = ?1)
+                 OR EXISTS(SELECT 1 FROM snapshots WHERE stream_id = ?1)
+                 OR EXISTS(SELECT 1 FROM stream_lineage WHERE stream_id = ?1)",
+        )
+        .bind(&candidate)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !taken && !assigned.contains(&candidate) {
+            break;
+        }
+        candidate = format!("{stream_id}-browser-{n}");
+    }
+    Ok(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, EventQuery};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// Schema of `BrowserEventStore.ts`.
+    const BROWSER_SCHEMA: &str = "
+        CREATE TABLE events (
+            event_id TEXT PRIMARY KEY,
+            stream_id TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            event_data TEXT NOT NULL,
+            metadata TEXT,
+            sequence INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(stream_id, sequence)
+        );
+        CREATE TABLE snapshots (
+            stream_id TEXT PRIMARY KEY,
+            sequence INTEGER NOT NULL,
+            state_data TEXT NOT NULL,
+            schema_version INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE save_games (
+            save_name TEXT PRIMARY KEY,
+            player_id TEXT NOT NULL,
+            last_event_id TEXT NOT NULL,
+            preview_data TEXT NOT NULL,
+            saved_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );";
+
+    async fn browser_database(name: &str, sql: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "space-fortress-{}-{name}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let mut conn = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::raw_sql(sql).execute(&mut conn).await.unwrap();
+        conn.close().await.unwrap();
+        path
+    }
+
+    #[test]
+    fn merges_browser_rows_into_a_new_profile() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let native = store
+                .append_events("player-1", None, None, &[bounty_modified(1)])
+                .await
+                .unwrap();
+            store
+                .append_events("ghost_1", None, None, &[bounty_modified(1)])
+                .await
+                .unwrap();
+
+            let data = bounty_modified(5).data.to_string();
+            let path = browser_database(
+                "merge",
+                &format!(
+                    "{BROWSER_SCHEMA}
+                     INSERT INTO events (event_id, stream_id, event_type, event_data, sequence) VALUES
+                         ('{taken}', 'player-1', 'BOUNTY_MODIFIED', '{data}', 1),
+                         ('b2', 'player-1', 'BOUNTY_MODIFIED', '{data}', 2),
+                         ('g1', 'ghost_1', 'BOUNTY_MODIFIED', '{data}', 1);
+                     INSERT INTO snapshots (stream_id, sequence, state_data) VALUES ('player-1', 2, '{{}}');
+                     INSERT INTO save_games (save_name, player_id, last_event_id, preview_data) VALUES
+                         ('autosave', '1', 'b2', '{{\"bounty\":5}}'),
+                         ('broken', '1', 'gone', '{{}}');",
+                    taken = native[0].event_id,
+                ),
+            )
+            .await;
+
+            let report = store.import_browser_database(&path).await.unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert_eq!(report.profiles.len(), 1);
+            assert_eq!((report.events, report.snapshots, report.saves), (3, 1, 1));
+            let kinds: Vec<_> = report
+                .conflicts
+                .iter()
+                .map(|c| serde_json::to_value(c).unwrap()["kind"].clone())
+                .collect();
+            assert_eq!(kinds, ["streamRenamed", "eventRenamed", "saveSkipped"]);
+            assert_eq!(
+                report.conflicts[0],
+                ImportConflict::StreamRenamed {
+                    stream_id: "ghost_1".to_string(),
+                    new_stream_id: "ghost_1-browser-1".to_string(),
+                }
+            );
+
+            let profile_id = &report.profiles[0].profile_id;
+            let stream_id = player_stream(profile_id);
+            assert_eq!(store.stream_version(&stream_id).await.unwrap(), 2);
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 1);
+            assert!(store
+                .verify_stream(&stream_id)
+                .await
+                .unwrap()
+                .first_broken
+                .is_none());
+            assert!(store.load_snapshot(&stream_id, 1).await.unwrap().is_some());
+
+            let saves = store.list_save_games(profile_id).await.unwrap();
+            assert_eq!(saves.len(), 1);
+            assert_eq!(saves[0].sequence, 2);
+            assert_eq!(saves[0].preview, json!({ "bounty": 5 }));
+        });
+    }
+
+    #[test]
+    fn rejects_a_file_without_the_browser_tables() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let path = browser_database("foreign", "CREATE TABLE notes (body TEXT);").await;
+
+            let result = store.import_browser_database(&path).await;
+            let _ = std::fs::remove_file(&path);
+
+            assert!(matches!(result, Err(Error::InvalidImport(_))));
+            assert!(store.list_profiles().await.unwrap().len() == 1);
+        });
+    }
+
+    #[test]
+    fn leaves_out_invalid_events_and_orphan_snapshots() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let data = bounty_modified(5).data.to_string();
+            let path = browser_database(
+                "invalid",
+                &format!(
+                    "{BROWSER_SCHEMA}
+                     INSERT INTO events (event_id, stream_id, event_type, event_data, sequence) VALUES
+                         ('a1', 'player-1', 'BOUNTY_MODIFIED', '{data}', 1),
+                         ('a2', 'player-1', 'BOUNTY_CHANGED', '{data}', 2),
+                         ('a3', 'player-1', 'BOUNTY_MODIFIED', '{data}', 3);
+                     INSERT INTO snapshots (stream_id, sequence, state_data) VALUES
+                         ('player-1', 3, '{{}}'),
+                         ('player-2', 0, '{{}}');
+                     INSERT INTO save_games (save_name, player_id, last_event_id, preview_data) VALUES
+                         ('early', '1', 'a1', '{{}}'),
+                         ('late', '1', 'a3', '{{}}');",
+                ),
+            )
+            .await;
+
+            let report = store.import_browser_database(&path).await.unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert_eq!((report.events, report.snapshots, report.saves), (1, 0, 1));
+            assert_eq!(
+                report.conflicts[0],
+                ImportConflict::EventRejected {
+                    stream_id: "player-1".to_string(),
+                    event_id: "a2".to_string(),
+                    sequence: 2,
+                    reason: "unknown event type BOUNTY_CHANGED".to_string(),
+                }
+            );
+            let kinds: Vec<_> = report
+                .conflicts
+                .iter()
+                .map(|c| serde_json::to_value(c).unwrap()["kind"].clone())
+                .collect();
+            assert_eq!(
+                kinds,
+                [
+                    "eventRejected",
+                    "snapshotSkipped",
+                    "snapshotSkipped",
+                    "saveSkipped"
+                ]
+            );
+            assert!(store.check_store(false).await.unwrap().problems.is_empty());
+        });
+    }
+
+    #[test]
+    fn iso_timestamps_fall_in_date_ranges() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let data = bounty_modified(5).data.to_string();
+            let path = browser_database(
+                "timestamps",
+                &format!(
+                    "{BROWSER_SCHEMA}
+                     INSERT INTO events (event_id, stream_id, event_type, event_data, sequence, created_at) VALUES
+                         ('t1', 'player-1', 'BOUNTY_MODIFIED', '{data}', 1, '2024-06-01T12:30:00.000Z'),
+                         ('t2', 'player-1', 'BOUNTY_MODIFIED', '{data}', 2, '2024-06-02T08:00:00.000Z');
+                     INSERT INTO save_games (save_name, player_id, last_event_id, preview_data, saved_at) VALUES
+                         ('autosave', '1', 't2', '{{}}', '2024-06-02T08:00:00.000Z');",
+                ),
+            )
+            .await;
+
+            let report = store.import_browser_database(&path).await.unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            let page = store
+                .query_events(&EventQuery {
+                    created_from: Some("2024-06-01".to_string()),
+                    created_to: Some("2024-06-02".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(page.events.len(), 1);
+            assert_eq!(page.events[0].created_at, "2024-06-01 12:30:00");
+            let saves = store
+                .list_save_games(&report.profiles[0].profile_id)
+                .await
+                .unwrap();
+            assert_eq!(saves[0].saved_at, "2024-06-02 08:00:00");
+        });
+    }
+}
@@ -0,0 +1,250 @@
+//! Tamper-evident hash chain over each stream.
+//!
+//! Every appended row stores `hash = sha256(prev_hash, sequence, event_type,
+//! event_data, metadata)`, where `prev_hash` is the hash of the row before it
+//! in the same stream (empty for the first). Editing, dropping or reordering a
+//! row by hand breaks the link at that row and every hash after it.
+//!
+//! The stream id is deliberately not hashed, so a forked prefix keeps a valid
+//! chain. Rows written before the chain existed have no hash and are skipped;
+//! `chain_legacy` records how far they run in each stream, so clearing the
+//! hashes of later rows does not pass for legacy data.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::EventStore;
+use crate::error::Result;
+
+/// Why a row does not continue the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChainFault {
+    /// The row has no hash but was written after the chain existed.
+    MissingHash,
+    /// The row's `prev_hash` is not the hash of the row before it.
+    PrevHashMismatch,
+    /// The row's contents no longer match its hash.
+    HashMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    pub event_id: String,
+    pub sequence: i64,
+    pub fault: ChainFault,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainReport {
+    pub stream_id: String,
+    /// Rows whose hash was checked, i.e. excluding unhashed legacy rows.
+    pub verified: i64,
+    /// The first row that breaks the chain, if any.
+    pub first_broken: Option<BrokenLink>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ChainRow {
+    event_id: String,
+    event_type: String,
+    event_data: String,
+    metadata: Option<String>,
+    sequence: i64,
+    prev_hash: Option<String>,
+    hash: Option<String>,
+}
+
+/// Hash of one row given the hash of the row before it.
+pub(crate) fn event_hash(
+    prev_hash: &str,
+    sequence: i64,
+    event_type: &str,
+    event_data: &str,
+    metadata: Option<&str>,
+) -> String {
+    let mut hasher = Sha256::new();
+    for field in [
+        prev_hash.as_bytes(),
+        sequence.to_string().as_bytes(),
+        event_type.as_bytes(),
+        event_data.as_bytes(),
+        metadata.unwrap_or_default().as_bytes(),
+    ] {
+        // Length-prefixed so that shifting bytes between fields changes the hash.
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    hex::encode(hasher.finalize())
+}
+
+impl EventStore {
+    /// Walks `stream_id` in sequence order and reports the first row that
+    /// breaks the hash chain.
+    pub async fn verify_stream(&self, stream_id: &str) -> Result<ChainReport> {
+        let rows = sqlx::query_as::<_, ChainRow>(
+            "SELECT event_id, event_type, event_data, metadata, sequence, prev_hash, hash
+             FROM events WHERE stream_id = ? ORDER BY sequence",
+        )
+        .bind(stream_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let legacy: i64 =
+            sqlx::query_scalar("SELECT last_sequence FROM chain_legacy WHERE stream_id = ?")
+                .bind(stream_id)
+                .fetch_optional(&self.pool)
+                .await?
+                .unwrap_or(0);
+
+        let mut report = ChainReport {
+            stream_id: stream_id.to_string(),
+            verified: 0,
+            first_broken: None,
+        };
+        let mut previous: Option<String> = None;
+        for row in rows {
+            let fault = match (&row.hash, &previous) {
+                (None, None) if row.sequence <= legacy => continue,
+                (None, _) => Some(ChainFault::MissingHash),
+                (Some(hash), _) => {
+                    let prev_hash = previous.as_deref().unwrap_or_default();
+                    if row.prev_hash.as_deref().unwrap_or_default() != prev_hash {
+                        Some(ChainFault::PrevHashMismatch)
+                    } else if *hash
+                        != event_hash(
+                            prev_hash,
+                            row.sequence,
+                            &row.event_type,
+                            &row.event_data,
+                            row.metadata.as_deref(),
+                        )
+                    {
+                        Some(ChainFault::HashMismatch)
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let Some(fault) = fault {
+                report.first_broken = Some(BrokenLink {
+                    event_id: row.event_id,
+                    sequence: row.sequence,
+                    fault,
+                });
+                break;
+            }
+            report.verified += 1;
+            previous = row.hash;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::bounty_modified;
+
+    async fn store_with_events(count: i64) -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        let events: Vec<_> = (0..count).map(bounty_modified).collect();
+        store
+            .append_events("player-1", None, None, &events)
+            .await
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn intact_chain_verifies_and_survives_a_fork() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(4).await;
+            store
+                .append_events("player-1", None, None, &[bounty_modified(9)])
+                .await
+                .unwrap();
+            store.fork_stream("player-1", 3, "player-2").await.unwrap();
+
+            let report = store.verify_stream("player-1").await.unwrap();
+            assert_eq!(report.verified, 5);
+            assert!(report.first_broken.is_none());
+            let forked = store.verify_stream("player-2").await.unwrap();
+            assert_eq!(forked.verified, 3);
+            assert!(forked.first_broken.is_none());
+        });
+    }
+
+    #[test]
+    fn reports_the_first_edited_row() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(4).await;
+            sqlx::query(
+                "UPDATE events SET event_data = replace(event_data, '\"amount\":2', '\"amount\":2000')
+                 WHERE stream_id = 'player-1' AND sequence = 3",
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+            let broken = store
+                .verify_stream("player-1")
+                .await
+                .unwrap()
+                .first_broken
+                .unwrap();
+            assert_eq!(broken.sequence, 3);
+            assert_eq!(broken.fault, ChainFault::HashMismatch);
+        });
+    }
+
+    #[test]
+    fn reports_a_removed_row_and_skips_legacy_rows() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            sqlx::query(
+                "INSERT INTO events (stream_id, event_type, event_data, sequence)
+                 VALUES ('player-1', 'FLAG_SET', '{}', 1);
+                 INSERT INTO chain_legacy (stream_id, last_sequence) VALUES ('player-1', 1);",
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+            let events: Vec<_> = (0..3).map(bounty_modified).collect();
+            store
+                .append_events("player-1", None, None, &events)
+                .await
+                .unwrap();
+            sqlx::query("DELETE FROM events WHERE stream_id = 'player-1' AND sequence = 3")
+                .execute(&store.pool)
+                .await
+                .unwrap();
+
+            let report = store.verify_stream("player-1").await.unwrap();
+            assert_eq!(report.verified, 1);
+            let broken = report.first_broken.unwrap();
+            assert_eq!(broken.sequence, 4);
+            assert_eq!(broken.fault, ChainFault::PrevHashMismatch);
+        });
+    }
+
+    #[test]
+    fn a_stream_stripped_of_its_hashes_is_not_intact() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(3).await;
+            sqlx::query("UPDATE events SET prev_hash = NULL, hash = NULL")
+                .execute(&store.pool)
+                .await
+                .unwrap();
+
+            let report = store.verify_stream("player-1").await.unwrap();
+            assert_eq!(report.verified, 0);
+            let broken = report.first_broken.unwrap();
+            assert_eq!(broken.sequence, 1);
+            assert_eq!(broken.fault, ChainFault::MissingHash);
+        });
+    }
+}
@@ -0,0 +1,286 @@
+//! Tauri commands exposing the [`EventStore`] to the webview.
+
+use std::path::Path;
+
+use serde_json::Value;
+use tauri::{AppHandle, State};
+
+use super::{
+    emit_appended, ArchiveManifest, ChainReport, Divergence, EventPage, EventQuery, EventStore,
+    ExportScope, ImportReport, ImportedArchive, IntegrityReport, LoadedGame, NewEvent, Profile,
+    ProfileSettings, SaveBranch, SaveSlot, Snapshot, StoredEvent, StreamState, UndoneCommand,
+};
+use crate::error::Result;
+
+#[tauri::command]
+pub async fn append_events(
+    app: AppHandle,
+    store: State<'_, EventStore>,
+    stream_id: String,
+    expected_version: Option<i64>,
+    command_id: Option<String>,
+    events: Vec<NewEvent>,
+) -> Result<Vec<StoredEvent>> {
+    let stored = store
+        .append_events(&stream_id, expected_version, command_id.as_deref(), &events)
+        .await?;
+    emit_appended(&app, &stream_id, &stored);
+    Ok(stored)
+}
+
+#[tauri::command]
+pub async fn read_stream(
+    store: State<'_, EventStore>,
+    stream_id: String,
+) -> Result<Vec<StoredEvent>> {
+    store.read_stream(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn read_stream_since(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    after_sequence: i64,
+) -> Result<Vec<StoredEvent>> {
+    store.read_stream_since(&stream_id, after_sequence).await
+}
+
+#[tauri::command]
+pub async fn delete_stream(store: State<'_, EventStore>, stream_id: String) -> Result<u64> {
+    store.delete_stream(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn stream_version(store: State<'_, EventStore>, stream_id: String) -> Result<i64> {
+    store.stream_version(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn save_snapshot(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    sequence: i64,
+    state: Value,
+    schema_version: i64,
+) -> Result<()> {
+    store
+        .save_snapshot(&stream_id, sequence, &state, schema_version)
+        .await
+}
+
+#[tauri::command]
+pub async fn load_snapshot(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    schema_version: i64,
+) -> Result<Option<Snapshot>> {
+    store.load_snapshot(&stream_id, schema_version).await
+}
+
+#[tauri::command]
+pub async fn load_stream_state(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    schema_version: i64,
+) -> Result<StreamState> {
+    store.load_stream_state(&stream_id, schema_version).await
+}
+
+#[tauri::command]
+pub async fn delete_snapshot(store: State<'_, EventStore>, stream_id: String) -> Result<()> {
+    store.delete_snapshot(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn invalidate_snapshots(
+    store: State<'_, EventStore>,
+    schema_version: i64,
+) -> Result<u64> {
+    store.invalidate_snapshots(schema_version).await
+}
+
+#[tauri::command]
+pub async fn save_game(
+    store: State<'_, EventStore>,
+    player_id: String,
+    save_name: String,
+    preview: Value,
+) -> Result<()> {
+    store.save_game(&player_id, &save_name, &preview).await
+}
+
+#[tauri::command]
+pub async fn list_save_games(
+    store: State<'_, EventStore>,
+    profile_id: String,
+) -> Result<Vec<SaveSlot>> {
+    store.list_save_games(&profile_id).await
+}
+
+#[tauri::command]
+pub async fn load_game(
+    store: State<'_, EventStore>,
+    profile_id: String,
+    save_name: String,
+    divergence: Option<Divergence>,
+) -> Result<LoadedGame> {
+    store.load_game(&profile_id, &save_name, divergence).await
+}
+
+#[tauri::command]
+pub async fn delete_save_game(
+    store: State<'_, EventStore>,
+    profile_id: String,
+    save_name: String,
+) -> Result<()> {
+    store.delete_save_game(&profile_id, &save_name).await
+}
+
+#[tauri::command]
+pub async fn fork_stream(
+    store: State<'_, EventStore>,
+    source_stream: String,
+    at_sequence: i64,
+    new_stream: String,
+) -> Result<i64> {
+    store
+        .fork_stream(&source_stream, at_sequence, &new_stream)
+        .await
+}
+
+#[tauri::command]
+pub async fn save_tree(
+    store: State<'_, EventStore>,
+    profile_id: String,
+) -> Result<Vec<SaveBranch>> {
+    store.save_tree(&profile_id).await
+}
+
+#[tauri::command]
+pub async fn verify_stream(store: State<'_, EventStore>, stream_id: String) -> Result<ChainReport> {
+    store.verify_stream(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn check_store(
+    store: State<'_, EventStore>,
+    repair: Option<bool>,
+) -> Result<IntegrityReport> {
+    store.check_store(repair.unwrap_or(false)).await
+}
+
+#[tauri::command]
+pub async fn query_events(store: State<'_, EventStore>, query: EventQuery) -> Result<EventPage> {
+    store.query_events(&query).await
+}
+
+#[tauri::command]
+pub async fn undo_last_command(
+    store: State<'_, EventStore>,
+    stream_id: String,
+) -> Result<UndoneCommand> {
+    store.undo_last_command(&stream_id).await
+}
+
+#[tauri::command]
+pub async fn profile_settings(
+    store: State<'_, EventStore>,
+    profile_id: String,
+) -> Result<ProfileSettings> {
+    store.profile_settings(&profile_id).await
+}
+
+#[tauri::command]
+pub async fn update_profile_settings(
+    store: State<'_, EventStore>,
+    profile_id: String,
+    settings: ProfileSettings,
+) -> Result<()> {
+    store.update_profile_settings(&profile_id, &settings).await
+}
+
+#[tauri::command]
+pub async fn create_profile(store: State<'_, EventStore>, name: String) -> Result<Profile> {
+    store.create_profile(&name).await
+}
+
+#[tauri::command]
+pub async fn rename_profile(
+    store: State<'_, EventStore>,
+    profile_id: String,
+    name: String,
+) -> Result<Profile> {
+    store.rename_profile(&profile_id, &name).await
+}
+
+#[tauri::command]
+pub async fn list_profiles(store: State<'_, EventStore>) -> Result<Vec<Profile>> {
+    store.list_profiles().await
+}
+
+#[tauri::command]
+pub async fn select_profile(store: State<'_, EventStore>, profile_id: String) -> Result<Profile> {
+    store.select_profile(&profile_id).await
+}
+
+#[tauri::command]
+pub async fn selected_profile(store: State<'_, EventStore>) -> Result<Option<Profile>> {
+    store.selected_profile().await
+}
+
+#[tauri::command]
+pub async fn delete_profile(store: State<'_, EventStore>, profile_id: String) -> Result<()> {
+    store.delete_profile(&profile_id).await
+}
+
+#[tauri::command]
+pub async fn import_browser_database(
+    store: State<'_, EventStore>,
+    path: String,
+) -> Result<ImportReport> {
+    store.import_browser_database(Path::new(&path)).await
+}
+
+#[tauri::command]
+pub async fn export_save_archive(
+    store: State<'_, EventStore>,
+    scope: ExportScope,
+    path: String,
+) -> Result<ArchiveManifest> {
+    store.export_save_archive(&scope, Path::new(&path)).await
+}
+
+#[tauri::command]
+pub async fn import_save_archive(
+    store: State<'_, EventStore>,
+    path: String,
+) -> Result<ImportedArchive> {
+    store.import_save_archive(Path::new(&path)).await
+}
+
+#[tauri::command]
+pub async fn seed_stream_rng(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    seed: i64,
+) -> Result<()> {
+    store.seed_stream_rng(&stream_id, seed).await
+}
+
+#[tauri::command]
+pub async fn roll_d20(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    count: u32,
+) -> Result<Vec<i32>> {
+    store.roll_d20(&stream_id, count).await
+}
+
+#[tauri::command]
+pub async fn shuffle(
+    store: State<'_, EventStore>,
+    stream_id: String,
+    items: Vec<Value>,
+) -> Result<Vec<Value>> {
+    store.shuffle(&stream_id, items).await
+}
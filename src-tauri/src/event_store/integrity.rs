@@ -0,0 +1,528 @@
+//! Whole-store integrity check with an opt-in repair.
+//!
+//! The check scans every table the store owns and reports what would make a
+//! stream fail to load or replay. Repair never deletes outright: each bad row
+//! is copied as JSON into `quarantine`, together with the problem found, and
+//! only then removed from its table. Sequence gaps are reported but left
+//! alone, since there is no single row to blame.
+//!
+//! A bad event takes the rest of its stream into quarantine with it, along
+//! with the saves and snapshot pointing into that tail. Removing it alone
+//! would open a gap and break the hash chain of every later event, so the
+//! next check would report problems the repair itself made.
+
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+use super::{player_stream, stream_player_id, EventStore, StoredEvent};
+use crate::battle::BattleEvent;
+use crate::error::Result;
+use crate::game::GameEvent;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Problem {
+    /// `next` follows `after` in the stream without the sequences in between.
+    SequenceGap {
+        stream_id: String,
+        after: i64,
+        next: i64,
+    },
+    InvalidJson {
+        stream_id: String,
+        event_id: String,
+        sequence: i64,
+    },
+    UnknownEventType {
+        stream_id: String,
+        event_id: String,
+        sequence: i64,
+        event_type: String,
+    },
+    /// The save points at an event that is not in the player's stream.
+    OrphanedSave {
+        profile_id: String,
+        save_name: String,
+        last_event_id: String,
+    },
+    SnapshotAhead {
+        stream_id: String,
+        sequence: i64,
+        stream_version: i64,
+    },
+}
+
+impl Problem {
+    /// The tables and keys of the rows to quarantine for the problem.
+    async fn rows(
+        &self,
+        conn: &mut SqliteConnection,
+    ) -> Result<Vec<(&'static QuarantineSource, String)>> {
+        match self {
+            Problem::SequenceGap { .. } => Ok(Vec::new()),
+            Problem::InvalidJson {
+                stream_id,
+                sequence,
+                ..
+            }
+            | Problem::UnknownEventType {
+                stream_id,
+                sequence,
+                ..
+            } => stream_tail(conn, stream_id, *sequence).await,
+            Problem::OrphanedSave {
+                profile_id,
+                save_name,
+                ..
+            } => Ok(vec![(&SAVE_GAMES, format!("{profile_id}/{save_name}"))]),
+            Problem::SnapshotAhead { stream_id, .. } => Ok(vec![(&SNAPSHOTS, stream_id.clone())]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub streams_checked: i64,
+    pub problems: Vec<Problem>,
+    /// Rows moved to `quarantine`; always 0 unless repairing.
+    pub quarantined: u64,
+}
+
+/// How to copy a row of one table into `quarantine`.
+struct QuarantineSource {
+    table: &'static str,
+    key_column: &'static str,
+    row_json: &'static str,
+}
+
+const EVENTS: QuarantineSource = QuarantineSource {
+    table: "events",
+    key_column: "event_id",
+    row_json: "json_object('event_id', event_id, 'stream_id', stream_id, 'event_type', event_type,
+                'event_data', event_data, 'metadata', metadata, 'sequence', sequence,
+                'created_at', created_at, 'prev_hash', prev_hash, 'hash', hash)",
+};
+
+const SAVE_GAMES: QuarantineSource = QuarantineSource {
+    table: "save_games",
+    key_column: "profile_id || '/' || save_name",
+    row_json:
+        "json_object('profile_id', profile_id, 'save_name', save_name, 'player_id', player_id,
+                'last_event_id', last_event_id, 'preview_data', preview_data, 'saved_at', saved_at)",
+};
+
+const SNAPSHOTS: QuarantineSource = QuarantineSource {
+    table: "snapshots",
+    key_column: "stream_id",
+    row_json: "json_object('stream_id', stream_id, 'sequence', sequence, 'state_data', state_data,
+                'schema_version', schema_version, 'created_at', created_at)",
+};
+
+impl EventStore {
+    /// Scans the whole store for problems. With `repair`, every problem tied
+    /// to a row is fixed by quarantining that row, all in one transaction.
+    pub async fn check_store(&self, repair: bool) -> Result<IntegrityReport> {
+        let mut tx = if repair {
+            self.pool.begin_with("BEGIN IMMEDIATE").await?
+        } else {
+            self.pool.begin().await?
+        };
+
+        let streams_checked = sqlx::query_scalar("SELECT COUNT(DISTINCT stream_id) FROM events")
+            .fetch_one(&mut *tx)
+            .await?;
+        let mut problems = sequence_gaps(&mut tx).await?;
+        problems.extend(self.bad_events(&mut tx).await?);
+        problems.extend(orphaned_saves(&mut tx).await?);
+        problems.extend(snapshots_ahead(&mut tx).await?);
+
+        let mut quarantined = 0;
+        if repair {
+            for problem in &problems {
+                for (source, key) in problem.rows(&mut tx).await? {
+                    quarantined += quarantine(&mut tx, source, &key, problem).await?;
+                }
+            }
+        }
+        tx.commit().await?;
+
+        Ok(IntegrityReport {
+            streams_checked,
+            problems,
+            quarantined,
+        })
+    }
+
+    /// Events whose data is not JSON, or whose type, once upcast, is neither a
+    /// `GameEvent` nor a `BattleEvent`.
+    async fn bad_events(&self, conn: &mut SqliteConnection) -> Result<Vec<Problem>> {
+        let rows =
+            sqlx::query_as::<_, StoredEvent>("SELECT * FROM events ORDER BY stream_id, sequence")
+                .fetch_all(&mut *conn)
+                .await?;
+
+        let mut problems = Vec::new();
+        for row in rows {
+            if serde_json::from_str::<Value>(&row.event_data).is_err() {
+                problems.push(Problem::InvalidJson {
+                    stream_id: row.stream_id,
+                    event_id: row.event_id,
+                    sequence: row.sequence,
+                });
+                continue;
+            }
+            let event = self.upcasters.upcast(row);
+            if !GameEvent::is_known_type(&event.event_type)
+                && !BattleEvent::is_known_type(&event.event_type)
+            {
+                problems.push(Problem::UnknownEventType {
+                    stream_id: event.stream_id,
+                    event_id: event.event_id,
+                    sequence: event.sequence,
+                    event_type: event.event_type,
+                });
+            }
+        }
+        Ok(problems)
+    }
+}
+
+async fn sequence_gaps(conn: &mut SqliteConnection) -> Result<Vec<Problem>> {
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT stream_id, after, sequence FROM (
+             SELECT stream_id, sequence,
+                    LAG(sequence, 1, 0) OVER (PARTITION BY stream_id ORDER BY sequence) AS after
+             FROM events
+         )
+         WHERE sequence != after + 1
+         ORDER BY stream_id, sequence",
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(stream_id, after, next)| Problem::SequenceGap {
+            stream_id,
+            after,
+            next,
+        })
+        .collect())
+}
+
+async fn orphaned_saves(conn: &mut SqliteConnection) -> Result<Vec<Problem>> {
+    let saves = sqlx::query_as::<_, (String, String, String, String)>(
+        "SELECT profile_id, save_name, player_id, last_event_id FROM save_games
+         WHERE last_event_id != '' ORDER BY profile_id, save_name",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut problems = Vec::new();
+    for (profile_id, save_name, player_id, last_event_id) in saves {
+        let found: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM events WHERE stream_id = ? AND event_id = ?)",
+        )
+        .bind(player_stream(&player_id))
+        .bind(&last_event_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !found {
+            problems.push(Problem::OrphanedSave {
+                profile_id,
+                save_name,
+                last_event_id,
+            });
+        }
+    }
+    Ok(problems)
+}
+
+async fn snapshots_ahead(conn: &mut SqliteConnection) -> Result<Vec<Problem>> {
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT stream_id, sequence, version FROM (
+             SELECT s.stream_id, s.sequence,
+                    COALESCE((SELECT MAX(e.sequence) FROM events e WHERE e.stream_id = s.stream_id), 0)
+                        AS version
+             FROM snapshots s
+         )
+         WHERE sequence > version
+         ORDER BY stream_id",
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(stream_id, sequence, stream_version)| Problem::SnapshotAhead {
+                stream_id,
+                sequence,
+                stream_version,
+            },
+        )
+        .collect())
+}
+
+/// The events of `stream_id` from `sequence` on, then the saves and the
+/// snapshot that point into them.
+async fn stream_tail(
+    conn: &mut SqliteConnection,
+    stream_id: &str,
+    sequence: i64,
+) -> Result<Vec<(&'static QuarantineSource, String)>> {
+    let events: Vec<String> = sqlx::query_scalar(
+        "SELECT event_id FROM events WHERE stream_id = ? AND sequence >= ? ORDER BY sequence",
+    )
+    .bind(stream_id)
+    .bind(sequence)
+    .fetch_all(&mut *conn)
+    .await?;
+    let saves: Vec<String> = sqlx::query_scalar(
+        "SELECT profile_id || '/' || save_name FROM save_games
+         WHERE player_id = ? AND last_event_id IN (
+             SELECT event_id FROM events WHERE stream_id = ? AND sequence >= ?
+         )
+         ORDER BY profile_id, save_name",
+    )
+    .bind(stream_player_id(stream_id))
+    .bind(stream_id)
+    .bind(sequence)
+    .fetch_all(&mut *conn)
+    .await?;
+    let snapshot: Option<String> =
+        sqlx::query_scalar("SELECT stream_id FROM snapshots WHERE stream_id = ? AND sequence >= ?")
+            .bind(stream_id)
+            .bind(sequence)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    Ok(events
+        .into_iter()
+        .map(|event_id| (&EVENTS, event_id))
+        .chain(saves.into_iter().map(|key| (&SAVE_GAMES, key)))
+        .chain(snapshot.map(|stream_id| (&SNAPSHOTS, stream_id)))
+        .collect())
+}
+
+/// Moves one row into `quarantine`. Returns 0 if an earlier problem already
+/// moved it.
+async fn quarantine(
+    conn: &mut SqliteConnection,
+    source: &QuarantineSource,
+    key: &str,
+    problem: &Problem,
+) -> Result<u64> {
+    let QuarantineSource {
+        table,
+        key_column,
+        row_json,
+    } = source;
+    sqlx::query(&format!(
+        "INSERT INTO quarantine (source_table, row_key, row_data, problem)
+         SELECT '{table}', {key_column}, {row_json}, ? FROM {table} WHERE {key_column} = ?"
+    ))
+    .bind(serde_json::to_string(problem)?)
+    .bind(key)
+    .execute(&mut *conn)
+    .await?;
+    let moved = sqlx::query(&format!("DELETE FROM {table} WHERE {key_column} = ?"))
+        .bind(key)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::ai::AiDifficulty;
+    use crate::battle::{Battle, Battles};
+    use crate::db;
+    use crate::event_store::bounty_modified;
+    use crate::game::types::Card;
+    use serde_json::json;
+
+    async fn execute(store: &EventStore, sql: &str) {
+        sqlx::query(sql).execute(&store.pool).await.unwrap();
+    }
+
+    #[test]
+    fn healthy_store_has_no_problems() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let events: Vec<_> = (0..3).map(bounty_modified).collect();
+            store
+                .append_events("player-1", None, None, &events)
+                .await
+                .unwrap();
+            store
+                .save_game("1", "slot", &json!({ "bounty": 0 }))
+                .await
+                .unwrap();
+            store
+                .save_snapshot("player-1", 3, &json!({}), 1)
+                .await
+                .unwrap();
+
+            let report = store.check_store(false).await.unwrap();
+            assert_eq!(report.streams_checked, 1);
+            assert!(report.problems.is_empty(), "{:?}", report.problems);
+        });
+    }
+
+    #[test]
+    fn tactical_battles_check_clean() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let deck: Vec<Card> = (0..8)
+                .map(|i| {
+                    serde_json::from_value(json!({
+                        "id": format!("c{i}"),
+                        "name": "Corvette",
+                        "faction": "ironveil",
+                        "attack": 3,
+                        "defense": 1,
+                        "hull": 4,
+                        "agility": 2,
+                        "energyCost": 2,
+                        "abilities": [],
+                    }))
+                    .unwrap()
+                })
+                .collect();
+            let battle = Battle::start("b1", deck.clone(), deck, AiDifficulty::Normal, 1);
+            let (_, stored) = Battles::default()
+                .insert(&store, "player-1", battle)
+                .await
+                .unwrap();
+
+            let report = store.check_store(true).await.unwrap();
+            assert!(report.problems.is_empty(), "{:?}", report.problems);
+            assert_eq!(
+                store.stream_version("player-1").await.unwrap(),
+                stored.len() as i64
+            );
+        });
+    }
+
+    #[test]
+    fn finds_problems_and_quarantines_only_when_repairing() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let events: Vec<_> = (0..5).map(bounty_modified).collect();
+            store
+                .append_events("player-1", None, None, &events)
+                .await
+                .unwrap();
+            execute(&store, "DELETE FROM events WHERE sequence = 2").await;
+            execute(
+                &store,
+                "UPDATE events SET event_data = '{oops' WHERE sequence = 4",
+            )
+            .await;
+            execute(
+                &store,
+                "UPDATE events SET event_type = 'BOUNTY_CHANGED' WHERE sequence = 5",
+            )
+            .await;
+            execute(
+                &store,
+                "INSERT INTO save_games (save_name, player_id, last_event_id, preview_data)
+                 VALUES ('lost', '1', 'missing', '{}')",
+            )
+            .await;
+            store
+                .save_snapshot("player-1", 9, &json!({}), 1)
+                .await
+                .unwrap();
+
+            let report = store.check_store(false).await.unwrap();
+            let kinds: Vec<_> = report
+                .problems
+                .iter()
+                .map(|p| serde_json::to_value(p).unwrap()["kind"].clone())
+                .collect();
+            assert_eq!(
+                kinds,
+                [
+                    "sequenceGap",
+                    "invalidJson",
+                    "unknownEventType",
+                    "orphanedSave",
+                    "snapshotAhead"
+                ]
+            );
+            assert_eq!(report.quarantined, 0);
+
+            let repaired = store.check_store(true).await.unwrap();
+            assert_eq!(repaired.quarantined, 4);
+            let (table, problem): (String, String) = sqlx::query_as(
+                "SELECT source_table, problem FROM quarantine ORDER BY quarantine_id LIMIT 1",
+            )
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+            assert_eq!(table, "events");
+            assert!(problem.contains("invalidJson"));
+
+            // Only the gap is left; rows 4 and 5 were the tail of the stream.
+            let after = store.check_store(false).await.unwrap();
+            assert_eq!(
+                after.problems,
+                [Problem::SequenceGap {
+                    stream_id: "player-1".to_string(),
+                    after: 1,
+                    next: 3,
+                }]
+            );
+        });
+    }
+
+    #[test]
+    fn repairing_a_bad_event_mid_stream_quarantines_its_tail() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let events: Vec<_> = (0..5).map(bounty_modified).collect();
+            store
+                .append_events("player-1", None, None, &events)
+                .await
+                .unwrap();
+            store
+                .save_game("1", "late", &json!({ "bounty": 0 }))
+                .await
+                .unwrap();
+            store
+                .save_snapshot("player-1", 4, &json!({}), 1)
+                .await
+                .unwrap();
+            execute(
+                &store,
+                "UPDATE events SET event_data = '{oops' WHERE sequence = 3",
+            )
+            .await;
+
+            let repaired = store.check_store(true).await.unwrap();
+            assert_eq!(repaired.problems.len(), 1);
+            // Events 3 to 5, the save at 5 and the snapshot at 4.
+            assert_eq!(repaired.quarantined, 5);
+
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 2);
+            assert!(store
+                .verify_stream("player-1")
+                .await
+                .unwrap()
+                .first_broken
+                .is_none());
+            let after = store.check_store(false).await.unwrap();
+            assert!(after.problems.is_empty(), "{:?}", after.problems);
+        });
+    }
+}
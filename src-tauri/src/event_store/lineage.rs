@@ -0,0 +1,298 @@
+//! Stream forking and the branch tree built from `stream_lineage`.
+//!
+//! Forking copies a prefix of one stream into a new, empty stream and records
+//! where it branched off. Save slots live on streams, so the lineage doubles
+//! as the tree of alternate playthroughs shown in the save list.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use sqlx::SqliteExecutor;
+
+use super::profiles::{owned_streams, stream_profile};
+use super::rng;
+use super::{last_sequence, player_stream, EventStore, SaveSlot};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamLineage {
+    pub stream_id: String,
+    pub parent_stream_id: String,
+    pub forked_at_sequence: i64,
+    pub created_at: String,
+}
+
+/// One stream in the save tree, with the saves made on it and the streams
+/// forked from it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveBranch {
+    pub stream_id: String,
+    /// Sequence of the parent stream this branch was forked at; `None` for roots.
+    pub forked_at_sequence: Option<i64>,
+    pub saves: Vec<SaveSlot>,
+    pub branches: Vec<SaveBranch>,
+}
+
+impl EventStore {
+    /// Copies events `1..=at_sequence` of `source_stream` into `new_stream`,
+    /// together with the random generator as it stood there, and records the
+    /// parent linkage. The fork belongs to the source's profile. Returns the new stream's version.
+    pub async fn fork_stream(
+        &self,
+        source_stream: &str,
+        at_sequence: i64,
+        new_stream: &str,
+    ) -> Result<i64> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let source_version = last_sequence(&mut *tx, source_stream).await?;
+        if !(0..=source_version).contains(&at_sequence) {
+            return Err(Error::SequenceOutOfRange {
+                sequence: at_sequence,
+                last: source_version,
+            });
+        }
+        // The target must be empty and must not be an ancestor of the source,
+        // which would turn the lineage into a cycle.
+        let is_ancestor: bool = sqlx::query_scalar(
+            "WITH RECURSIVE ancestors(stream_id) AS (
+                 SELECT ?
+                 UNION
+                 SELECT l.parent_stream_id FROM stream_lineage l
+                 JOIN ancestors a ON l.stream_id = a.stream_id
+             )
+             SELECT EXISTS(SELECT 1 FROM ancestors WHERE stream_id = ?)",
+        )
+        .bind(source_stream)
+        .bind(new_stream)
+        .fetch_one(&mut *tx)
+        .await?;
+        if is_ancestor || last_sequence(&mut *tx, new_stream).await? > 0 {
+            return Err(Error::StreamAlreadyExists(new_stream.to_string()));
+        }
+
+        sqlx::query(
+            "INSERT INTO events (stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash)
+             SELECT ?, event_type, event_data, metadata, sequence, created_at, prev_hash, hash
+             FROM events WHERE stream_id = ? AND sequence <= ?
+             ORDER BY sequence",
+        )
+        .bind(new_stream)
+        .bind(source_stream)
+        .bind(at_sequence)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO stream_lineage (stream_id, parent_stream_id, forked_at_sequence)
+             VALUES (?, ?, ?)",
+        )
+        .bind(new_stream)
+        .bind(source_stream)
+        .bind(at_sequence)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO chain_legacy (stream_id, last_sequence)
+             SELECT ?, MIN(last_sequence, ?) FROM chain_legacy WHERE stream_id = ?",
+        )
+        .bind(new_stream)
+        .bind(at_sequence)
+        .bind(source_stream)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO stream_profiles (stream_id, profile_id)
+             VALUES (?, ?)",
+        )
+        .bind(new_stream)
+        .bind(stream_profile(&mut *tx, source_stream).await?)
+        .execute(&mut *tx)
+        .await?;
+        rng::restore_at(&mut tx, source_stream, at_sequence, new_stream).await?;
+
+        tx.commit().await?;
+        Ok(at_sequence)
+    }
+
+    pub async fn stream_lineage(&self) -> Result<Vec<StreamLineage>> {
+        let rows = sqlx::query_as::<_, (String, String, i64, String)>(
+            "SELECT stream_id, parent_stream_id, forked_at_sequence, created_at
+             FROM stream_lineage ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(stream_id, parent_stream_id, forked_at_sequence, created_at)| StreamLineage {
+                    stream_id,
+                    parent_stream_id,
+                    forked_at_sequence,
+                    created_at,
+                },
+            )
+            .collect())
+    }
+
+    /// Arranges the saves of `profile_id` into the tree of streams they
+    /// belong to.
+    pub async fn save_tree(&self, profile_id: &str) -> Result<Vec<SaveBranch>> {
+        let owned: HashSet<String> = owned_streams(&self.pool, profile_id)
+            .await?
+            .into_iter()
+            .collect();
+        let lineage: Vec<_> = self
+            .stream_lineage()
+            .await?
+            .into_iter()
+            .filter(|link| owned.contains(&link.stream_id))
+            .collect();
+
+        let mut saves_by_stream: BTreeMap<String, Vec<SaveSlot>> = BTreeMap::new();
+        for save in self.list_save_games(profile_id).await? {
+            saves_by_stream
+                .entry(player_stream(&save.player_id))
+                .or_default()
+                .push(save);
+        }
+
+        let parents: HashMap<&str, &StreamLineage> = lineage
+            .iter()
+            .map(|link| (link.stream_id.as_str(), link))
+            .collect();
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for link in &lineage {
+            children
+                .entry(link.parent_stream_id.as_str())
+                .or_default()
+                .push(&link.stream_id);
+        }
+
+        // Roots are streams without a recorded parent: those holding saves and
+        // the parents named by lineage rows.
+        let mut roots: Vec<String> = saves_by_stream
+            .keys()
+            .chain(lineage.iter().map(|link| &link.parent_stream_id))
+            .filter(|stream| !parents.contains_key(stream.as_str()))
+            .cloned()
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+
+        Ok(roots
+            .into_iter()
+            .map(|root| build_branch(&root, &parents, &children, &mut saves_by_stream))
+            .collect())
+    }
+}
+
+/// A player id for a new fork: `fork-` and a random suffix, so it never
+/// clashes with the timestamp ids of new games.
+pub(crate) async fn new_fork_player_id<'e>(executor: impl SqliteExecutor<'e>) -> Result<String> {
+    let player_id = sqlx::query_scalar("SELECT 'fork-' || lower(hex(randomblob(8)))")
+        .fetch_one(executor)
+        .await?;
+    Ok(player_id)
+}
+
+fn build_branch(
+    stream_id: &str,
+    parents: &HashMap<&str, &StreamLineage>,
+    children: &HashMap<&str, Vec<&str>>,
+    saves_by_stream: &mut BTreeMap<String, Vec<SaveSlot>>,
+) -> SaveBranch {
+    let branches = children
+        .get(stream_id)
+        .into_iter()
+        .flatten()
+        .map(|child| build_branch(child, parents, children, saves_by_stream))
+        .collect();
+
+    SaveBranch {
+        stream_id: stream_id.to_string(),
+        forked_at_sequence: parents.get(stream_id).map(|link| link.forked_at_sequence),
+        saves: saves_by_stream.remove(stream_id).unwrap_or_default(),
+        branches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::bounty_modified;
+    use serde_json::{json, Value};
+
+    async fn store_with_events(count: usize) -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        let events: Vec<_> = (0..count).map(|i| bounty_modified(i as i64)).collect();
+        store
+            .append_events("player-1", None, None, &events)
+            .await
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn fork_copies_prefix_and_records_parent() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(5).await;
+            store.fork_stream("player-1", 3, "player-2").await.unwrap();
+
+            let forked = store.read_stream("player-2").await.unwrap();
+            assert_eq!(forked.len(), 3);
+            let data: Value = serde_json::from_str(&forked[2].event_data).unwrap();
+            assert_eq!(data["amount"], 2);
+
+            let lineage = store.stream_lineage().await.unwrap();
+            assert_eq!(lineage[0].parent_stream_id, "player-1");
+            assert_eq!(lineage[0].forked_at_sequence, 3);
+        });
+    }
+
+    #[test]
+    fn fork_rejects_bad_targets() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(2).await;
+            assert!(matches!(
+                store.fork_stream("player-1", 3, "player-2").await,
+                Err(Error::SequenceOutOfRange { .. })
+            ));
+            assert!(matches!(
+                store.fork_stream("player-1", 1, "player-1").await,
+                Err(Error::StreamAlreadyExists(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn save_tree_nests_forks_under_their_parent() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(4).await;
+            store.fork_stream("player-1", 2, "player-2").await.unwrap();
+            store.fork_stream("player-2", 1, "player-3").await.unwrap();
+            store
+                .save_game("1", "main", &json!({ "bounty": 0 }))
+                .await
+                .unwrap();
+            store
+                .save_game("3", "what if", &json!({ "bounty": 0 }))
+                .await
+                .unwrap();
+
+            let tree = store.save_tree("1").await.unwrap();
+            assert_eq!(tree.len(), 1);
+            assert_eq!(tree[0].stream_id, "player-1");
+            assert_eq!(tree[0].saves[0].save_name, "main");
+
+            let grandchild = &tree[0].branches[0].branches[0];
+            assert_eq!(grandchild.stream_id, "player-3");
+            assert_eq!(grandchild.forked_at_sequence, Some(1));
+            assert_eq!(grandchild.saves[0].save_name, "what if");
+        });
+    }
+}
@@ -0,0 +1,482 @@
+//! Native event store over the `events` table.
+//!
+//! Every stream is an ordered log keyed by `(stream_id, sequence)`, with
+//! sequences starting at 1. This replaces the raw SQL previously issued from
+//! `SQLiteEventStore.ts`.
+//!
+//! Events are appended in batches, one batch per handled command. A batch is
+//! committed atomically and every event in it carries the same `commandId` in
+//! its metadata, which marks the command boundaries within a stream.
+
+mod archive;
+mod browser_import;
+mod chain;
+pub mod commands;
+mod integrity;
+mod lineage;
+mod notify;
+mod profiles;
+mod query;
+mod rng;
+mod saves;
+mod settings;
+mod snapshots;
+mod undo;
+mod upcast;
+
+pub use archive::{ArchiveManifest, ExportScope, ImportedArchive};
+pub use browser_import::ImportReport;
+pub use chain::ChainReport;
+pub use integrity::IntegrityReport;
+pub use lineage::SaveBranch;
+pub use notify::{emit_appended, EVENTS_APPENDED};
+pub use profiles::Profile;
+pub use query::{EventPage, EventQuery};
+pub use rng::RngRecord;
+pub use saves::{Divergence, LoadedGame, SaveSlot};
+pub use settings::ProfileSettings;
+pub use snapshots::{Snapshot, StreamState};
+pub use undo::UndoneCommand;
+pub use upcast::UpcasterRegistry;
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{SqliteExecutor, SqlitePool};
+
+use crate::battle::BattleEvent;
+use crate::error::{Error, Result};
+use crate::game::GameEvent;
+use chain::event_hash;
+use profiles::claim_stream;
+
+/// An event as produced by `decide()`, i.e. a `GameEvent` from `events.ts`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: Value,
+}
+
+/// A persisted row of the `events` table, shaped like `StoredEvent` in `types.ts`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct StoredEvent {
+    pub event_id: String,
+    pub stream_id: String,
+    pub event_type: String,
+    pub event_data: String,
+    pub metadata: Option<String>,
+    pub sequence: i64,
+    pub created_at: String,
+}
+
+/// Contents of the `metadata` column.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventMetadata {
+    pub timestamp: String,
+    /// Id shared by all events appended for the same command. Absent on
+    /// events written before batches were tagged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<String>,
+    /// Version of the event's `data` shape. Absent means version 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
+    /// Seeded draws the stream made since its previous append, set on the
+    /// first event of a batch only. See `rng`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rng: Option<RngRecord>,
+}
+
+/// Handle to the event tables of `game.db`, managed as Tauri state.
+pub struct EventStore {
+    pool: SqlitePool,
+    upcasters: UpcasterRegistry,
+}
+
+impl EventStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            upcasters: UpcasterRegistry::game(),
+        }
+    }
+
+    /// Appends the events of one command to the end of `stream_id` and returns
+    /// the stored rows.
+    ///
+    /// The batch is tagged with `command_id`, or a freshly generated id when
+    /// none is given, and runs in one write transaction: either every event is
+    /// stored or none is. When `expected_version` is given, it must equal the
+    /// stream's current last sequence (0 for a new stream), otherwise nothing
+    /// is written and [`Error::WrongExpectedVersion`] is returned.
+    ///
+    /// Each row is linked into the stream's hash chain, see `chain`.
+    ///
+    /// Every event must be a well-formed [`GameEvent`] or [`BattleEvent`];
+    /// the first one that is not fails the whole batch with
+    /// [`Error::UnknownEventType`] or [`Error::InvalidEvent`] before anything
+    /// is written. Each event's
+    /// metadata is stamped with the current schema version of its type, see
+    /// [`UpcasterRegistry`], and the first event's with the stream's pending
+    /// random draws, see [`RngRecord`].
+    pub async fn append_events(
+        &self,
+        stream_id: &str,
+        expected_version: Option<i64>,
+        command_id: Option<&str>,
+        events: &[NewEvent],
+    ) -> Result<Vec<StoredEvent>> {
+        validate(events)?;
+
+        // IMMEDIATE takes the write lock up front, so the version check and
+        // the inserts cannot interleave with another writer.
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let actual = last_sequence(&mut *tx, stream_id).await?;
+        if let Some(expected) = expected_version {
+            if expected != actual {
+                return Err(Error::WrongExpectedVersion { expected, actual });
+            }
+        }
+
+        let command_id = match command_id {
+            Some(id) => id.to_string(),
+            None => {
+                sqlx::query_scalar("SELECT lower(hex(randomblob(16)))")
+                    .fetch_one(&mut *tx)
+                    .await?
+            }
+        };
+        let timestamp = now();
+        let mut prev_hash: String = sqlx::query_scalar(
+            "SELECT hash FROM events WHERE stream_id = ? ORDER BY sequence DESC LIMIT 1",
+        )
+        .bind(stream_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten()
+        .unwrap_or_default();
+        let mut rng = rng::take_pending(&mut tx, stream_id).await?;
+        if actual == 0 {
+            claim_stream(&mut *tx, stream_id).await?;
+        }
+
+        let mut stored = Vec::with_capacity(events.len());
+        for (offset, event) in (1..).zip(events) {
+            let metadata = serde_json::to_string(&EventMetadata {
+                timestamp: timestamp.clone(),
+                command_id: Some(command_id.clone()),
+                schema_version: Some(self.upcasters.current_version(&event.event_type)),
+                rng: rng.take(),
+            })?;
+            let sequence = actual + offset;
+            let event_data = event.data.to_string();
+            let hash = event_hash(
+                &prev_hash,
+                sequence,
+                &event.event_type,
+                &event_data,
+                Some(&metadata),
+            );
+            let row = sqlx::query_as::<_, StoredEvent>(
+                "INSERT INTO events (stream_id, event_type, event_data, metadata, sequence, prev_hash, hash)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)
+                 RETURNING *",
+            )
+            .bind(stream_id)
+            .bind(&event.event_type)
+            .bind(&event_data)
+            .bind(&metadata)
+            .bind(sequence)
+            .bind(&prev_hash)
+            .bind(&hash)
+            .fetch_one(&mut *tx)
+            .await?;
+            stored.push(row);
+            prev_hash = hash;
+        }
+
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    /// Returns every event of `stream_id` in sequence order.
+    pub async fn read_stream(&self, stream_id: &str) -> Result<Vec<StoredEvent>> {
+        self.read_stream_since(stream_id, 0).await
+    }
+
+    /// Returns the events of `stream_id` with a sequence above `after_sequence`,
+    /// upcast to their current shapes.
+    pub async fn read_stream_since(
+        &self,
+        stream_id: &str,
+        after_sequence: i64,
+    ) -> Result<Vec<StoredEvent>> {
+        let rows = sqlx::query_as::<_, StoredEvent>(
+            "SELECT * FROM events WHERE stream_id = ? AND sequence > ? ORDER BY sequence ASC",
+        )
+        .bind(stream_id)
+        .bind(after_sequence)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|event| self.upcasters.upcast(event))
+            .collect())
+    }
+
+    /// Removes a stream together with its snapshot, its own lineage link, its
+    /// random generator and its legacy chain cutoff.
+    /// Returns the number of events deleted.
+    pub async fn delete_stream(&self, stream_id: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query("DELETE FROM events WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM snapshots WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM stream_lineage WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM stream_rng WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM chain_legacy WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Current version of `stream_id`: its highest sequence, or 0 when empty.
+    /// This is the value to pass as `expected_version` on the next append.
+    pub async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        last_sequence(&self.pool, stream_id).await
+    }
+}
+
+fn validate(events: &[NewEvent]) -> Result<()> {
+    for (index, event) in events.iter().enumerate() {
+        let parsed = if GameEvent::is_known_type(&event.event_type) {
+            GameEvent::from_parts(&event.event_type, &event.data).map(drop)
+        } else if BattleEvent::is_known_type(&event.event_type) {
+            BattleEvent::from_parts(&event.event_type, &event.data).map(drop)
+        } else {
+            return Err(Error::UnknownEventType {
+                index,
+                event_type: event.event_type.clone(),
+            });
+        };
+        parsed.map_err(|err| Error::InvalidEvent {
+            index,
+            event_type: event.event_type.clone(),
+            reason: err.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn last_sequence<'e>(
+    executor: impl SqliteExecutor<'e>,
+    stream_id: &str,
+) -> Result<i64> {
+    let last: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(sequence) FROM events WHERE stream_id = ?")
+            .bind(stream_id)
+            .fetch_one(executor)
+            .await?;
+    Ok(last.unwrap_or(0))
+}
+
+/// Stream holding a player's game, as built by `getStreamId` in `gameStore.ts`.
+pub fn player_stream(player_id: &str) -> String {
+    format!("player-{player_id}")
+}
+
+/// Inverse of [`player_stream`].
+pub(crate) fn stream_player_id(stream_id: &str) -> Option<&str> {
+    stream_id.strip_prefix("player-")
+}
+
+/// Current UTC time in the ISO-8601 form the webview writes with `toISOString()`.
+pub(crate) fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// A valid `BOUNTY_MODIFIED` event, for tests that only need some events.
+#[cfg(test)]
+pub(crate) fn bounty_modified(amount: i64) -> NewEvent {
+    NewEvent {
+        event_type: "BOUNTY_MODIFIED".to_string(),
+        data: serde_json::json!({
+            "timestamp": now(),
+            "amount": amount,
+            "newValue": amount,
+            "source": "quest",
+            "reason": "test",
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use serde_json::json;
+
+    fn game_started() -> NewEvent {
+        NewEvent {
+            event_type: "GAME_STARTED".to_string(),
+            data: json!({ "timestamp": now(), "playerId": "1", "starterCardIds": [] }),
+        }
+    }
+
+    fn command_id(event: &StoredEvent) -> Option<String> {
+        let metadata: EventMetadata = serde_json::from_str(event.metadata.as_deref()?).unwrap();
+        metadata.command_id
+    }
+
+    async fn store() -> EventStore {
+        EventStore::new(db::connect_in_memory().await.unwrap())
+    }
+
+    #[test]
+    fn appends_and_reads_in_sequence_order() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            store
+                .append_events("player-1", None, None, &[game_started()])
+                .await
+                .unwrap();
+            let stored = store
+                .append_events(
+                    "player-1",
+                    Some(1),
+                    None,
+                    &[bounty_modified(10), bounty_modified(5)],
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                stored.iter().map(|e| e.sequence).collect::<Vec<_>>(),
+                vec![2, 3]
+            );
+
+            let all = store.read_stream("player-1").await.unwrap();
+            assert_eq!(all.len(), 3);
+            assert_eq!(all[0].event_type, "GAME_STARTED");
+
+            let since = store.read_stream_since("player-1", 2).await.unwrap();
+            assert_eq!(since.len(), 1);
+            let data: Value = serde_json::from_str(&since[0].event_data).unwrap();
+            assert_eq!(data["amount"], 5);
+        });
+    }
+
+    #[test]
+    fn delete_stream_leaves_other_streams() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let started = [game_started()];
+            store
+                .append_events("player-1", None, None, &started)
+                .await
+                .unwrap();
+            store
+                .append_events("player-2", None, None, &started)
+                .await
+                .unwrap();
+
+            assert_eq!(store.delete_stream("player-1").await.unwrap(), 1);
+            assert!(store.read_stream("player-1").await.unwrap().is_empty());
+            assert_eq!(store.read_stream("player-2").await.unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn rejects_stale_expected_version() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let started = [game_started()];
+            store
+                .append_events("player-1", Some(0), None, &started)
+                .await
+                .unwrap();
+
+            let err = store
+                .append_events("player-1", Some(0), None, &started)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                Error::WrongExpectedVersion {
+                    expected: 0,
+                    actual: 1
+                }
+            ));
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn rejects_events_outside_the_catalog() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let unknown = NewEvent {
+                event_type: "BOUNTY_CHANGED".to_string(),
+                data: json!({}),
+            };
+            let err = store
+                .append_events("player-1", None, None, &[game_started(), unknown])
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::UnknownEventType { index: 1, .. }));
+
+            let mut malformed = bounty_modified(5);
+            malformed.data["source"] = json!("lottery");
+            let err = store
+                .append_events("player-1", None, None, &[malformed])
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&err, Error::InvalidEvent { index: 0, reason, .. } if reason.contains("lottery"))
+            );
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn tags_each_batch_with_one_command_id() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let first = store
+                .append_events(
+                    "player-1",
+                    None,
+                    Some("cmd-accept"),
+                    &[game_started(), bounty_modified(100)],
+                )
+                .await
+                .unwrap();
+            let second = store
+                .append_events("player-1", None, None, &[bounty_modified(5)])
+                .await
+                .unwrap();
+
+            assert!(first
+                .iter()
+                .all(|e| command_id(e).as_deref() == Some("cmd-accept")));
+            let generated = command_id(&second[0]).unwrap();
+            assert_eq!(generated.len(), 32);
+            assert_ne!(generated, "cmd-accept");
+        });
+    }
+}
@@ -0,0 +1,72 @@
+//! Push notifications of committed appends to the webview.
+//!
+//! After a batch is committed, every window receives an [`EVENTS_APPENDED`]
+//! Tauri event carrying the stored rows, so projections and debug views can
+//! follow the durable log instead of in-memory dispatch.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use super::StoredEvent;
+
+/// Name of the Tauri event emitted after each committed append.
+pub const EVENTS_APPENDED: &str = "event-store:appended";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsAppended<'a> {
+    pub stream_id: &'a str,
+    /// Stream version after the append, i.e. the sequence of the last event.
+    pub sequence: i64,
+    pub events: &'a [StoredEvent],
+}
+
+impl<'a> EventsAppended<'a> {
+    /// The notification for one committed batch, or `None` if it was empty.
+    pub fn new(stream_id: &'a str, events: &'a [StoredEvent]) -> Option<Self> {
+        let last = events.last()?;
+        Some(Self {
+            stream_id,
+            sequence: last.sequence,
+            events,
+        })
+    }
+}
+
+/// Tells every window that `events` were committed to `stream_id`.
+pub fn emit_appended(app: &AppHandle, stream_id: &str, events: &[StoredEvent]) {
+    if let Some(payload) = EventsAppended::new(stream_id, events) {
+        // The events are already durable; a failed notification must not turn
+        // the append into an error, and listeners can always re-read the stream.
+        let _ = app.emit(EVENTS_APPENDED, payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, EventStore};
+
+    #[test]
+    fn payload_carries_stream_and_last_sequence() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let stored = store
+                .append_events(
+                    "player-1",
+                    None,
+                    None,
+                    &[bounty_modified(1), bounty_modified(2)],
+                )
+                .await
+                .unwrap();
+
+            let payload = serde_json::to_value(EventsAppended::new("player-1", &stored)).unwrap();
+            assert_eq!(payload["streamId"], "player-1");
+            assert_eq!(payload["sequence"], 2);
+            assert_eq!(payload["events"][1]["event_type"], "BOUNTY_MODIFIED");
+            assert!(EventsAppended::new("player-1", &[]).is_none());
+        });
+    }
+}
@@ -0,0 +1,329 @@
+//! Player profiles over `profiles`.
+//!
+//! `stream_profiles` records the profile owning each stream. A stream is
+//! claimed on its first append: by the profile its player id names, so
+//! profile `7` owns `player-7`, and otherwise by the selected profile, which
+//! is where the webview's timestamp-named games go. A fork (see `lineage`)
+//! belongs to its parent's profile. A profile's saves follow its streams,
+//! and it has its own `profile_settings` row. At most one profile is
+//! selected at a time.
+
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqliteExecutor};
+
+use super::{stream_player_id, EventStore};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub profile_id: String,
+    pub name: String,
+    pub selected: bool,
+    pub created_at: String,
+}
+
+impl EventStore {
+    pub async fn create_profile(&self, name: &str) -> Result<Profile> {
+        insert_profile(&self.pool, name).await
+    }
+
+    pub async fn rename_profile(&self, profile_id: &str, name: &str) -> Result<Profile> {
+        sqlx::query_as::<_, Profile>(
+            "UPDATE profiles SET name = ? WHERE profile_id = ? RETURNING *",
+        )
+        .bind(name)
+        .bind(profile_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| Error::ProfileNotFound(profile_id.to_string()))
+    }
+
+    /// Lists every profile, oldest first.
+    pub async fn list_profiles(&self) -> Result<Vec<Profile>> {
+        let profiles =
+            sqlx::query_as::<_, Profile>("SELECT * FROM profiles ORDER BY created_at, rowid")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(profiles)
+    }
+
+    /// Makes `profile_id` the selected profile, deselecting any other.
+    pub async fn select_profile(&self, profile_id: &str) -> Result<Profile> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        require_profile(&mut tx, profile_id).await?;
+        sqlx::query("UPDATE profiles SET selected = 0 WHERE selected = 1")
+            .execute(&mut *tx)
+            .await?;
+        let profile = sqlx::query_as::<_, Profile>(
+            "UPDATE profiles SET selected = 1 WHERE profile_id = ? RETURNING *",
+        )
+        .bind(profile_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(profile)
+    }
+
+    pub async fn selected_profile(&self) -> Result<Option<Profile>> {
+        let profile = sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE selected = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(profile)
+    }
+
+    /// Deletes a profile with everything it owns: its streams and their
+    /// snapshots, lineage, undone events, random generators, legacy chain
+    /// cutoffs and ownership, its saves and its settings.
+    pub async fn delete_profile(&self, profile_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        require_profile(&mut tx, profile_id).await?;
+
+        for stream_id in owned_streams(&mut *tx, profile_id).await? {
+            for table in [
+                "events",
+                "snapshots",
+                "stream_lineage",
+                "undone_events",
+                "stream_rng",
+                "chain_legacy",
+                "stream_profiles",
+            ] {
+                sqlx::query(&format!("DELETE FROM {table} WHERE stream_id = ?"))
+                    .bind(&stream_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        for table in ["save_games", "profile_settings", "profiles"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE profile_id = ?"))
+                .bind(profile_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+pub(crate) async fn insert_profile<'e>(
+    executor: impl SqliteExecutor<'e>,
+    name: &str,
+) -> Result<Profile> {
+    let profile = sqlx::query_as::<_, Profile>(
+        "INSERT INTO profiles (profile_id, name) VALUES (lower(hex(randomblob(8))), ?)
+         RETURNING *",
+    )
+    .bind(name)
+    .fetch_one(executor)
+    .await?;
+    Ok(profile)
+}
+
+/// The profile owning `stream_id`, or for a stream never appended to, the
+/// one its player id would name.
+pub(crate) async fn stream_profile<'e>(
+    executor: impl SqliteExecutor<'e>,
+    stream_id: &str,
+) -> Result<String> {
+    let owner: Option<String> =
+        sqlx::query_scalar("SELECT profile_id FROM stream_profiles WHERE stream_id = ?")
+            .bind(stream_id)
+            .fetch_optional(executor)
+            .await?;
+    Ok(owner.unwrap_or_else(|| stream_player_id(stream_id).unwrap_or(stream_id).to_string()))
+}
+
+/// Records the owner of `stream_id` unless it has one: the profile named by
+/// its player id, else the selected profile, else its player id as before
+/// profiles existed.
+pub(crate) async fn claim_stream<'e>(
+    executor: impl SqliteExecutor<'e>,
+    stream_id: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO stream_profiles (stream_id, profile_id)
+         SELECT ?1, COALESCE(
+             (SELECT profile_id FROM profiles WHERE 'player-' || profile_id = ?1),
+             (SELECT profile_id FROM profiles WHERE selected = 1),
+             ?2
+         )",
+    )
+    .bind(stream_id)
+    .bind(stream_player_id(stream_id).unwrap_or(stream_id))
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// The streams owned by `profile_id`, each after the stream it was forked
+/// from.
+pub(crate) async fn owned_streams<'e>(
+    executor: impl SqliteExecutor<'e>,
+    profile_id: &str,
+) -> Result<Vec<String>> {
+    let streams = sqlx::query_scalar(
+        "WITH RECURSIVE depths(stream_id, depth) AS (
+             SELECT stream_id, 0 FROM stream_profiles WHERE profile_id = ?
+             UNION
+             SELECT l.stream_id, d.depth + 1 FROM stream_lineage l
+             JOIN depths d ON l.parent_stream_id = d.stream_id
+         )
+         SELECT d.stream_id FROM depths d
+         JOIN stream_profiles s ON s.stream_id = d.stream_id AND s.profile_id = ?
+         GROUP BY d.stream_id
+         ORDER BY MAX(d.depth), d.stream_id",
+    )
+    .bind(profile_id)
+    .bind(profile_id)
+    .fetch_all(executor)
+    .await?;
+    Ok(streams)
+}
+
+async fn require_profile(conn: &mut SqliteConnection, profile_id: &str) -> Result<()> {
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM profiles WHERE profile_id = ?)")
+            .bind(profile_id)
+            .fetch_one(conn)
+            .await?;
+    if exists {
+        Ok(())
+    } else {
+        Err(Error::ProfileNotFound(profile_id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, player_stream};
+    use serde_json::json;
+
+    async fn play(store: &EventStore, profile_id: &str) {
+        store
+            .append_events(
+                &player_stream(profile_id),
+                None,
+                None,
+                &[bounty_modified(1)],
+            )
+            .await
+            .unwrap();
+        store
+            .save_game(profile_id, "autosave", &json!({ "profile": profile_id }))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn profiles_keep_same_named_saves_apart() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let sibling = store.create_profile("Sam").await.unwrap();
+            play(&store, "1").await;
+            play(&store, &sibling.profile_id).await;
+
+            let mine = store.list_save_games("1").await.unwrap();
+            let theirs = store.list_save_games(&sibling.profile_id).await.unwrap();
+            assert_eq!(mine.len(), 1);
+            assert_eq!(mine[0].preview, json!({ "profile": "1" }));
+            assert_eq!(theirs[0].player_id, sibling.profile_id);
+        });
+    }
+
+    #[test]
+    fn select_rename_and_list() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let sibling = store.create_profile("Sam").await.unwrap();
+            assert!(!sibling.selected);
+
+            store.select_profile(&sibling.profile_id).await.unwrap();
+            store
+                .rename_profile(&sibling.profile_id, "Samira")
+                .await
+                .unwrap();
+
+            let selected = store.selected_profile().await.unwrap().unwrap();
+            assert_eq!(selected.name, "Samira");
+            let names: Vec<_> = store
+                .list_profiles()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|p| (p.name, p.selected))
+                .collect();
+            assert_eq!(
+                names,
+                [
+                    ("Player 1".to_string(), false),
+                    ("Samira".to_string(), true)
+                ]
+            );
+            assert!(matches!(
+                store.select_profile("nobody").await,
+                Err(Error::ProfileNotFound(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn delete_removes_only_what_the_profile_owns() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let sibling = store.create_profile("Sam").await.unwrap();
+            play(&store, "1").await;
+            play(&store, &sibling.profile_id).await;
+            let sibling_stream = player_stream(&sibling.profile_id);
+            store
+                .fork_stream(&sibling_stream, 1, "player-fork")
+                .await
+                .unwrap();
+
+            store.delete_profile(&sibling.profile_id).await.unwrap();
+
+            assert_eq!(store.stream_version(&sibling_stream).await.unwrap(), 0);
+            assert_eq!(store.stream_version("player-fork").await.unwrap(), 0);
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 1);
+            assert_eq!(store.list_save_games("1").await.unwrap().len(), 1);
+            assert_eq!(store.list_profiles().await.unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn games_named_by_timestamp_belong_to_the_selected_profile() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let sibling = store.create_profile("Sam").await.unwrap();
+            store.select_profile(&sibling.profile_id).await.unwrap();
+            play(&store, "1700000000000").await;
+            store
+                .fork_stream("player-1700000000000", 1, "player-fork")
+                .await
+                .unwrap();
+
+            let saves = store.list_save_games(&sibling.profile_id).await.unwrap();
+            assert_eq!(saves.len(), 1);
+            assert_eq!(saves[0].player_id, "1700000000000");
+            let tree = store.save_tree(&sibling.profile_id).await.unwrap();
+            assert_eq!(tree.len(), 1);
+            assert_eq!(tree[0].stream_id, "player-1700000000000");
+            assert_eq!(tree[0].branches[0].stream_id, "player-fork");
+
+            store.delete_profile(&sibling.profile_id).await.unwrap();
+            assert_eq!(
+                store.stream_version("player-1700000000000").await.unwrap(),
+                0
+            );
+            assert_eq!(store.stream_version("player-fork").await.unwrap(), 0);
+            assert!(store
+                .list_save_games("1700000000000")
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+}
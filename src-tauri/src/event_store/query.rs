@@ -0,0 +1,275 @@
+//! Cross-stream queries over `events`, backed by `idx_events_type` and
+//! `idx_events_created`.
+//!
+//! Every filter is optional and they combine with AND. Filters apply to the
+//! rows as stored; matching events are upcast before they are returned.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+
+use super::{EventStore, StoredEvent};
+use crate::error::Result;
+
+/// Page size used when the query does not set `limit`.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Largest page a single query may return.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EventQuery {
+    /// Only events of these types; empty means every type.
+    pub event_types: Vec<String>,
+    /// Only streams whose id starts with this, e.g. `"player-"`.
+    pub stream_prefix: Option<String>,
+    /// Inclusive lower bound on `created_at`. Any form SQLite's `datetime()`
+    /// understands, including the webview's ISO-8601 timestamps.
+    pub created_from: Option<String>,
+    /// Exclusive upper bound on `created_at`.
+    pub created_to: Option<String>,
+    /// Inclusive sequence window within each stream.
+    pub min_sequence: Option<i64>,
+    pub max_sequence: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventPage {
+    pub events: Vec<StoredEvent>,
+    /// Offset of the next page, or `None` when this was the last one.
+    pub next_offset: Option<i64>,
+}
+
+impl EventStore {
+    /// Returns one page of the events matching `query`, ordered by
+    /// `created_at`, then stream and sequence.
+    pub async fn query_events(&self, query: &EventQuery) -> Result<EventPage> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let mut sql = QueryBuilder::<Sqlite>::new("SELECT * FROM events WHERE 1 = 1");
+        if !query.event_types.is_empty() {
+            sql.push(" AND event_type IN (");
+            let mut types = sql.separated(", ");
+            for event_type in &query.event_types {
+                types.push_bind(event_type);
+            }
+            types.push_unseparated(")");
+        }
+        if let Some(prefix) = &query.stream_prefix {
+            sql.push(" AND stream_id LIKE ")
+                .push_bind(format!("{}%", escape_like(prefix)))
+                .push(" ESCAPE '\\'");
+        }
+        if let Some(from) = &query.created_from {
+            sql.push(" AND created_at >= datetime(")
+                .push_bind(from)
+                .push(")");
+        }
+        if let Some(to) = &query.created_to {
+            sql.push(" AND created_at < datetime(")
+                .push_bind(to)
+                .push(")");
+        }
+        if let Some(min) = query.min_sequence {
+            sql.push(" AND sequence >= ").push_bind(min);
+        }
+        if let Some(max) = query.max_sequence {
+            sql.push(" AND sequence <= ").push_bind(max);
+        }
+        // One extra row tells whether another page follows.
+        sql.push(" ORDER BY created_at, stream_id, sequence LIMIT ")
+            .push_bind(limit + 1)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        let mut events = sql
+            .build_query_as::<StoredEvent>()
+            .fetch_all(&self.pool)
+            .await?;
+        let next_offset = (events.len() as i64 > limit).then_some(offset + limit);
+        events.truncate(limit as usize);
+
+        Ok(EventPage {
+            events: events
+                .into_iter()
+                .map(|event| self.upcasters.upcast(event))
+                .collect(),
+            next_offset,
+        })
+    }
+
+    /// Returns the events carrying `battle_id` in sequence order, upcast.
+    ///
+    /// Forked streams repeat their parent's events, so only the stream that
+    /// recorded the battle first is read. Rows that are not valid JSON are
+    /// skipped; the `json_valid` guard also lets `idx_events_battle` serve
+    /// the lookup.
+    pub async fn battle_events(&self, battle_id: &str) -> Result<Vec<StoredEvent>> {
+        let rows = sqlx::query_as::<_, StoredEvent>(
+            "SELECT * FROM events
+             WHERE json_valid(event_data) AND json_extract(event_data, '$.battleId') = ?1
+               AND stream_id = (
+                 SELECT stream_id FROM events
+                 WHERE json_valid(event_data) AND json_extract(event_data, '$.battleId') = ?1
+                 ORDER BY rowid LIMIT 1
+               )
+             ORDER BY sequence",
+        )
+        .bind(battle_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|event| self.upcasters.upcast(event))
+            .collect())
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, NewEvent};
+    use serde_json::json;
+
+    fn choice_made(choice_id: &str) -> NewEvent {
+        NewEvent {
+            event_type: "CHOICE_MADE".to_string(),
+            data: json!({
+                "timestamp": "2025-01-01T00:00:00.000Z",
+                "dilemmaId": "d1",
+                "choiceId": choice_id,
+                "questId": "q1",
+            }),
+        }
+    }
+
+    async fn store() -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        for (stream_id, choice) in [("player-1", "a"), ("player-2", "b"), ("ghost_1", "c")] {
+            store
+                .append_events(
+                    stream_id,
+                    None,
+                    None,
+                    &[bounty_modified(1), choice_made(choice), bounty_modified(2)],
+                )
+                .await
+                .unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn filters_by_type_and_stream_prefix() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let page = store
+                .query_events(&EventQuery {
+                    event_types: vec!["CHOICE_MADE".to_string()],
+                    stream_prefix: Some("player-".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            let streams: Vec<_> = page.events.iter().map(|e| e.stream_id.as_str()).collect();
+            assert_eq!(streams, ["player-1", "player-2"]);
+            assert_eq!(page.next_offset, None);
+
+            // `_` in the prefix is literal, not a LIKE wildcard.
+            let page = store
+                .query_events(&EventQuery {
+                    stream_prefix: Some("player_".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert!(page.events.is_empty());
+        });
+    }
+
+    #[test]
+    fn filters_by_created_at_and_sequence_window() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            sqlx::query(
+                "UPDATE events SET created_at = '2024-06-01 12:00:00' WHERE stream_id = 'player-2'",
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+            let page = store
+                .query_events(&EventQuery {
+                    created_from: Some("2024-06-01T00:00:00.000Z".to_string()),
+                    created_to: Some("2024-06-02T00:00:00.000Z".to_string()),
+                    min_sequence: Some(2),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            let found: Vec<_> = page
+                .events
+                .iter()
+                .map(|e| (e.stream_id.as_str(), e.sequence))
+                .collect();
+            assert_eq!(found, [("player-2", 2), ("player-2", 3)]);
+        });
+    }
+
+    #[test]
+    fn paginates_with_next_offset() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            let mut query = EventQuery {
+                limit: Some(4),
+                ..Default::default()
+            };
+            let mut seen = Vec::new();
+            loop {
+                let page = store.query_events(&query).await.unwrap();
+                seen.extend(page.events.into_iter().map(|e| e.event_id));
+                match page.next_offset {
+                    Some(offset) => query.offset = Some(offset),
+                    None => break,
+                }
+            }
+            assert_eq!(seen.len(), 9);
+            seen.sort();
+            seen.dedup();
+            assert_eq!(seen.len(), 9);
+        });
+    }
+
+    #[test]
+    fn battle_events_skip_rows_that_are_not_json() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            sqlx::query(
+                "INSERT INTO events (stream_id, event_type, event_data, sequence)
+                 VALUES ('ghost_2', 'BOUNTY_MODIFIED', 'not json', 1),
+                        ('ghost_2', 'BATTLE_STARTED', '{\"battleId\":\"b1\"}', 2)",
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+            let events = store.battle_events("b1").await.unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].sequence, 2);
+        });
+    }
+}
@@ -0,0 +1,424 @@
+//! Per-stream seeded randomness over `stream_rng`.
+//!
+//! Each stream gets its own seed, picked at its first draw unless set with
+//! [`seed_stream_rng`](EventStore::seed_stream_rng), and a generator that
+//! advances only when the stream draws. The generator is the one of
+//! `combat.ts`, see [`Lcg`].
+//!
+//! Draws made for a stream are held as pending until its next append, which
+//! records them with the seed in the metadata of the batch's first event.
+//! Any battle can then be reproduced from the log: the rolls are there, and
+//! so is what produced them. The records also tell where the generator stood
+//! after any event, which truncate, undo and fork use to rewind or copy it
+//! together with the events.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+use super::EventStore;
+use crate::error::Result;
+use crate::game::Lcg;
+
+/// One use of a stream's generator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RngDraw {
+    /// `index` is the position of the draw's first number in the stream's
+    /// sequence of generated numbers.
+    D20 { index: i64, roll: i32 },
+    /// `order[i]` is the original position of the item shuffled to `i`.
+    Shuffle { index: i64, order: Vec<usize> },
+    /// A seed handed to a generator of its own, such as a tactical battle's.
+    Seed { index: i64, seed: i64 },
+}
+
+/// Draws recorded in an event's metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngRecord {
+    pub seed: i64,
+    pub draws: Vec<RngDraw>,
+}
+
+/// Seeds are drawn from `0..2^31`, the states the generator can reach.
+const SEED_RANGE: u64 = 1 << 31;
+
+/// A stream's generator as stored.
+struct StreamRng {
+    seed: i64,
+    lcg: Lcg,
+    /// Numbers generated so far.
+    draws: i64,
+    pending: Vec<RngDraw>,
+}
+
+impl StreamRng {
+    fn new(seed: i64) -> Self {
+        Self {
+            seed,
+            lcg: Lcg::new(seed),
+            draws: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The generator of `seed` after it generated `draws` numbers.
+    fn at(seed: i64, draws: i64) -> Self {
+        let mut rng = Self::new(seed);
+        for _ in 0..draws {
+            rng.lcg.below(1);
+        }
+        rng.draws = draws;
+        rng
+    }
+
+    fn roll_d20(&mut self) -> i32 {
+        let index = self.draws;
+        let roll = self.lcg.d20();
+        self.draws += 1;
+        self.pending.push(RngDraw::D20 { index, roll });
+        roll
+    }
+
+    fn draw_seed(&mut self) -> i64 {
+        let index = self.draws;
+        let seed = self.lcg.below(SEED_RANGE) as i64;
+        self.draws += 1;
+        self.pending.push(RngDraw::Seed { index, seed });
+        seed
+    }
+
+    /// A permutation of `0..len`.
+    fn shuffle(&mut self, len: usize) -> Vec<usize> {
+        let index = self.draws;
+        let mut order: Vec<usize> = (0..len).collect();
+        self.lcg.shuffle(&mut order);
+        self.draws += len.saturating_sub(1) as i64;
+        self.pending.push(RngDraw::Shuffle {
+            index,
+            order: order.clone(),
+        });
+        order
+    }
+}
+
+impl EventStore {
+    /// Sets the seed of `stream_id`, restarting its generator and dropping
+    /// draws not yet recorded.
+    pub async fn seed_stream_rng(&self, stream_id: &str, seed: i64) -> Result<()> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        store(&mut tx, stream_id, &StreamRng::new(seed)).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Rolls `count` d20s for `stream_id`.
+    pub async fn roll_d20(&self, stream_id: &str, count: u32) -> Result<Vec<i32>> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut rng = load(&mut tx, stream_id).await?;
+        let rolls = (0..count).map(|_| rng.roll_d20()).collect();
+        store(&mut tx, stream_id, &rng).await?;
+        tx.commit().await?;
+        Ok(rolls)
+    }
+
+    /// Draws a seed for `stream_id`, for a generator that runs apart from
+    /// the stream's own.
+    pub async fn draw_seed(&self, stream_id: &str) -> Result<i64> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut rng = load(&mut tx, stream_id).await?;
+        let seed = rng.draw_seed();
+        store(&mut tx, stream_id, &rng).await?;
+        tx.commit().await?;
+        Ok(seed)
+    }
+
+    /// Returns `items` in an order drawn for `stream_id`.
+    pub async fn shuffle(&self, stream_id: &str, items: Vec<Value>) -> Result<Vec<Value>> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut rng = load(&mut tx, stream_id).await?;
+        let order = rng.shuffle(items.len());
+        store(&mut tx, stream_id, &rng).await?;
+        tx.commit().await?;
+
+        let mut items: Vec<_> = items.into_iter().map(Some).collect();
+        Ok(order
+            .into_iter()
+            .filter_map(|from| items[from].take())
+            .collect())
+    }
+}
+
+/// Takes the draws of `stream_id` not yet recorded, for the append running
+/// in `conn` to record.
+pub(crate) async fn take_pending(
+    conn: &mut SqliteConnection,
+    stream_id: &str,
+) -> Result<Option<RngRecord>> {
+    let row: Option<(i64, String)> = sqlx::query_as(
+        "SELECT seed, pending FROM stream_rng WHERE stream_id = ? AND pending != '[]'",
+    )
+    .bind(stream_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((seed, pending)) = row else {
+        return Ok(None);
+    };
+    sqlx::query("UPDATE stream_rng SET pending = '[]' WHERE stream_id = ?")
+        .bind(stream_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(Some(RngRecord {
+        seed,
+        draws: serde_json::from_str(&pending)?,
+    }))
+}
+
+/// Sets the generator of `target` to the one `stream_id` had right after
+/// event `sequence`, dropping draws not yet recorded. Truncate and undo
+/// rewind a stream onto itself, before deleting its tail; a fork starts
+/// from its parent's.
+pub(crate) async fn restore_at(
+    conn: &mut SqliteConnection,
+    stream_id: &str,
+    sequence: i64,
+    target: &str,
+) -> Result<()> {
+    let records: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT sequence, json_extract(metadata, '$.rng') FROM events
+         WHERE stream_id = ? AND json_valid(metadata)
+           AND json_extract(metadata, '$.rng') IS NOT NULL
+         ORDER BY sequence",
+    )
+    .bind(stream_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut before = None;
+    let mut after = None;
+    for (at, record) in records {
+        let record: RngRecord = serde_json::from_str(&record)?;
+        if at <= sequence {
+            before = Some(record);
+        } else {
+            after = Some(record);
+            break;
+        }
+    }
+
+    // Every draw is recorded on the first append after it, so the last
+    // record up to `sequence` ends where the generator stood; failing that,
+    // the first draw after it starts there.
+    let rng = if let Some(record) = before {
+        let draws = record.draws.last().map_or(0, |draw| match draw {
+            RngDraw::D20 { index, .. } | RngDraw::Seed { index, .. } => index + 1,
+            RngDraw::Shuffle { index, order } => index + order.len().saturating_sub(1) as i64,
+        });
+        Some(StreamRng::at(record.seed, draws))
+    } else if let Some(record) = after {
+        Some(StreamRng::at(record.seed, first_index(&record.draws)))
+    } else {
+        let row: Option<(i64, i64, String)> =
+            sqlx::query_as("SELECT seed, draws, pending FROM stream_rng WHERE stream_id = ?")
+                .bind(stream_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+        match row {
+            Some((seed, draws, pending)) => {
+                let pending: Vec<RngDraw> = serde_json::from_str(&pending)?;
+                let draws = if pending.is_empty() {
+                    draws
+                } else {
+                    first_index(&pending)
+                };
+                Some(StreamRng::at(seed, draws))
+            }
+            None => None,
+        }
+    };
+
+    match rng {
+        Some(rng) => store(conn, target, &rng).await,
+        None => {
+            sqlx::query("DELETE FROM stream_rng WHERE stream_id = ?")
+                .bind(target)
+                .execute(&mut *conn)
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+fn first_index(draws: &[RngDraw]) -> i64 {
+    draws.first().map_or(0, |draw| match draw {
+        RngDraw::D20 { index, .. }
+        | RngDraw::Shuffle { index, .. }
+        | RngDraw::Seed { index, .. } => *index,
+    })
+}
+
+/// The generator of `stream_id`, freshly seeded if it has none yet.
+async fn load(conn: &mut SqliteConnection, stream_id: &str) -> Result<StreamRng> {
+    let row: Option<(i64, i64, i64, String)> =
+        sqlx::query_as("SELECT seed, state, draws, pending FROM stream_rng WHERE stream_id = ?")
+            .bind(stream_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    match row {
+        Some((seed, state, draws, pending)) => Ok(StreamRng {
+            seed,
+            lcg: Lcg::new(state),
+            draws,
+            pending: serde_json::from_str(&pending)?,
+        }),
+        None => {
+            let seed: i64 = sqlx::query_scalar("SELECT abs(random() % 2147483648)")
+                .fetch_one(&mut *conn)
+                .await?;
+            Ok(StreamRng::new(seed))
+        }
+    }
+}
+
+async fn store(conn: &mut SqliteConnection, stream_id: &str, rng: &StreamRng) -> Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO stream_rng (stream_id, seed, state, draws, pending)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(stream_id)
+    .bind(rng.seed)
+    .bind(rng.lcg.state() as i64)
+    .bind(rng.draws)
+    .bind(serde_json::to_string(&rng.pending)?)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, Divergence, EventMetadata};
+    use serde_json::json;
+
+    #[test]
+    fn the_same_seed_gives_the_same_draws() {
+        let mut first = StreamRng::new(42);
+        let mut second = StreamRng::new(42);
+        let rolls: Vec<_> = (0..50).map(|_| first.roll_d20()).collect();
+        assert_eq!(
+            rolls,
+            (0..50).map(|_| second.roll_d20()).collect::<Vec<_>>()
+        );
+        assert!(rolls.iter().all(|roll| (1..=20).contains(roll)));
+        let mut other = StreamRng::new(7);
+        assert_ne!(rolls, (0..50).map(|_| other.roll_d20()).collect::<Vec<_>>());
+
+        let mut order = first.shuffle(10);
+        assert_eq!(order, second.shuffle(10));
+        order.sort();
+        assert_eq!(order, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn draws_are_recorded_on_the_next_append() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            store.seed_stream_rng("player-1", 42).await.unwrap();
+            let rolls = store.roll_d20("player-1", 2).await.unwrap();
+            let shuffled = store
+                .shuffle("player-1", vec![json!("a"), json!("b"), json!("c")])
+                .await
+                .unwrap();
+            assert_eq!(shuffled.len(), 3);
+
+            let batch = [bounty_modified(1), bounty_modified(2)];
+            let first = store
+                .append_events("player-1", None, None, &batch)
+                .await
+                .unwrap();
+            let second = store
+                .append_events("player-1", None, None, &[bounty_modified(3)])
+                .await
+                .unwrap();
+
+            let metadata = |raw: &Option<String>| -> EventMetadata {
+                serde_json::from_str(raw.as_deref().unwrap()).unwrap()
+            };
+            let record = metadata(&first[0].metadata).rng.unwrap();
+            assert_eq!(record.seed, 42);
+            assert_eq!(
+                record.draws[..2],
+                [
+                    RngDraw::D20 {
+                        index: 0,
+                        roll: rolls[0]
+                    },
+                    RngDraw::D20 {
+                        index: 1,
+                        roll: rolls[1]
+                    },
+                ]
+            );
+            assert!(matches!(record.draws[2], RngDraw::Shuffle { index: 2, .. }));
+            assert!(metadata(&first[1].metadata).rng.is_none());
+            assert!(metadata(&second[0].metadata).rng.is_none());
+
+            // Replaying the recorded seed reproduces the rolls.
+            let mut replay = StreamRng::new(record.seed);
+            assert_eq!([replay.roll_d20(), replay.roll_d20()], rolls[..]);
+        });
+    }
+
+    #[test]
+    fn undo_fork_and_truncate_carry_the_generator_with_the_events() {
+        tauri::async_runtime::block_on(async {
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            store.seed_stream_rng("player-1", 42).await.unwrap();
+            store
+                .append_events("player-1", None, Some("start"), &[bounty_modified(1)])
+                .await
+                .unwrap();
+            let rolls = store.roll_d20("player-1", 2).await.unwrap();
+            store
+                .append_events("player-1", None, Some("battle"), &[bounty_modified(2)])
+                .await
+                .unwrap();
+            let next = store.roll_d20("player-1", 3).await.unwrap();
+
+            // Undoing the battle takes its rolls back, and the pending ones.
+            store.undo_last_command("player-1").await.unwrap();
+            assert_eq!(store.roll_d20("player-1", 2).await.unwrap(), rolls);
+            store
+                .append_events("player-1", None, Some("battle"), &[bounty_modified(2)])
+                .await
+                .unwrap();
+
+            // A fork draws what its parent drew after the fork point.
+            store.fork_stream("player-1", 1, "player-2").await.unwrap();
+            assert_eq!(store.roll_d20("player-2", 2).await.unwrap(), rolls);
+            store.fork_stream("player-1", 2, "player-3").await.unwrap();
+            assert_eq!(store.roll_d20("player-3", 3).await.unwrap(), next);
+            assert_eq!(store.roll_d20("player-1", 3).await.unwrap(), next);
+
+            // So does loading a save over the events after it.
+            store
+                .save_game("1", "before the next battle", &json!({}))
+                .await
+                .unwrap();
+            store
+                .append_events("player-1", None, Some("battle"), &[bounty_modified(3)])
+                .await
+                .unwrap();
+            store
+                .load_game("1", "before the next battle", Some(Divergence::Truncate))
+                .await
+                .unwrap();
+            assert_eq!(store.roll_d20("player-1", 3).await.unwrap(), next);
+        });
+    }
+}
@@ -0,0 +1,347 @@
+//! Named save slots over `save_games`.
+//!
+//! Save names are scoped to the profile owning the saved stream (see
+//! `profiles`), so two profiles can each keep an "autosave".
+//!
+//! A save records the id of the last event of the player's stream at the
+//! time it was made. Loading restores exactly that prefix of the stream. If
+//! the player has kept playing since, the caller must choose whether to
+//! truncate the live stream back to the save or fork the prefix into a new
+//! stream (see `lineage`); without a choice the load is refused so nothing
+//! is lost silently. Truncated events are kept in `undone_events`, as undo
+//! keeps the commands it removes.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::lineage::new_fork_player_id;
+use super::profiles::stream_profile;
+use super::rng;
+use super::settings::load_settings;
+use super::{player_stream, EventStore, StoredEvent};
+use crate::error::{Error, Result, UndoRefusal};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSlot {
+    pub save_name: String,
+    pub player_id: String,
+    pub last_event_id: String,
+    /// Sequence of `last_event_id` in the player's stream, 0 for an empty save.
+    pub sequence: i64,
+    pub preview: Value,
+    pub saved_at: String,
+}
+
+/// What to do with events recorded after the save being loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Divergence {
+    /// Move the later events out of the live stream into `undone_events`.
+    Truncate,
+    /// Leave the live stream alone and continue the save in a new stream.
+    Fork,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedGame {
+    pub player_id: String,
+    pub stream_id: String,
+    pub events: Vec<StoredEvent>,
+}
+
+/// Fresh fork ids tried before a clash is reported; one needs two equal
+/// 64-bit random suffixes.
+const FORK_ID_ATTEMPTS: usize = 3;
+
+#[derive(sqlx::FromRow)]
+struct SaveRow {
+    save_name: String,
+    player_id: String,
+    last_event_id: String,
+    preview_data: String,
+    saved_at: String,
+}
+
+impl EventStore {
+    /// Saves the current head of `player_id`'s stream under `save_name`,
+    /// replacing any save of the same name in the stream's profile.
+    pub async fn save_game(&self, player_id: &str, save_name: &str, preview: &Value) -> Result<()> {
+        let stream_id = player_stream(player_id);
+        let last_event_id: Option<String> = sqlx::query_scalar(
+            "SELECT event_id FROM events WHERE stream_id = ? ORDER BY sequence DESC LIMIT 1",
+        )
+        .bind(&stream_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let profile_id = stream_profile(&self.pool, &stream_id).await?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO save_games
+                 (profile_id, save_name, player_id, last_event_id, preview_data, saved_at)
+             VALUES (?, ?, ?, ?, ?, datetime('now'))",
+        )
+        .bind(profile_id)
+        .bind(save_name)
+        .bind(player_id)
+        .bind(last_event_id.unwrap_or_default())
+        .bind(preview.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the saves of `profile_id`, most recent first.
+    pub async fn list_save_games(&self, profile_id: &str) -> Result<Vec<SaveSlot>> {
+        let rows = sqlx::query_as::<_, SaveRow>(
+            "SELECT * FROM save_games WHERE profile_id = ? ORDER BY saved_at DESC",
+        )
+        .bind(profile_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut saves = Vec::with_capacity(rows.len());
+        for row in rows {
+            // A preview that no longer parses is skipped, like the browser store does.
+            let Ok(preview) = serde_json::from_str(&row.preview_data) else {
+                continue;
+            };
+            let sequence = self
+                .event_sequence(&player_stream(&row.player_id), &row.last_event_id)
+                .await?
+                .unwrap_or(0);
+            saves.push(SaveSlot {
+                save_name: row.save_name,
+                player_id: row.player_id,
+                last_event_id: row.last_event_id,
+                sequence,
+                preview,
+                saved_at: row.saved_at,
+            });
+        }
+        Ok(saves)
+    }
+
+    /// Restores the stream prefix recorded by `save_name`.
+    ///
+    /// Fails with [`Error::UnconfirmedDivergence`] when the live stream has
+    /// moved past the save and no `divergence` was chosen, and with
+    /// [`Error::UndoRefused`] when an ironman run would truncate; forking
+    /// leaves the run as it was.
+    pub async fn load_game(
+        &self,
+        profile_id: &str,
+        save_name: &str,
+        divergence: Option<Divergence>,
+    ) -> Result<LoadedGame> {
+        let row = sqlx::query_as::<_, SaveRow>(
+            "SELECT * FROM save_games WHERE profile_id = ? AND save_name = ?",
+        )
+        .bind(profile_id)
+        .bind(save_name)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| Error::SaveNotFound(save_name.to_string()))?;
+
+        let stream_id = player_stream(&row.player_id);
+        let saved = self.saved_sequence(&stream_id, &row).await?;
+        let live = self.stream_version(&stream_id).await?;
+
+        if live > saved {
+            match divergence {
+                None => {
+                    return Err(Error::UnconfirmedDivergence {
+                        events_after: live - saved,
+                    })
+                }
+                Some(Divergence::Truncate) => {
+                    let profile_id = stream_profile(&self.pool, &stream_id).await?;
+                    if load_settings(&self.pool, &profile_id).await?.ironman {
+                        return Err(Error::UndoRefused(UndoRefusal::Ironman));
+                    }
+                    self.truncate_stream(&stream_id, saved).await?
+                }
+                Some(Divergence::Fork) => {
+                    let player_id = self.fork_to_new_stream(&stream_id, saved).await?;
+                    let fork_id = player_stream(&player_id);
+                    return Ok(LoadedGame {
+                        events: self.read_stream(&fork_id).await?,
+                        player_id,
+                        stream_id: fork_id,
+                    });
+                }
+            }
+        }
+
+        Ok(LoadedGame {
+            events: self.read_stream(&stream_id).await?,
+            player_id: row.player_id,
+            stream_id,
+        })
+    }
+
+    pub async fn delete_save_game(&self, profile_id: &str, save_name: &str) -> Result<()> {
+        sqlx::query("DELETE FROM save_games WHERE profile_id = ? AND save_name = ?")
+            .bind(profile_id)
+            .bind(save_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn saved_sequence(&self, stream_id: &str, row: &SaveRow) -> Result<i64> {
+        if row.last_event_id.is_empty() {
+            return Ok(0);
+        }
+        self.event_sequence(stream_id, &row.last_event_id)
+            .await?
+            .ok_or_else(|| Error::SaveNotFound(row.save_name.clone()))
+    }
+
+    async fn event_sequence(&self, stream_id: &str, event_id: &str) -> Result<Option<i64>> {
+        let sequence =
+            sqlx::query_scalar("SELECT sequence FROM events WHERE stream_id = ? AND event_id = ?")
+                .bind(stream_id)
+                .bind(event_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(sequence)
+    }
+
+    /// Forks `stream_id` at `sequence` into the stream of a new player id
+    /// and returns that id.
+    async fn fork_to_new_stream(&self, stream_id: &str, sequence: i64) -> Result<String> {
+        let mut attempts = 1;
+        loop {
+            let player_id = new_fork_player_id(&self.pool).await?;
+            match self
+                .fork_stream(stream_id, sequence, &player_stream(&player_id))
+                .await
+            {
+                Err(Error::StreamAlreadyExists(_)) if attempts < FORK_ID_ATTEMPTS => attempts += 1,
+                result => return result.map(|_| player_id),
+            }
+        }
+    }
+
+    /// Moves every event of `stream_id` after `sequence` to `undone_events`,
+    /// drops a snapshot that covers any of them and rewinds the stream's
+    /// generator to where it stood at `sequence`.
+    async fn truncate_stream(&self, stream_id: &str, sequence: i64) -> Result<()> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        rng::restore_at(&mut tx, stream_id, sequence, stream_id).await?;
+        sqlx::query(
+            "INSERT INTO undone_events
+                 (event_id, stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash)
+             SELECT event_id, stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash
+             FROM events WHERE stream_id = ? AND sequence > ?",
+        )
+        .bind(stream_id)
+        .bind(sequence)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM events WHERE stream_id = ? AND sequence > ?")
+            .bind(stream_id)
+            .bind(sequence)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM snapshots WHERE stream_id = ? AND sequence > ?")
+            .bind(stream_id)
+            .bind(sequence)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::bounty_modified;
+    use serde_json::json;
+
+    async fn play(store: &EventStore, count: usize) {
+        let events: Vec<_> = (0..count).map(|i| bounty_modified(i as i64)).collect();
+        store
+            .append_events("player-1", None, None, &events)
+            .await
+            .unwrap();
+    }
+
+    async fn store_with_save() -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        play(&store, 3).await;
+        store
+            .save_game("1", "before the vault", &json!({ "bounty": 30 }))
+            .await
+            .unwrap();
+        play(&store, 2).await;
+        store
+    }
+
+    #[test]
+    fn refuses_to_drop_later_events_without_a_choice() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_save().await;
+            let err = store
+                .load_game("1", "before the vault", None)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                Error::UnconfirmedDivergence { events_after: 2 }
+            ));
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 5);
+        });
+    }
+
+    #[test]
+    fn truncate_restores_the_saved_prefix() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_save().await;
+            let loaded = store
+                .load_game("1", "before the vault", Some(Divergence::Truncate))
+                .await
+                .unwrap();
+            assert_eq!(loaded.stream_id, "player-1");
+            assert_eq!(loaded.events.len(), 3);
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 3);
+
+            let archived: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM undone_events WHERE stream_id = 'player-1'",
+            )
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+            assert_eq!(archived, 2);
+        });
+    }
+
+    #[test]
+    fn fork_keeps_the_live_stream() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_save().await;
+            let loaded = store
+                .load_game("1", "before the vault", Some(Divergence::Fork))
+                .await
+                .unwrap();
+            assert!(loaded.player_id.starts_with("fork-"));
+            assert_eq!(loaded.stream_id, player_stream(&loaded.player_id));
+            assert_eq!(loaded.events.len(), 3);
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 5);
+
+            // Forking the same save again at once gets a stream of its own.
+            let again = store
+                .load_game("1", "before the vault", Some(Divergence::Fork))
+                .await
+                .unwrap();
+            assert_ne!(again.stream_id, loaded.stream_id);
+
+            let saves = store.list_save_games("1").await.unwrap();
+            assert_eq!(saves[0].sequence, 3);
+        });
+    }
+}
@@ -0,0 +1,65 @@
+//! Per-profile settings over `profile_settings`.
+//!
+//! The settings of a stream are those of the profile owning it, see
+//! `profiles`. Profiles without a row use the defaults.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteExecutor;
+
+use super::EventStore;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileSettings {
+    /// Ironman runs cannot undo commands or load a save over later events,
+    /// and stay ironman once set.
+    pub ironman: bool,
+}
+
+impl EventStore {
+    pub async fn profile_settings(&self, profile_id: &str) -> Result<ProfileSettings> {
+        load_settings(&self.pool, profile_id).await
+    }
+
+    /// Fails with [`Error::InvalidProfileSettings`] when it would turn
+    /// ironman off.
+    pub async fn update_profile_settings(
+        &self,
+        profile_id: &str,
+        settings: &ProfileSettings,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        if load_settings(&mut *tx, profile_id).await?.ironman && !settings.ironman {
+            return Err(Error::InvalidProfileSettings(
+                "ironman cannot be turned off".to_string(),
+            ));
+        }
+        sqlx::query(
+            "INSERT INTO profile_settings (profile_id, ironman) VALUES (?, ?)
+             ON CONFLICT (profile_id) DO UPDATE SET ironman = excluded.ironman",
+        )
+        .bind(profile_id)
+        .bind(settings.ironman)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+pub(crate) async fn load_settings<'e>(
+    executor: impl SqliteExecutor<'e>,
+    profile_id: &str,
+) -> Result<ProfileSettings> {
+    let ironman: Option<bool> =
+        sqlx::query_scalar("SELECT ironman FROM profile_settings WHERE profile_id = ?")
+            .bind(profile_id)
+            .fetch_optional(executor)
+            .await?;
+    Ok(
+        ironman.map_or_else(ProfileSettings::default, |ironman| ProfileSettings {
+            ironman,
+        }),
+    )
+}
@@ -0,0 +1,208 @@
+//! `GameState` snapshots, mirroring the snapshot support in `BrowserEventStore`.
+//!
+//! The state itself is opaque JSON folded by the webview. A snapshot is only
+//! handed back when it was taken under the caller's `SCHEMA_VERSION`; anything
+//! else is discarded and the stream is replayed from the start.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{EventStore, StoredEvent};
+use crate::error::Result;
+
+/// Number of events replayed past the last snapshot before a new one is due.
+pub const SNAPSHOT_THRESHOLD: i64 = 50;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub stream_id: String,
+    pub sequence: i64,
+    pub state: Value,
+    pub schema_version: i64,
+    pub created_at: String,
+}
+
+/// Everything needed to rebuild a stream's state: the latest usable snapshot
+/// and the events that follow it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamState {
+    pub snapshot: Option<Snapshot>,
+    pub events: Vec<StoredEvent>,
+    /// True when enough events were replayed that the caller should save a
+    /// fresh snapshot of the folded state.
+    pub snapshot_due: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct SnapshotRow {
+    stream_id: String,
+    sequence: i64,
+    state_data: String,
+    schema_version: i64,
+    created_at: String,
+}
+
+impl EventStore {
+    /// Stores `state` as the snapshot of `stream_id` at `sequence`, replacing
+    /// any previous snapshot of that stream.
+    pub async fn save_snapshot(
+        &self,
+        stream_id: &str,
+        sequence: i64,
+        state: &Value,
+        schema_version: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO snapshots (stream_id, sequence, state_data, schema_version, created_at)
+             VALUES (?, ?, ?, ?, datetime('now'))",
+        )
+        .bind(stream_id)
+        .bind(sequence)
+        .bind(state.to_string())
+        .bind(schema_version)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Loads the snapshot of `stream_id` if it matches `schema_version`.
+    ///
+    /// Snapshots from another schema version, or whose state no longer parses,
+    /// are deleted so the next load rebuilds from events.
+    pub async fn load_snapshot(
+        &self,
+        stream_id: &str,
+        schema_version: i64,
+    ) -> Result<Option<Snapshot>> {
+        let row = sqlx::query_as::<_, SnapshotRow>(
+            "SELECT stream_id, sequence, state_data, schema_version, created_at
+             FROM snapshots WHERE stream_id = ?",
+        )
+        .bind(stream_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let state = match serde_json::from_str(&row.state_data) {
+            Ok(state) if row.schema_version == schema_version => state,
+            _ => {
+                self.delete_snapshot(stream_id).await?;
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(Snapshot {
+            stream_id: row.stream_id,
+            sequence: row.sequence,
+            state,
+            schema_version: row.schema_version,
+            created_at: row.created_at,
+        }))
+    }
+
+    /// Loads the usable snapshot of `stream_id` plus the events recorded after it.
+    pub async fn load_stream_state(
+        &self,
+        stream_id: &str,
+        schema_version: i64,
+    ) -> Result<StreamState> {
+        let snapshot = self.load_snapshot(stream_id, schema_version).await?;
+        let after = snapshot.as_ref().map_or(0, |s| s.sequence);
+        let events = self.read_stream_since(stream_id, after).await?;
+        let snapshot_due = events.len() as i64 >= SNAPSHOT_THRESHOLD;
+
+        Ok(StreamState {
+            snapshot,
+            events,
+            snapshot_due,
+        })
+    }
+
+    pub async fn delete_snapshot(&self, stream_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM snapshots WHERE stream_id = ?")
+            .bind(stream_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes every snapshot not taken under `schema_version`. Returns how
+    /// many were removed.
+    pub async fn invalidate_snapshots(&self, schema_version: i64) -> Result<u64> {
+        let deleted = sqlx::query("DELETE FROM snapshots WHERE schema_version != ?")
+            .bind(schema_version)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::bounty_modified;
+    use serde_json::json;
+
+    async fn store_with_events(count: usize) -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        let events: Vec<_> = (0..count).map(|i| bounty_modified(i as i64)).collect();
+        store
+            .append_events("player-1", None, None, &events)
+            .await
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn loads_snapshot_and_only_later_events() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(60).await;
+            let before = store.load_stream_state("player-1", 1).await.unwrap();
+            assert!(before.snapshot.is_none());
+            assert!(before.snapshot_due);
+
+            store
+                .save_snapshot("player-1", 58, &json!({ "bounty": 1711 }), 1)
+                .await
+                .unwrap();
+
+            let after = store.load_stream_state("player-1", 1).await.unwrap();
+            assert_eq!(after.snapshot.unwrap().state["bounty"], 1711);
+            assert_eq!(after.events.len(), 2);
+            assert!(!after.snapshot_due);
+        });
+    }
+
+    #[test]
+    fn discards_snapshot_from_other_schema_version() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(3).await;
+            store
+                .save_snapshot("player-1", 3, &json!({}), 1)
+                .await
+                .unwrap();
+
+            assert!(store.load_snapshot("player-1", 2).await.unwrap().is_none());
+            assert!(store.load_snapshot("player-1", 1).await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn invalidates_snapshots_by_schema_version() {
+        tauri::async_runtime::block_on(async {
+            let store = store_with_events(3).await;
+            let state = json!({});
+            store.save_snapshot("player-1", 3, &state, 1).await.unwrap();
+            store.save_snapshot("player-2", 0, &state, 2).await.unwrap();
+
+            assert_eq!(store.invalidate_snapshots(2).await.unwrap(), 1);
+            assert!(store.load_snapshot("player-2", 2).await.unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,260 @@
+//! Undo of the last command appended to a stream.
+//!
+//! A command's events share one `commandId` in their metadata, so the last
+//! command is the run of events at the end of the stream carrying the same
+//! id. Undoing it moves those rows to `undone_events` and drops any snapshot
+//! taken after the remaining head. The tail of the hash chain goes with
+//! them, so the stream still verifies, and the stream's generator rewinds to
+//! where it stood before the command.
+//!
+//! Whether undo is allowed follows the owning profile's [`UndoPolicy`].
+
+use serde::Serialize;
+
+use super::profiles::stream_profile;
+use super::rng;
+use super::settings::{load_settings, ProfileSettings};
+use super::{EventMetadata, EventStore, StoredEvent};
+use crate::error::{Error, Result, UndoRefusal};
+
+/// What a profile may undo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UndoPolicy {
+    Disabled,
+    /// Commands may be undone until one that changed the phase is reached.
+    CurrentPhase,
+}
+
+impl UndoPolicy {
+    pub fn for_settings(settings: &ProfileSettings) -> Self {
+        if settings.ironman {
+            UndoPolicy::Disabled
+        } else {
+            UndoPolicy::CurrentPhase
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoneCommand {
+    pub command_id: String,
+    /// The removed events, in sequence order.
+    pub events: Vec<StoredEvent>,
+    /// Stream version after the undo.
+    pub stream_version: i64,
+}
+
+impl EventStore {
+    /// Removes the events of the last command appended to `stream_id`.
+    ///
+    /// Fails with [`Error::UndoRefused`] when the profile owning the stream
+    /// runs ironman, when the command changed the phase, or when the last
+    /// event carries no command id.
+    pub async fn undo_last_command(&self, stream_id: &str) -> Result<UndoneCommand> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let last = sqlx::query_as::<_, StoredEvent>(
+            "SELECT * FROM events WHERE stream_id = ? ORDER BY sequence DESC LIMIT 1",
+        )
+        .bind(stream_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::UndoRefused(UndoRefusal::EmptyStream))?;
+        let command_id = last
+            .metadata
+            .as_deref()
+            .and_then(|raw| serde_json::from_str::<EventMetadata>(raw).ok())
+            .and_then(|metadata| metadata.command_id)
+            .ok_or(Error::UndoRefused(UndoRefusal::UntaggedEvent))?;
+
+        let profile_id = stream_profile(&mut *tx, stream_id).await?;
+        let policy = UndoPolicy::for_settings(&load_settings(&mut *tx, &profile_id).await?);
+        if policy == UndoPolicy::Disabled {
+            return Err(Error::UndoRefused(UndoRefusal::Ironman));
+        }
+
+        // The command starts right after the last event tagged otherwise.
+        let boundary: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(sequence), 0) FROM events
+             WHERE stream_id = ?
+               AND CASE WHEN json_valid(metadata) THEN json_extract(metadata, '$.commandId') END IS NOT ?",
+        )
+        .bind(stream_id)
+        .bind(&command_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let events = sqlx::query_as::<_, StoredEvent>(
+            "SELECT * FROM events WHERE stream_id = ? AND sequence > ? ORDER BY sequence",
+        )
+        .bind(stream_id)
+        .bind(boundary)
+        .fetch_all(&mut *tx)
+        .await?;
+        if events.iter().any(|e| e.event_type == "PHASE_CHANGED") {
+            return Err(Error::UndoRefused(UndoRefusal::PhaseBoundary));
+        }
+
+        rng::restore_at(&mut tx, stream_id, boundary, stream_id).await?;
+        sqlx::query(
+            "INSERT INTO undone_events
+                 (event_id, stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash)
+             SELECT event_id, stream_id, event_type, event_data, metadata, sequence, created_at, prev_hash, hash
+             FROM events WHERE stream_id = ? AND sequence > ?",
+        )
+        .bind(stream_id)
+        .bind(boundary)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM events WHERE stream_id = ? AND sequence > ?")
+            .bind(stream_id)
+            .bind(boundary)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM snapshots WHERE stream_id = ? AND sequence > ?")
+            .bind(stream_id)
+            .bind(boundary)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(UndoneCommand {
+            command_id,
+            events,
+            stream_version: boundary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::{bounty_modified, Divergence, NewEvent};
+    use serde_json::json;
+
+    fn phase_changed() -> NewEvent {
+        NewEvent {
+            event_type: "PHASE_CHANGED".to_string(),
+            data: json!({ "timestamp": "t", "fromPhase": "quest_hub", "toPhase": "narrative" }),
+        }
+    }
+
+    async fn store() -> EventStore {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        store
+            .append_events(
+                "player-1",
+                None,
+                Some("accept"),
+                &[bounty_modified(1), phase_changed()],
+            )
+            .await
+            .unwrap();
+        store
+            .append_events(
+                "player-1",
+                None,
+                Some("choose"),
+                &[bounty_modified(2), bounty_modified(3)],
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn undoes_the_last_command_within_the_phase() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            store
+                .save_snapshot("player-1", 4, &json!({}), 1)
+                .await
+                .unwrap();
+
+            let undone = store.undo_last_command("player-1").await.unwrap();
+            assert_eq!(undone.command_id, "choose");
+            assert_eq!(undone.events.len(), 2);
+            assert_eq!(undone.stream_version, 2);
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 2);
+            assert!(store.load_snapshot("player-1", 1).await.unwrap().is_none());
+            assert!(store
+                .verify_stream("player-1")
+                .await
+                .unwrap()
+                .first_broken
+                .is_none());
+
+            let archived: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM undone_events")
+                .fetch_one(&store.pool)
+                .await
+                .unwrap();
+            assert_eq!(archived, 2);
+
+            // The command before it changed phase.
+            assert!(matches!(
+                store.undo_last_command("player-1").await,
+                Err(Error::UndoRefused(UndoRefusal::PhaseBoundary))
+            ));
+        });
+    }
+
+    #[test]
+    fn ironman_profiles_cannot_undo_even_on_forks() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            store
+                .update_profile_settings("1", &ProfileSettings { ironman: true })
+                .await
+                .unwrap();
+            store.fork_stream("player-1", 4, "player-99").await.unwrap();
+
+            for stream_id in ["player-1", "player-99"] {
+                assert!(matches!(
+                    store.undo_last_command(stream_id).await,
+                    Err(Error::UndoRefused(UndoRefusal::Ironman))
+                ));
+            }
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 4);
+        });
+    }
+
+    #[test]
+    fn ironman_stays_on_and_keeps_loads_from_truncating() {
+        tauri::async_runtime::block_on(async {
+            let store = store().await;
+            store
+                .save_game("1", "before choosing", &json!({}))
+                .await
+                .unwrap();
+            store
+                .append_events("player-1", None, None, &[bounty_modified(4)])
+                .await
+                .unwrap();
+            store
+                .update_profile_settings("1", &ProfileSettings { ironman: true })
+                .await
+                .unwrap();
+
+            assert!(matches!(
+                store
+                    .update_profile_settings("1", &ProfileSettings { ironman: false })
+                    .await,
+                Err(Error::InvalidProfileSettings(_))
+            ));
+            assert!(store.profile_settings("1").await.unwrap().ironman);
+            assert!(matches!(
+                store
+                    .load_game("1", "before choosing", Some(Divergence::Truncate))
+                    .await,
+                Err(Error::UndoRefused(UndoRefusal::Ironman))
+            ));
+            assert_eq!(store.stream_version("player-1").await.unwrap(), 5);
+            store
+                .load_game("1", "before choosing", Some(Divergence::Fork))
+                .await
+                .unwrap();
+        });
+    }
+}
@@ -0,0 +1,208 @@
+//! Upcasting of stored events to the current shapes in `events.ts`.
+//!
+//! Every append stamps `schemaVersion` into the event metadata; events written
+//! before stamping count as version 1. On read, upcasters registered under
+//! `(event_type, schema_version)` are chained until the event reaches its
+//! current version, so old `game.db` files keep replaying after the domain
+//! changes. Stored rows are never rewritten.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::StoredEvent;
+
+/// Schema version assumed for events stored without one.
+pub const INITIAL_SCHEMA_VERSION: u32 = 1;
+
+/// One step from `from_version` to `from_version + 1`, optionally renaming
+/// the event type.
+#[derive(Clone, Copy)]
+struct Upcaster {
+    to_type: &'static str,
+    transform: fn(Value) -> Value,
+}
+
+#[derive(Default)]
+pub struct UpcasterRegistry {
+    upcasters: HashMap<(&'static str, u32), Upcaster>,
+}
+
+impl UpcasterRegistry {
+    /// Registers the step that moves `event_type` data from `from_version`
+    /// to the next version.
+    // Unused until `game()` registers its first step.
+    #[allow(dead_code)]
+    pub fn register(
+        &mut self,
+        event_type: &'static str,
+        from_version: u32,
+        transform: fn(Value) -> Value,
+    ) -> &mut Self {
+        self.register_rename(event_type, from_version, event_type, transform)
+    }
+
+    /// Like [`register`](Self::register), but the upcast event is also
+    /// renamed to `to_type`, whose history then continues at
+    /// `from_version + 1`.
+    pub fn register_rename(
+        &mut self,
+        event_type: &'static str,
+        from_version: u32,
+        to_type: &'static str,
+        transform: fn(Value) -> Value,
+    ) -> &mut Self {
+        self.upcasters
+            .insert((event_type, from_version), Upcaster { to_type, transform });
+        self
+    }
+
+    /// Version stamped on newly appended events of `event_type`.
+    pub fn current_version(&self, event_type: &str) -> u32 {
+        self.upcasters
+            .iter()
+            .filter(|(_, upcaster)| upcaster.to_type == event_type)
+            .map(|((_, from_version), _)| from_version + 1)
+            .max()
+            .unwrap_or(INITIAL_SCHEMA_VERSION)
+    }
+
+    /// Brings `event` up to the current version of its type.
+    ///
+    /// Events whose data or metadata do not parse are returned untouched for
+    /// the caller to deal with.
+    pub fn upcast(&self, mut event: StoredEvent) -> StoredEvent {
+        let mut metadata: Map<String, Value> = event
+            .metadata
+            .as_deref()
+            .and_then(|raw| serde_json::from_str(raw).ok())
+            .unwrap_or_default();
+        let mut version = metadata
+            .get("schemaVersion")
+            .and_then(Value::as_u64)
+            .map_or(INITIAL_SCHEMA_VERSION, |v| v as u32);
+
+        let Some(first) = self.upcasters.get(&(event.event_type.as_str(), version)) else {
+            return event;
+        };
+        let Ok(mut data) = serde_json::from_str::<Value>(&event.event_data) else {
+            return event;
+        };
+
+        let mut step = Some(*first);
+        while let Some(upcaster) = step {
+            data = (upcaster.transform)(data);
+            version += 1;
+            step = self.upcasters.get(&(upcaster.to_type, version)).copied();
+            event.event_type = upcaster.to_type.to_string();
+        }
+
+        metadata.insert("schemaVersion".to_string(), version.into());
+        event.event_data = data.to_string();
+        event.metadata = Some(Value::Object(metadata).to_string());
+        event
+    }
+
+    /// Upcasters for the event catalog in `events.ts`.
+    ///
+    /// Empty while every event type is still at version 1; the first change
+    /// to a stored shape registers its step here.
+    pub fn game() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stored(event_type: &str, data: Value, metadata: Option<Value>) -> StoredEvent {
+        StoredEvent {
+            event_id: "e1".to_string(),
+            stream_id: "player-1".to_string(),
+            event_type: event_type.to_string(),
+            event_data: data.to_string(),
+            metadata: metadata.map(|m| m.to_string()),
+            sequence: 1,
+            created_at: "2025-01-01 00:00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn chains_upcasters_across_a_rename() {
+        let mut registry = UpcasterRegistry::default();
+        registry
+            .register("DILEMMA_SHOWN", 1, |mut data| {
+                data["questId"] = json!("unknown");
+                data
+            })
+            .register_rename("DILEMMA_SHOWN", 2, "DILEMMA_PRESENTED", |data| data);
+        assert_eq!(registry.current_version("DILEMMA_PRESENTED"), 3);
+        assert_eq!(registry.current_version("CHOICE_MADE"), 1);
+
+        let event = registry.upcast(stored(
+            "DILEMMA_SHOWN",
+            json!({ "dilemmaId": "d1" }),
+            Some(json!({ "timestamp": "t", "commandId": "c" })),
+        ));
+        assert_eq!(event.event_type, "DILEMMA_PRESENTED");
+        assert_eq!(
+            event.event_data,
+            r#"{"dilemmaId":"d1","questId":"unknown"}"#
+        );
+        let metadata: Value = serde_json::from_str(event.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["schemaVersion"], 3);
+        assert_eq!(metadata["commandId"], "c");
+    }
+
+    /// A hypothetical v2 that made `cardIdsProvided` and `isSecret`
+    /// required on ALLIANCE_FORMED.
+    fn alliance_registry() -> UpcasterRegistry {
+        let mut registry = UpcasterRegistry::default();
+        registry.register("ALLIANCE_FORMED", 1, |mut data| {
+            if let Value::Object(fields) = &mut data {
+                fields
+                    .entry("cardIdsProvided")
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                fields.entry("isSecret").or_insert(Value::Bool(false));
+            }
+            data
+        });
+        registry
+    }
+
+    #[test]
+    fn leaves_current_events_untouched() {
+        let registry = alliance_registry();
+        let data = json!({ "factionId": "ironveil", "bountyShare": 0.2 });
+        let event = registry.upcast(stored(
+            "ALLIANCE_FORMED",
+            data.clone(),
+            Some(json!({ "schemaVersion": 2 })),
+        ));
+        assert_eq!(event.event_data, data.to_string());
+    }
+
+    #[test]
+    fn fills_fields_missing_from_old_saves() {
+        let registry = alliance_registry();
+        let event = registry.upcast(stored(
+            "ALLIANCE_FORMED",
+            json!({ "factionId": "ironveil", "bountyShare": 0.2 }),
+            None,
+        ));
+        let data: Value = serde_json::from_str(&event.event_data).unwrap();
+        assert_eq!(data["isSecret"], false);
+        assert_eq!(data["cardIdsProvided"], json!([]));
+    }
+
+    #[test]
+    fn game_events_are_all_at_their_first_version() {
+        let registry = UpcasterRegistry::game();
+        assert_eq!(registry.current_version("ALLIANCE_FORMED"), 1);
+        let data = json!({ "factionId": "ironveil", "bountyShare": 0.2 });
+        let event = registry.upcast(stored("ALLIANCE_FORMED", data.clone(), None));
+        assert_eq!(event.event_data, data.to_string());
+    }
+}
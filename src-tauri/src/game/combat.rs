@@ -0,0 +1,84 @@
+//! The d20 rules of `resolveBattle` in `combat.ts`.
+//!
+//! The cards in the same position of two fleets meet in each of five
+//! rounds. Both roll d20 + attack against 10 + the other's defense, and the
+//! round goes to the side that hits when the other misses. The battle goes
+//! to the side that won more rounds.
+
+use super::rng::Lcg;
+use super::types::{BattleOutcome, Card, RoundOutcome};
+
+/// Cards in a fleet, one per round.
+pub const FLEET_SIZE: usize = 5;
+
+/// `resolveAttack`: whether `roll` + `attack` reaches 10 + `defense`.
+pub fn hits(roll: i32, attack: i32, defense: i32) -> bool {
+    roll + attack >= 10 + defense
+}
+
+/// `determineRoundOutcome`.
+pub fn round_outcome(player_hit: bool, opponent_hit: bool) -> RoundOutcome {
+    match (player_hit, opponent_hit) {
+        (true, false) => RoundOutcome::PlayerWon,
+        (false, true) => RoundOutcome::OpponentWon,
+        _ => RoundOutcome::Draw,
+    }
+}
+
+/// `resolveBattle` for fleets in position order, rolling the player's
+/// attack before the opponent's in each round.
+pub fn resolve_battle(
+    player_fleet: &[Card],
+    opponent_fleet: &[Card],
+    rng: &mut Lcg,
+) -> BattleOutcome {
+    let (mut player_wins, mut opponent_wins) = (0, 0);
+    for (player, opponent) in player_fleet.iter().zip(opponent_fleet) {
+        let player_hit = hits(rng.d20(), player.attack, opponent.defense);
+        let opponent_hit = hits(rng.d20(), opponent.attack, player.defense);
+        match round_outcome(player_hit, opponent_hit) {
+            RoundOutcome::PlayerWon => player_wins += 1,
+            RoundOutcome::OpponentWon => opponent_wins += 1,
+            RoundOutcome::Draw => {}
+        }
+    }
+    match player_wins.cmp(&opponent_wins) {
+        std::cmp::Ordering::Greater => BattleOutcome::Victory,
+        std::cmp::Ordering::Less => BattleOutcome::Defeat,
+        std::cmp::Ordering::Equal => BattleOutcome::Draw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn card(attack: i32, defense: i32) -> Card {
+        serde_json::from_value(json!({
+            "id": "card",
+            "name": "card",
+            "faction": "ironveil",
+            "attack": attack,
+            "defense": defense,
+            "hull": 4,
+            "agility": 3,
+            "energyCost": 2,
+            "abilities": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rounds_follow_the_rolls() {
+        assert!(hits(10, 1, 1));
+        assert!(!hits(9, 1, 1));
+
+        // Seed 1 rolls 11 for the player, then 4 for the opponent.
+        let outcome = |opponent: Card| resolve_battle(&[card(0, 0)], &[opponent], &mut Lcg::new(1));
+        assert_eq!(outcome(card(0, 1)), BattleOutcome::Victory);
+        assert_eq!(outcome(card(6, 2)), BattleOutcome::Defeat);
+        assert_eq!(outcome(card(6, 1)), BattleOutcome::Draw);
+        assert_eq!(outcome(card(0, 2)), BattleOutcome::Draw);
+    }
+}
@@ -0,0 +1,712 @@
+//! The game event catalog, mirroring `game/events.ts` and the slices'
+//! `shared-kernel/events.ts`.
+//!
+//! Events travel as `{ type, data }` with camelCase fields, exactly as the
+//! webview appends them. Where the two TypeScript catalogs disagree, fields
+//! missing from either are optional here. Fields the catalog does not know
+//! about are ignored, so additive changes on the TypeScript side do not break
+//! appends.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::types::{
+    BattleOutcome, Card, EndingType, FactionId, GamePhase, Initiative, OpponentFactionId,
+    PostBattleDilemmaType, ReputationStatus, RollSummary, RoundOutcome, RoundResult, Side,
+};
+
+/// Every event type in the catalog, in `game/events.ts` order.
+pub const EVENT_TYPES: &[&str] = &[
+    "QUESTS_GENERATED",
+    "QUEST_VIEWED",
+    "QUEST_ACCEPTED",
+    "QUEST_DECLINED",
+    "QUEST_COMPLETED",
+    "QUEST_FAILED",
+    "DILEMMA_PRESENTED",
+    "CHOICE_MADE",
+    "FLAG_SET",
+    "ALLIANCE_PHASE_STARTED",
+    "ALLIANCE_TERMS_VIEWED",
+    "ALLIANCE_FORMED",
+    "ALLIANCE_REJECTED",
+    "ALLIANCES_DECLINED",
+    "ALLIANCES_FINALIZED",
+    "SECRET_ALLIANCE_FORMED",
+    "ALLIANCE_DISCOVERED",
+    "MEDIATION_STARTED",
+    "POSITION_VIEWED",
+    "MEDIATION_LEANED",
+    "MEDIATION_COLLAPSED",
+    "COMPROMISE_ACCEPTED",
+    "REPUTATION_CHANGED",
+    "REPUTATION_THRESHOLD_CROSSED",
+    "CARDS_UNLOCKED",
+    "CARDS_LOCKED",
+    "CARD_GAINED",
+    "CARD_LOST",
+    "BATTLE_TRIGGERED",
+    "CARD_SELECTED",
+    "CARD_DESELECTED",
+    "FLEET_COMMITTED",
+    "CARD_POSITIONED",
+    "ORDERS_LOCKED",
+    "BATTLE_STARTED",
+    "ROUND_STARTED",
+    "CARDS_REVEALED",
+    "INITIATIVE_RESOLVED",
+    "ATTACK_ROLLED",
+    "ROUND_RESOLVED",
+    "BATTLE_RESOLVED",
+    "BOUNTY_CALCULATED",
+    "BOUNTY_SHARED",
+    "OUTCOME_ACKNOWLEDGED",
+    "BOUNTY_MODIFIED",
+    "CHOICE_CONSEQUENCE_PRESENTED",
+    "CHOICE_CONSEQUENCE_ACKNOWLEDGED",
+    "QUEST_SUMMARY_PRESENTED",
+    "QUEST_SUMMARY_ACKNOWLEDGED",
+    "POST_BATTLE_DILEMMA_TRIGGERED",
+    "POST_BATTLE_CHOICE_MADE",
+    "GAME_STARTED",
+    "GAME_END_TRIGGERED",
+    "ENDING_DETERMINED",
+    "NEW_GAME_STARTED",
+    "GAME_ENDED",
+    "PHASE_CHANGED",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "data",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    rename_all_fields = "camelCase"
+)]
+pub enum GameEvent {
+    // ------------------------------------------------------------------------
+    // Quest events
+    // ------------------------------------------------------------------------
+    QuestsGenerated {
+        timestamp: String,
+        quest_ids: Vec<String>,
+    },
+    QuestViewed {
+        timestamp: String,
+        quest_id: String,
+    },
+    QuestAccepted {
+        timestamp: String,
+        quest_id: String,
+        faction_id: FactionId,
+        initial_bounty: f64,
+        initial_card_ids: Vec<String>,
+    },
+    QuestDeclined {
+        timestamp: String,
+        quest_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    QuestCompleted {
+        timestamp: String,
+        quest_id: String,
+        outcome: QuestCompletion,
+        final_bounty: f64,
+    },
+    QuestFailed {
+        timestamp: String,
+        quest_id: String,
+        failure_point: String,
+        reason: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Narrative events
+    // ------------------------------------------------------------------------
+    DilemmaPresented {
+        timestamp: String,
+        dilemma_id: String,
+        quest_id: String,
+    },
+    ChoiceMade {
+        timestamp: String,
+        dilemma_id: String,
+        choice_id: String,
+        quest_id: String,
+    },
+    FlagSet {
+        timestamp: String,
+        flag_name: String,
+        value: bool,
+    },
+
+    // ------------------------------------------------------------------------
+    // Alliance events
+    // ------------------------------------------------------------------------
+    AlliancePhaseStarted {
+        timestamp: String,
+        quest_id: String,
+        battle_context: String,
+        available_faction_ids: Vec<FactionId>,
+    },
+    AllianceTermsViewed {
+        timestamp: String,
+        faction_id: FactionId,
+    },
+    AllianceFormed {
+        timestamp: String,
+        faction_id: FactionId,
+        bounty_share: f64,
+        card_ids_provided: Vec<String>,
+        is_secret: bool,
+    },
+    AllianceRejected {
+        timestamp: String,
+        faction_id: FactionId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    AlliancesDeclined {
+        timestamp: String,
+        quest_id: String,
+    },
+    AlliancesFinalized {
+        timestamp: String,
+        quest_id: String,
+        alliance_count: i32,
+    },
+    SecretAllianceFormed {
+        timestamp: String,
+        faction_id: FactionId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        public_faction_id: Option<FactionId>,
+        discovery_risk: f64,
+        card_ids_provided: Vec<String>,
+    },
+    AllianceDiscovered {
+        timestamp: String,
+        secret_faction_id: FactionId,
+        discovered_by_faction_id: FactionId,
+        reputation_penalty: i32,
+    },
+
+    // ------------------------------------------------------------------------
+    // Mediation events
+    // ------------------------------------------------------------------------
+    MediationStarted {
+        timestamp: String,
+        mediation_id: String,
+        quest_id: String,
+        facilitator_faction_id: FactionId,
+        party_faction_ids: [FactionId; 2],
+    },
+    PositionViewed {
+        timestamp: String,
+        faction_id: FactionId,
+    },
+    MediationLeaned {
+        timestamp: String,
+        toward_faction_id: FactionId,
+        away_from_faction_id: FactionId,
+    },
+    MediationCollapsed {
+        timestamp: String,
+        reason: String,
+        battle_triggered: bool,
+    },
+    CompromiseAccepted {
+        timestamp: String,
+        terms: String,
+        bounty_modifier: f64,
+    },
+
+    // ------------------------------------------------------------------------
+    // Reputation events
+    // ------------------------------------------------------------------------
+    ReputationChanged {
+        timestamp: String,
+        faction_id: FactionId,
+        delta: i32,
+        new_value: i32,
+        source: ReputationSource,
+    },
+    ReputationThresholdCrossed {
+        timestamp: String,
+        faction_id: FactionId,
+        old_status: ReputationStatus,
+        new_status: ReputationStatus,
+        direction: Direction,
+    },
+    CardsUnlocked {
+        timestamp: String,
+        faction_id: FactionId,
+        card_ids: Vec<String>,
+        reason: String,
+    },
+    CardsLocked {
+        timestamp: String,
+        faction_id: FactionId,
+        card_ids: Vec<String>,
+        reason: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Card events
+    // ------------------------------------------------------------------------
+    /// The decider includes the card's stats at acquisition so projections
+    /// need no lookup; the slices emit only the id.
+    CardGained {
+        timestamp: String,
+        card_id: String,
+        faction_id: FactionId,
+        source: CardSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attack: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        defense: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hull: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        agility: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        energy_cost: Option<i32>,
+    },
+    CardLost {
+        timestamp: String,
+        card_id: String,
+        faction_id: FactionId,
+        reason: CardLossReason,
+    },
+
+    // ------------------------------------------------------------------------
+    // Battle events
+    // ------------------------------------------------------------------------
+    BattleTriggered {
+        timestamp: String,
+        battle_id: String,
+        quest_id: String,
+        context: String,
+        opponent_type: String,
+        opponent_faction_id: OpponentFactionId,
+        difficulty: Difficulty,
+    },
+    CardSelected {
+        timestamp: String,
+        card_id: String,
+        battle_id: String,
+    },
+    CardDeselected {
+        timestamp: String,
+        card_id: String,
+        battle_id: String,
+    },
+    FleetCommitted {
+        timestamp: String,
+        battle_id: String,
+        card_ids: Vec<String>,
+    },
+    CardPositioned {
+        timestamp: String,
+        card_id: String,
+        /// 1-5.
+        position: i32,
+        battle_id: String,
+    },
+    OrdersLocked {
+        timestamp: String,
+        battle_id: String,
+        /// Card ids in position order.
+        positions: Vec<String>,
+    },
+    BattleStarted {
+        timestamp: String,
+        battle_id: String,
+        player_card_ids: Vec<String>,
+        opponent_cards: Vec<Card>,
+    },
+    RoundStarted {
+        timestamp: String,
+        battle_id: String,
+        round_number: i32,
+    },
+    CardsRevealed {
+        timestamp: String,
+        battle_id: String,
+        round_number: i32,
+        player_card: Card,
+        opponent_card: Card,
+    },
+    InitiativeResolved {
+        timestamp: String,
+        battle_id: String,
+        round_number: i32,
+        first_striker: Initiative,
+        player_agility: i32,
+        opponent_agility: i32,
+    },
+    AttackRolled {
+        timestamp: String,
+        battle_id: String,
+        round_number: i32,
+        attacker: Side,
+        roll: i32,
+        modifier: i32,
+        total: i32,
+        target_armor: i32,
+        target_number: i32,
+        hit: bool,
+    },
+    RoundResolved {
+        timestamp: String,
+        battle_id: String,
+        round_number: i32,
+        outcome: RoundOutcome,
+        player_card: Card,
+        opponent_card: Card,
+        player_roll: RollSummary,
+        opponent_roll: RollSummary,
+    },
+    BattleResolved {
+        timestamp: String,
+        battle_id: String,
+        outcome: BattleOutcome,
+        player_wins: i32,
+        opponent_wins: i32,
+        draws: i32,
+        rounds_summary: Vec<RoundResult>,
+    },
+
+    // ------------------------------------------------------------------------
+    // Consequence events
+    // ------------------------------------------------------------------------
+    BountyCalculated {
+        timestamp: String,
+        battle_id: String,
+        base: f64,
+        shares: Vec<BountyShare>,
+        modifiers: Vec<BountyAdjustment>,
+        net: f64,
+    },
+    BountyShared {
+        timestamp: String,
+        faction_id: FactionId,
+        amount: f64,
+    },
+    OutcomeAcknowledged {
+        timestamp: String,
+        battle_id: String,
+    },
+    BountyModified {
+        timestamp: String,
+        amount: f64,
+        new_value: f64,
+        source: BountySource,
+        reason: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Choice consequence events
+    // ------------------------------------------------------------------------
+    ChoiceConsequencePresented {
+        timestamp: String,
+        dilemma_id: String,
+        choice_id: String,
+        quest_id: String,
+        choice_label: String,
+        narrative_text: String,
+        triggers_next: NextStep,
+        consequences: ChoiceConsequences,
+    },
+    ChoiceConsequenceAcknowledged {
+        timestamp: String,
+        dilemma_id: String,
+        choice_id: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Quest summary events
+    // ------------------------------------------------------------------------
+    QuestSummaryPresented {
+        timestamp: String,
+        quest_id: String,
+        quest_title: String,
+        outcome: QuestSummaryOutcome,
+    },
+    QuestSummaryAcknowledged {
+        timestamp: String,
+        quest_id: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Post-battle events
+    // ------------------------------------------------------------------------
+    PostBattleDilemmaTriggered {
+        timestamp: String,
+        battle_id: String,
+        dilemma_id: String,
+        dilemma_type: PostBattleDilemmaType,
+        context: String,
+    },
+    PostBattleChoiceMade {
+        timestamp: String,
+        dilemma_id: String,
+        choice_id: String,
+    },
+
+    // ------------------------------------------------------------------------
+    // Game lifecycle events
+    // ------------------------------------------------------------------------
+    GameStarted {
+        timestamp: String,
+        player_id: String,
+        starter_card_ids: Vec<String>,
+    },
+    GameEndTriggered {
+        timestamp: String,
+        quests_completed: i32,
+        total_play_time_seconds: f64,
+    },
+    EndingDetermined {
+        timestamp: String,
+        ending_type: EndingType,
+        title: String,
+        subtitle: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        primary_faction_id: Option<FactionId>,
+    },
+    NewGameStarted {
+        timestamp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous_ending_type: Option<EndingType>,
+    },
+    GameEnded {
+        timestamp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_quests: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        final_bounty: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<GameEndReason>,
+    },
+
+    // ------------------------------------------------------------------------
+    // Phase events
+    // ------------------------------------------------------------------------
+    PhaseChanged {
+        timestamp: String,
+        from_phase: GamePhase,
+        to_phase: GamePhase,
+    },
+}
+
+impl GameEvent {
+    pub fn is_known_type(event_type: &str) -> bool {
+        EVENT_TYPES.contains(&event_type)
+    }
+
+    /// Parses an event from the `type`/`data` pair it is stored as.
+    pub fn from_parts(event_type: &str, data: &Value) -> serde_json::Result<Self> {
+        serde_json::from_value(json!({ "type": event_type, "data": data }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestCompletion {
+    Completed,
+    Full,
+    Partial,
+    Compromised,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestSummaryOutcome {
+    Completed,
+    Failed,
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameEndReason {
+    Completed,
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReputationSource {
+    Quest,
+    Choice,
+    Alliance,
+    Battle,
+    Betrayal,
+    Discovery,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSource {
+    Starter,
+    Quest,
+    Alliance,
+    Choice,
+    Unlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardLossReason {
+    Reputation,
+    Betrayal,
+    Choice,
+    Penalty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BountySource {
+    Choice,
+    Alliance,
+    Quest,
+    Penalty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NextStep {
+    Dilemma,
+    Battle,
+    Alliance,
+    Mediation,
+    QuestComplete,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BountyShare {
+    pub faction_id: FactionId,
+    pub amount: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BountyAdjustment {
+    pub amount: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChoiceConsequences {
+    pub reputation_changes: Vec<ReputationChange>,
+    pub cards_gained: Vec<String>,
+    pub cards_lost: Vec<String>,
+    pub bounty_change: Option<BountyChange>,
+    pub flags_set: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReputationChange {
+    pub faction_id: FactionId,
+    pub delta: i32,
+    pub new_value: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BountyChange {
+    pub amount: f64,
+    pub new_value: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_listed_type_is_a_variant() {
+        for event_type in EVENT_TYPES {
+            // An empty payload fails on a missing field, never on the tag.
+            let err = GameEvent::from_parts(event_type, &json!({})).unwrap_err();
+            assert!(
+                err.to_string().starts_with("missing field"),
+                "{event_type}: {err}"
+            );
+        }
+        assert!(!GameEvent::is_known_type("BOUNTY_CHANGED"));
+    }
+
+    #[test]
+    fn round_trips_the_webview_shape() {
+        let data = json!({
+            "timestamp": "2025-01-01T00:00:00.000Z",
+            "factionId": "void_wardens",
+            "publicFactionId": "meridian",
+            "discoveryRisk": 0.3,
+            "cardIdsProvided": ["warden_picket"],
+        });
+        let event = GameEvent::from_parts("SECRET_ALLIANCE_FORMED", &data).unwrap();
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({ "type": "SECRET_ALLIANCE_FORMED", "data": data })
+        );
+    }
+
+    #[test]
+    fn accepts_both_typescript_catalogs() {
+        let slice = json!({
+            "timestamp": "t",
+            "cardId": "ashfall_ember",
+            "factionId": "ashfall",
+            "source": "choice",
+        });
+        assert!(GameEvent::from_parts("CARD_GAINED", &slice).is_ok());
+        let decider = json!({
+            "timestamp": "t",
+            "cardId": "ashfall_ember",
+            "factionId": "ashfall",
+            "source": "choice",
+            "name": "Ember",
+            "attack": 3,
+            "defense": 1,
+            "hull": 4,
+            "agility": 4,
+            "energyCost": 2,
+        });
+        assert!(GameEvent::from_parts("CARD_GAINED", &decider).is_ok());
+        let ended = json!({ "timestamp": "t", "reason": "abandoned" });
+        assert!(GameEvent::from_parts("GAME_ENDED", &ended).is_ok());
+    }
+
+    #[test]
+    fn rejects_values_outside_the_catalog() {
+        let err = GameEvent::from_parts(
+            "PHASE_CHANGED",
+            &json!({ "timestamp": "t", "fromPhase": "battle", "toPhase": "victory_lap" }),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("victory_lap"), "{err}");
+    }
+}
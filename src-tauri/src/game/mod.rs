@@ -0,0 +1,10 @@
+//! Rust model of the game domain in `src/lib/game`.
+
+pub mod combat;
+pub mod events;
+pub mod opponents;
+pub mod rng;
+pub mod types;
+
+pub use events::GameEvent;
+pub use rng::Lcg;
@@ -0,0 +1,176 @@
+//! Fleet templates of `opponents.ts` and the fleets drawn from them.
+//!
+//! Selection follows `selectCards`, drawing from an [`Lcg`] where the
+//! TypeScript uses `Math.random()`.
+
+use serde::{Deserialize, Serialize};
+
+use super::events::Difficulty;
+use super::rng::Lcg;
+use super::types::{Card, OpponentFactionId};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetTemplate {
+    pub name: String,
+    pub faction_id: OpponentFactionId,
+    pub difficulty: Difficulty,
+    pub description: String,
+    pub card_pool: Vec<CardTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardTemplate {
+    pub id: String,
+    pub name: String,
+    pub attack: i32,
+    pub defense: i32,
+    pub hull: i32,
+    pub agility: i32,
+    pub energy_cost: i32,
+    /// Selection weight; weight 1 marks the pool's flagships.
+    pub weight: i32,
+}
+
+/// Added to every stat of a generated card, before the stat's floor.
+fn stat_bonus(difficulty: Difficulty) -> i32 {
+    match difficulty {
+        Difficulty::Easy => -1,
+        Difficulty::Medium => 0,
+        Difficulty::Hard => 1,
+    }
+}
+
+/// `selectCards`: `count` templates by weight, at most two of each. On hard,
+/// one flagship is always in and the rest come from the three rarest
+/// templates left.
+pub fn select_cards<'a>(
+    pool: &'a [CardTemplate],
+    count: usize,
+    difficulty: Difficulty,
+    rng: &mut Lcg,
+) -> Vec<&'a CardTemplate> {
+    let hard = difficulty == Difficulty::Hard;
+    let mut available: Vec<&CardTemplate> = pool.iter().collect();
+    let mut selected: Vec<&CardTemplate> = Vec::new();
+
+    if hard {
+        let flagships: Vec<_> = available
+            .iter()
+            .copied()
+            .filter(|card| card.weight == 1)
+            .collect();
+        if !flagships.is_empty() {
+            let flagship = flagships[rng.below(flagships.len() as u64) as usize];
+            selected.push(flagship);
+            if let Some(index) = available.iter().position(|card| card.id == flagship.id) {
+                available.remove(index);
+            }
+        }
+    }
+
+    while selected.len() < count && !available.is_empty() {
+        let card = if hard {
+            available.sort_by_key(|card| card.weight);
+            available[rng.below(available.len().min(3) as u64) as usize]
+        } else {
+            let total: i32 = available.iter().map(|card| card.weight).sum();
+            let mut remaining = rng.below(total.max(1) as u64) as i32;
+            *available
+                .iter()
+                .find(|card| {
+                    remaining -= card.weight;
+                    remaining < 0
+                })
+                .unwrap_or(&available[0])
+        };
+        selected.push(card);
+        if selected.iter().filter(|other| other.id == card.id).count() >= 2 {
+            if let Some(index) = available.iter().position(|other| other.id == card.id) {
+                available.remove(index);
+            }
+        }
+    }
+    selected
+}
+
+/// `templateToCard`: the card in position `index` of a fleet of
+/// `faction_id` at `difficulty`.
+pub fn template_to_card(
+    template: &CardTemplate,
+    faction_id: OpponentFactionId,
+    difficulty: Difficulty,
+    index: usize,
+) -> Card {
+    let bonus = stat_bonus(difficulty);
+    Card {
+        id: format!("{}_{index}", template.id),
+        name: template.name.clone(),
+        faction: faction_id,
+        attack: (template.attack + bonus).max(1),
+        defense: (template.defense + bonus).max(0),
+        hull: (template.hull + bonus).max(1),
+        agility: (template.agility + bonus).max(1),
+        energy_cost: template.energy_cost,
+        abilities: Vec::new(),
+        flavor_text: None,
+        is_liaison: None,
+        favor_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(id: &str, weight: i32) -> CardTemplate {
+        CardTemplate {
+            id: id.to_string(),
+            name: id.to_string(),
+            attack: 1,
+            defense: 0,
+            hull: 4,
+            agility: 3,
+            energy_cost: 2,
+            weight,
+        }
+    }
+
+    #[test]
+    fn fleets_hold_at_most_two_of_a_card() {
+        let pool = [template("common", 3), template("flagship", 1)];
+        for seed in 0..20 {
+            let mut rng = Lcg::new(seed);
+            let fleet = select_cards(&pool, 5, Difficulty::Medium, &mut rng);
+            assert_eq!(fleet.len(), 4);
+            assert!(fleet.iter().filter(|card| card.id == "flagship").count() == 2);
+
+            let fleet = select_cards(&pool, 5, Difficulty::Hard, &mut rng);
+            assert_eq!(fleet[0].id, "flagship");
+            assert_eq!(fleet.len(), 3);
+        }
+    }
+
+    #[test]
+    fn difficulty_shifts_stats_down_to_their_floor() {
+        let card = template_to_card(
+            &template("dart", 2),
+            OpponentFactionId::Scavengers,
+            Difficulty::Easy,
+            3,
+        );
+        assert_eq!(card.id, "dart_3");
+        assert_eq!(
+            (card.attack, card.defense, card.hull, card.agility),
+            (1, 0, 3, 2)
+        );
+        let card = template_to_card(
+            &template("dart", 2),
+            OpponentFactionId::Scavengers,
+            Difficulty::Hard,
+            0,
+        );
+        assert_eq!((card.attack, card.defense), (2, 1));
+    }
+}
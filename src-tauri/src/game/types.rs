@@ -0,0 +1,351 @@
+//! Domain types shared by the event catalog, mirroring `types.ts`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactionId {
+    Ironveil,
+    Ashfall,
+    Meridian,
+    VoidWardens,
+    SunderedOath,
+}
+
+/// A faction or one of the non-faction opponents (`OpponentFactionId` in `opponents.ts`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpponentFactionId {
+    Ironveil,
+    Ashfall,
+    Meridian,
+    VoidWardens,
+    SunderedOath,
+    Scavengers,
+    Pirates,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReputationStatus {
+    Devoted,
+    Friendly,
+    Neutral,
+    Unfriendly,
+    Hostile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    NotStarted,
+    QuestHub,
+    Narrative,
+    ChoiceConsequence,
+    Alliance,
+    Mediation,
+    CardSelection,
+    Deployment,
+    Battle,
+    Consequence,
+    PostBattleDilemma,
+    QuestSummary,
+    Ending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BattleOutcome {
+    Victory,
+    Defeat,
+    Draw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundOutcome {
+    PlayerWon,
+    OpponentWon,
+    Draw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostBattleDilemmaType {
+    Spoils,
+    Retreat,
+    Discovery,
+    Complication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndingType {
+    FactionCommander,
+    Broker,
+    Opportunist,
+    Conqueror,
+    Negotiator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Player,
+    Opponent,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::Player => Side::Opponent,
+            Side::Opponent => Side::Player,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Initiative {
+    Player,
+    Opponent,
+    Simultaneous,
+}
+
+// ----------------------------------------------------------------------------
+// Cards
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AbilityTrigger {
+    OnDeploy,
+    OnAttack,
+    OnDefend,
+    OnDestroyed,
+    StartTurn,
+    EndTurn,
+    Activated,
+    Passive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbilityTargetType {
+    #[serde(rename = "self")]
+    Own,
+    Ally,
+    Enemy,
+    AnyCard,
+    AllEnemies,
+    AllAllies,
+    Adjacent,
+    Flagship,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AreaTargets {
+    AllEnemies,
+    Adjacent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectTarget {
+    Attacker,
+    Adjacent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForcedTarget {
+    Ally,
+    Flagship,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnTarget {
+    #[serde(rename = "self")]
+    Own,
+    Enemy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Gt,
+    Lt,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AbilityEffect {
+    DealDamage {
+        amount: i32,
+    },
+    AreaDamage {
+        amount: i32,
+        targets: AreaTargets,
+    },
+    DamageFlagship {
+        amount: i32,
+    },
+    Repair {
+        amount: i32,
+    },
+    Shield {
+        amount: i32,
+        duration: i32,
+    },
+    RedirectDamage {
+        to: RedirectTarget,
+    },
+    RepairFlagship {
+        amount: i32,
+    },
+    Stun {
+        duration: i32,
+    },
+    DisableAbility {
+        duration: i32,
+    },
+    ForceAttack {
+        target: ForcedTarget,
+    },
+    Taunt {
+        duration: i32,
+    },
+    BoostAttack {
+        amount: i32,
+        duration: i32,
+    },
+    BoostDefense {
+        amount: i32,
+        duration: i32,
+    },
+    ReduceAttack {
+        amount: i32,
+        duration: i32,
+    },
+    ReduceDefense {
+        amount: i32,
+        duration: i32,
+    },
+    EnergyDrain {
+        amount: i32,
+    },
+    EnergyRestore {
+        amount: i32,
+    },
+    DrawCard {
+        amount: i32,
+    },
+    DiscardRandom {
+        amount: i32,
+    },
+    ReturnToHand {
+        target: ReturnTarget,
+    },
+    DestroyCard,
+    CopyStats,
+    GainFavor {
+        amount: i32,
+        /// A faction id, or `"same"` for the card's own faction.
+        faction: String,
+    },
+    Conditional {
+        condition: AbilityCondition,
+        effect: Box<AbilityEffect>,
+    },
+    Multi {
+        effects: Vec<AbilityEffect>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AbilityCondition {
+    HullBelow { percentage: i32 },
+    HullAbove { percentage: i32 },
+    EnergyAbove { amount: i32 },
+    AlliesCount { comparison: Comparison, count: i32 },
+    EnemiesCount { comparison: Comparison, count: i32 },
+    CardDestroyedThisTurn,
+    FirstCardPlayed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardAbility {
+    pub id: String,
+    pub name: String,
+    pub trigger: AbilityTrigger,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_cost: Option<i32>,
+    pub target_type: AbilityTargetType,
+    pub effect: AbilityEffect,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Card {
+    pub id: String,
+    pub name: String,
+    /// Opponent fleets coerce `scavengers`/`pirates` into this field, so it
+    /// accepts any opponent faction.
+    pub faction: OpponentFactionId,
+    pub attack: i32,
+    pub defense: i32,
+    pub hull: i32,
+    pub agility: i32,
+    pub energy_cost: i32,
+    pub abilities: Vec<CardAbility>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flavor_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_liaison: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favor_generation: Option<i32>,
+}
+
+// ----------------------------------------------------------------------------
+// Battle results
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombatRoll {
+    pub base: i32,
+    pub modifier: i32,
+    pub total: i32,
+    pub target: i32,
+    pub hit: bool,
+}
+
+/// The roll summary carried by `ROUND_RESOLVED`, which omits the target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollSummary {
+    pub base: i32,
+    pub modifier: i32,
+    pub total: i32,
+    pub hit: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundResult {
+    pub round_number: i32,
+    pub player_card: Card,
+    pub opponent_card: Card,
+    pub initiative: Initiative,
+    pub player_roll: CombatRoll,
+    pub opponent_roll: CombatRoll,
+    pub outcome: RoundOutcome,
+}
@@ -0,0 +1,137 @@
+//! Single-instance guard.
+//!
+//! Two running copies of the game would both append to the same streams of
+//! `game.db`. On startup the app takes an exclusive lock on a file in the app
+//! data dir and holds it until it exits. The lock belongs to the process, so
+//! the OS releases it however the process ends, crashes included, and a lock
+//! file left behind never blocks the next launch.
+//!
+//! A later launch normally never gets that far: `tauri-plugin-single-instance`
+//! hands it over to the running instance, which comes to the front. One that
+//! still finds the lock taken tells the player in a native dialog, since a
+//! game started from the desktop has no visible stderr.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::Path;
+
+/// Name of the lock file in the app data dir.
+pub const LOCK_FILE: &str = "instance.lock";
+
+const ALREADY_RUNNING: &str =
+    "Space Fortress is already running; close it before starting it again.";
+
+/// The exclusive lock of the running instance, released when dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Takes the instance lock in `dir`, or returns `None` when another process
+/// holds it.
+pub fn try_acquire(dir: &Path) -> io::Result<Option<InstanceLock>> {
+    std::fs::create_dir_all(dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+/// Tells the player that another instance holds the lock: on stderr, and
+/// in a native dialog on desktop.
+pub fn report_already_running() {
+    eprintln!("{ALREADY_RUNNING}");
+    #[cfg(desktop)]
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title("Space Fortress")
+        .set_description(ALREADY_RUNNING)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    /// Set to a directory, makes `hold_lock_until_killed` take the lock there.
+    const HOLDER_ENV: &str = "SPACE_FORTRESS_LOCK_HOLDER";
+
+    fn lock_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "space-fortress-{}-instance-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn only_one_holder_at_a_time() {
+        let dir = lock_dir("exclusive");
+        let first = try_acquire(&dir).unwrap();
+        assert!(first.is_some());
+        assert!(try_acquire(&dir).unwrap().is_none());
+
+        drop(first);
+        // The lock file is still there, but no longer locked.
+        assert!(dir.join(LOCK_FILE).exists());
+        assert!(try_acquire(&dir).unwrap().is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Child process of `lock_is_released_after_a_crash`.
+    #[test]
+    #[ignore = "run in a child process by lock_is_released_after_a_crash"]
+    fn hold_lock_until_killed() {
+        let Some(dir) = std::env::var_os(HOLDER_ENV) else {
+            return;
+        };
+        // The parent may be probing the lock at the same moment.
+        let _lock = loop {
+            if let Some(lock) = try_acquire(Path::new(&dir)).unwrap() {
+                break lock;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        loop {
+            std::thread::sleep(Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn lock_is_released_after_a_crash() {
+        let dir = lock_dir("crash");
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--ignored",
+                "--exact",
+                "instance::tests::hold_lock_until_killed",
+            ])
+            .env(HOLDER_ENV, &dir)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while try_acquire(&dir).unwrap().is_some() {
+            assert!(Instant::now() < deadline, "the child never took the lock");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        // Killed outright, the child never runs its destructors.
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(try_acquire(&dir).unwrap().is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,23 +1,120 @@
-use tauri_plugin_sql::{Migration, MigrationKind};
+mod backup;
+pub mod balance;
+pub mod battle;
+mod db;
+mod error;
+mod event_store;
+pub mod game;
+mod instance;
+mod migrations;
+mod replay;
+
+use tauri::Manager;
+
+use backup::BackupService;
+use event_store::EventStore;
+use replay::Replays;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let migrations = vec![
-        Migration {
-            version: 1,
-            description: "create event store tables",
-            sql: include_str!("../migrations/001_event_store.sql"),
-            kind: MigrationKind::Up,
-        }
-    ];
-
-    tauri::Builder::default()
+    let mut builder = tauri::Builder::default();
+    // First, so a second launch hands over to the running instance before
+    // anything else starts.
+    #[cfg(desktop)]
+    {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+        }));
+    }
+    builder
         .plugin(tauri_plugin_opener::init())
         .plugin(
             tauri_plugin_sql::Builder::default()
-                .add_migrations("sqlite:game.db", migrations)
+                .add_migrations(db::DB_URL, migrations::plugin_migrations())
                 .build()
         )
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            let Some(lock) = instance::try_acquire(&data_dir)? else {
+                instance::report_already_running();
+                std::process::exit(1);
+            };
+            app.manage(lock);
+
+            let dir = app.path().app_config_dir()?;
+            std::fs::create_dir_all(&dir)?;
+            let pool = tauri::async_runtime::block_on(db::connect(&dir.join(db::DB_FILE)))?;
+            let backups = BackupService::new(pool.clone(), data_dir.join(backup::BACKUP_DIR));
+            backup::start(app.handle(), backups.clone());
+            app.manage(backups);
+            app.manage(EventStore::new(pool));
+            app.manage(Replays::default());
+            #[cfg(feature = "tactical")]
+            app.manage(battle::Battles::default());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            event_store::commands::append_events,
+            event_store::commands::read_stream,
+            event_store::commands::read_stream_since,
+            event_store::commands::delete_stream,
+            event_store::commands::stream_version,
+            event_store::commands::save_snapshot,
+            event_store::commands::load_snapshot,
+            event_store::commands::load_stream_state,
+            event_store::commands::delete_snapshot,
+            event_store::commands::invalidate_snapshots,
+            event_store::commands::save_game,
+            event_store::commands::list_save_games,
+            event_store::commands::load_game,
+            event_store::commands::delete_save_game,
+            event_store::commands::fork_stream,
+            event_store::commands::save_tree,
+            event_store::commands::verify_stream,
+            event_store::commands::check_store,
+            event_store::commands::query_events,
+            event_store::commands::undo_last_command,
+            event_store::commands::profile_settings,
+            event_store::commands::update_profile_settings,
+            event_store::commands::create_profile,
+            event_store::commands::rename_profile,
+            event_store::commands::list_profiles,
+            event_store::commands::select_profile,
+            event_store::commands::selected_profile,
+            event_store::commands::delete_profile,
+            event_store::commands::import_browser_database,
+            event_store::commands::export_save_archive,
+            event_store::commands::import_save_archive,
+            event_store::commands::seed_stream_rng,
+            event_store::commands::roll_d20,
+            event_store::commands::shuffle,
+            backup::commands::list_backups,
+            backup::commands::restore_backup,
+            backup::commands::backup_settings,
+            backup::commands::update_backup_settings,
+            replay::commands::open_replay,
+            replay::commands::replay_status,
+            replay::commands::play_replay,
+            replay::commands::pause_replay,
+            replay::commands::seek_replay,
+            replay::commands::set_replay_speed,
+            replay::commands::close_replay,
+            #[cfg(feature = "tactical")]
+            battle::commands::start_tactical_battle,
+            #[cfg(feature = "tactical")]
+            battle::commands::play_card,
+            #[cfg(feature = "tactical")]
+            battle::commands::activate_ability,
+            #[cfg(feature = "tactical")]
+            battle::commands::end_turn,
+            #[cfg(feature = "tactical")]
+            battle::commands::close_tactical_battle,
+            #[cfg(feature = "tactical")]
+            battle::commands::choose_opponent_orders,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,286 @@
+//! Schema migrations for `game.db`, discovered from `src-tauri/migrations`.
+//!
+//! Every file there is named `<version>_<description>.sql`, e.g.
+//! `007_profiles.sql`. The build script collects them in version order, and
+//! the versions must run 1, 2, 3, ... without gaps or the crate does not
+//! compile. Adding a migration is adding a file, plus a fixture in the tests
+//! below showing that existing data survives it.
+//!
+//! The same list is handed to `tauri_plugin_sql`, so the webview and the
+//! native store agree on which versions have been applied.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use sqlx::error::BoxDynError;
+use sqlx::migrate::{Migration as SqlxMigration, MigrationSource, MigrationType, Migrator};
+use sqlx::sqlite::SqlitePool;
+use tauri_plugin_sql::{Migration, MigrationKind};
+
+use crate::error::Result;
+
+#[derive(Debug)]
+pub struct SqlMigration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, in version order.
+pub const MIGRATIONS: &[SqlMigration] = include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+const _: () = assert!(
+    is_contiguous(MIGRATIONS),
+    "migration versions in src-tauri/migrations must run 1, 2, 3, ... without gaps"
+);
+
+const fn is_contiguous(migrations: &[SqlMigration]) -> bool {
+    let mut index = 0;
+    while index < migrations.len() {
+        if migrations[index].version != index as i64 + 1 {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
+/// Migrations in the shape expected by `tauri_plugin_sql`.
+pub fn plugin_migrations() -> Vec<Migration> {
+    MIGRATIONS
+        .iter()
+        .map(|migration| Migration {
+            version: migration.version,
+            description: migration.description,
+            sql: migration.sql,
+            kind: MigrationKind::Up,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct MigrationList(&'static [SqlMigration]);
+
+impl MigrationSource<'static> for MigrationList {
+    fn resolve(
+        self,
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<Vec<SqlxMigration>, BoxDynError>> + Send>>
+    {
+        Box::pin(async move {
+            // Mirror the plugin's conversion exactly so checksums recorded by
+            // either side validate on the other.
+            Ok(self
+                .0
+                .iter()
+                .map(|migration| {
+                    SqlxMigration::new(
+                        migration.version,
+                        migration.description.into(),
+                        MigrationType::ReversibleUp,
+                        migration.sql.into(),
+                        false,
+                    )
+                })
+                .collect())
+        })
+    }
+}
+
+/// Applies any pending migrations to `pool`.
+pub async fn migrate(pool: &SqlitePool) -> Result<()> {
+    migrate_to(pool, MIGRATIONS.len()).await
+}
+
+/// Applies the pending migrations up to and including `version`.
+async fn migrate_to(pool: &SqlitePool, version: usize) -> Result<()> {
+    Migrator::new(MigrationList(&MIGRATIONS[..version]))
+        .await?
+        .run(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    /// Data written at `version - 1`, and what must read back after
+    /// `version` is applied.
+    struct Fixture {
+        version: i64,
+        seed: &'static str,
+        /// `(query, expected)`, where the query yields a single value.
+        checks: &'static [(&'static str, &'static str)],
+    }
+
+    const EVENT: &str = "INSERT INTO events (event_id, stream_id, event_type, event_data, sequence)
+         VALUES ('e1', 'player-1', 'BOUNTY_MODIFIED', '{\"amount\":5}', 1);";
+
+    const FIXTURES: &[Fixture] = &[
+        Fixture {
+            version: 1,
+            seed: "",
+            checks: &[(
+                "SELECT group_concat(name) FROM (
+                     SELECT name FROM sqlite_master WHERE type = 'table'
+                     AND name IN ('events', 'snapshots', 'save_games') ORDER BY name)",
+                "events,save_games,snapshots",
+            )],
+        },
+        Fixture {
+            version: 2,
+            seed: "INSERT INTO snapshots (stream_id, sequence, state_data)
+                   VALUES ('player-1', 1, '{\"bounty\":5}');",
+            checks: &[(
+                "SELECT state_data || '@' || schema_version FROM snapshots",
+                "{\"bounty\":5}@1",
+            )],
+        },
+        Fixture {
+            version: 3,
+            seed: EVENT,
+            checks: &[
+                ("SELECT event_data FROM events", "{\"amount\":5}"),
+                ("SELECT COUNT(*) FROM stream_lineage", "0"),
+            ],
+        },
+        Fixture {
+            version: 4,
+            seed: EVENT,
+            checks: &[(
+                "SELECT event_id || ':' || coalesce(hash, 'unhashed') FROM events",
+                "e1:unhashed",
+            )],
+        },
+        Fixture {
+            version: 5,
+            seed: EVENT,
+            checks: &[
+                ("SELECT COUNT(*) FROM events", "1"),
+                ("SELECT COUNT(*) FROM quarantine", "0"),
+            ],
+        },
+        Fixture {
+            version: 6,
+            seed: EVENT,
+            checks: &[
+                ("SELECT COUNT(*) FROM events", "1"),
+                ("SELECT COUNT(*) FROM profile_settings", "0"),
+            ],
+        },
+        Fixture {
+            version: 7,
+            seed: "INSERT INTO save_games (save_name, player_id, last_event_id, preview_data)
+                   VALUES ('autosave', '1', 'e1', '{\"bounty\":5}');
+                   INSERT INTO profile_settings (profile_id, ironman) VALUES ('1', 1);",
+            checks: &[
+                (
+                    "SELECT profile_id || '/' || save_name || '/' || last_event_id FROM save_games",
+                    "1/autosave/e1",
+                ),
+                ("SELECT preview_data FROM save_games", "{\"bounty\":5}"),
+                ("SELECT name FROM profiles WHERE selected = 1", "Player 1"),
+                ("SELECT ironman FROM profile_settings", "1"),
+            ],
+        },
+        Fixture {
+            version: 8,
+            seed: "INSERT INTO save_games (profile_id, save_name, player_id, last_event_id, preview_data)
+                   VALUES ('1', 'autosave', '1', 'e1', '{}');",
+            checks: &[
+                ("SELECT COUNT(*) FROM save_games", "1"),
+                ("SELECT retention FROM backup_settings", "10"),
+            ],
+        },
+        Fixture {
+            version: 9,
+            seed: "INSERT INTO backup_settings (id, retention) VALUES (1, 3)
+                   ON CONFLICT (id) DO UPDATE SET retention = 3;",
+            checks: &[
+                ("SELECT retention FROM backup_settings", "3"),
+                ("SELECT COUNT(*) FROM stream_rng", "0"),
+            ],
+        },
+        Fixture {
+            version: 10,
+            seed: EVENT,
+            checks: &[(
+                "SELECT stream_id || ':' || last_sequence FROM chain_legacy",
+                "player-1:1",
+            )],
+        },
+        Fixture {
+            version: 11,
+            seed: "INSERT INTO events (event_id, stream_id, event_type, event_data, sequence)
+                   VALUES ('e1', 'player-1', 'BOUNTY_MODIFIED', '{}', 1),
+                          ('e2', 'player-1700000000000', 'BOUNTY_MODIFIED', '{}', 1);
+                   INSERT INTO stream_lineage (stream_id, parent_stream_id, forked_at_sequence)
+                   VALUES ('player-fork-1', 'player-1700000000000', 1);
+                   INSERT INTO save_games (profile_id, save_name, player_id, last_event_id, preview_data)
+                   VALUES ('1700000000000', 'autosave', '1700000000000', 'e2', '{}');",
+            checks: &[
+                (
+                    "SELECT group_concat(stream_id || '=' || profile_id) FROM (
+                         SELECT * FROM stream_profiles ORDER BY stream_id)",
+                    "player-1=1,player-1700000000000=1,player-fork-1=1",
+                ),
+                ("SELECT profile_id FROM save_games", "1"),
+            ],
+        },
+        Fixture {
+            version: 12,
+            seed: "INSERT INTO events (event_id, stream_id, event_type, event_data, sequence)
+                   VALUES ('e1', 'player-1', 'BOUNTY_MODIFIED', 'not json', 1);",
+            checks: &[(
+                "SELECT count(*) FROM sqlite_master WHERE name = 'idx_events_battle'",
+                "1",
+            )],
+        },
+    ];
+
+    #[test]
+    fn versions_must_be_contiguous_from_one() {
+        let migration = |version| SqlMigration {
+            version,
+            description: "",
+            sql: "",
+        };
+        assert!(is_contiguous(&[migration(1), migration(2), migration(3)]));
+        assert!(!is_contiguous(&[migration(1), migration(3)]));
+        assert!(!is_contiguous(&[migration(1), migration(1)]));
+        assert!(!is_contiguous(&[migration(2)]));
+    }
+
+    #[test]
+    fn every_migration_has_a_fixture() {
+        let fixtures: Vec<_> = FIXTURES.iter().map(|fixture| fixture.version).collect();
+        let migrations: Vec<_> = MIGRATIONS
+            .iter()
+            .map(|migration| migration.version)
+            .collect();
+        assert_eq!(fixtures, migrations);
+    }
+
+    #[test]
+    fn data_survives_each_migration() {
+        tauri::async_runtime::block_on(async {
+            for fixture in FIXTURES {
+                let version = fixture.version as usize;
+                let pool = db::open_in_memory().await.unwrap();
+                migrate_to(&pool, version - 1).await.unwrap();
+                sqlx::raw_sql(fixture.seed).execute(&pool).await.unwrap();
+                migrate_to(&pool, version).await.unwrap();
+
+                for (query, expected) in fixture.checks {
+                    let actual: String =
+                        sqlx::query_scalar(&format!("SELECT CAST(({query}) AS TEXT)"))
+                            .fetch_one(&pool)
+                            .await
+                            .unwrap();
+                    assert_eq!(actual, *expected, "migration {version}: {query}");
+                }
+            }
+        });
+    }
+}
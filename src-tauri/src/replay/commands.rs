@@ -0,0 +1,55 @@
+//! Tauri commands exposing [`Replays`] to the webview.
+
+use tauri::State;
+
+use super::{Replay, ReplayStatus, Replays};
+use crate::error::Result;
+use crate::event_store::EventStore;
+
+#[tauri::command]
+pub async fn open_replay(
+    store: State<'_, EventStore>,
+    replays: State<'_, Replays>,
+    battle_id: String,
+) -> Result<ReplayStatus> {
+    let replay = Replay::load(&store, &battle_id).await?;
+    Ok(replays.insert(replay))
+}
+
+#[tauri::command]
+pub fn replay_status(replays: State<'_, Replays>, battle_id: String) -> Result<ReplayStatus> {
+    replays.update(&battle_id, |_, _| {})
+}
+
+#[tauri::command]
+pub fn play_replay(replays: State<'_, Replays>, battle_id: String) -> Result<ReplayStatus> {
+    replays.update(&battle_id, Replay::play)
+}
+
+#[tauri::command]
+pub fn pause_replay(replays: State<'_, Replays>, battle_id: String) -> Result<ReplayStatus> {
+    replays.update(&battle_id, Replay::pause)
+}
+
+#[tauri::command]
+pub fn seek_replay(
+    replays: State<'_, Replays>,
+    battle_id: String,
+    frame_index: usize,
+) -> Result<ReplayStatus> {
+    replays.update(&battle_id, |replay, now| replay.seek(frame_index, now))
+}
+
+#[tauri::command]
+pub fn set_replay_speed(
+    replays: State<'_, Replays>,
+    battle_id: String,
+    speed: f64,
+) -> Result<ReplayStatus> {
+    replays.update(&battle_id, |replay, now| replay.set_speed(speed, now))
+}
+
+#[tauri::command]
+pub fn close_replay(replays: State<'_, Replays>, battle_id: String) {
+    replays.close(&battle_id);
+}
@@ -0,0 +1,376 @@
+//! Step-through playback of finished battles.
+//!
+//! A replay is built from the events `generateBattleEvents` in `combat.ts`
+//! recorded for one `battleId`, one frame per event. Each frame is shown for
+//! a fixed time depending on its event type, scaled by the playback speed.
+//! The player keeps no timer of its own: the current frame follows from the
+//! time elapsed since playback last started, so the webview polls
+//! [`replay_status`](commands::replay_status) at whatever rate it animates.
+
+pub mod commands;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::event_store::EventStore;
+use crate::game::GameEvent;
+
+/// Slowest and fastest playback, as multiples of normal speed.
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 8.0;
+
+/// How long each kind of frame stays on screen at normal speed, in
+/// milliseconds. Events of other types are not part of a replay.
+const FRAME_DURATIONS: &[(&str, u64)] = &[
+    ("BATTLE_STARTED", 1500),
+    ("ROUND_STARTED", 800),
+    ("CARDS_REVEALED", 1500),
+    ("INITIATIVE_RESOLVED", 1000),
+    ("ATTACK_ROLLED", 1500),
+    ("ROUND_RESOLVED", 1500),
+    ("BATTLE_RESOLVED", 2500),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayFrame {
+    /// Sequence of the frame's event in its stream.
+    pub sequence: i64,
+    /// When the frame starts, in milliseconds from the start at normal speed.
+    pub at_ms: u64,
+    pub event: GameEvent,
+}
+
+/// Where playback of a replay stands.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayStatus {
+    pub battle_id: String,
+    pub stream_id: String,
+    pub frame_index: usize,
+    pub frame_count: usize,
+    pub frame: ReplayFrame,
+    pub playing: bool,
+    pub speed: f64,
+    pub position_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// One battle's frames and its playback state.
+#[derive(Debug)]
+pub struct Replay {
+    battle_id: String,
+    stream_id: String,
+    /// Never empty.
+    frames: Vec<ReplayFrame>,
+    /// Playback position when it last started, paused or changed speed.
+    position_ms: u64,
+    /// When playback last started, or `None` while paused.
+    playing_since: Option<Instant>,
+    speed: f64,
+}
+
+impl Replay {
+    /// Loads the frames of `battle_id`, paused on the first one.
+    pub async fn load(store: &EventStore, battle_id: &str) -> Result<Self> {
+        let mut frames = Vec::new();
+        let mut stream_id = String::new();
+        let mut at_ms = 0;
+        for event in store.battle_events(battle_id).await? {
+            let Some(&(_, duration)) = FRAME_DURATIONS
+                .iter()
+                .find(|(event_type, _)| *event_type == event.event_type)
+            else {
+                continue;
+            };
+            let data = serde_json::from_str(&event.event_data)?;
+            frames.push(ReplayFrame {
+                sequence: event.sequence,
+                at_ms,
+                event: GameEvent::from_parts(&event.event_type, &data)?,
+            });
+            stream_id = event.stream_id;
+            at_ms += duration;
+        }
+        if frames.is_empty() {
+            return Err(Error::ReplayNotFound(battle_id.to_string()));
+        }
+        Ok(Self {
+            battle_id: battle_id.to_string(),
+            stream_id,
+            frames,
+            position_ms: 0,
+            playing_since: None,
+            speed: 1.0,
+        })
+    }
+
+    /// Start of the last frame; playback stops there.
+    fn duration_ms(&self) -> u64 {
+        self.frames[self.frames.len() - 1].at_ms
+    }
+
+    fn position_at(&self, now: Instant) -> u64 {
+        let elapsed = self.playing_since.map_or(0.0, |since| {
+            now.saturating_duration_since(since).as_millis() as f64 * self.speed
+        });
+        (self.position_ms + elapsed as u64).min(self.duration_ms())
+    }
+
+    /// Fixes the position reached at `now` as the new starting point.
+    fn rebase(&mut self, now: Instant) {
+        self.position_ms = self.position_at(now);
+        if self.playing_since.is_some() {
+            self.playing_since = Some(now);
+        }
+    }
+
+    /// Resumes playback, from the start when it had reached the end.
+    pub fn play(&mut self, now: Instant) {
+        self.rebase(now);
+        if self.position_ms >= self.duration_ms() {
+            self.position_ms = 0;
+        }
+        self.playing_since = Some(now);
+    }
+
+    pub fn pause(&mut self, now: Instant) {
+        self.rebase(now);
+        self.playing_since = None;
+    }
+
+    /// Jumps to the start of frame `index`, or of the last frame when past
+    /// the end. Playback keeps playing or stays paused.
+    pub fn seek(&mut self, index: usize, now: Instant) {
+        let index = index.min(self.frames.len() - 1);
+        self.position_ms = self.frames[index].at_ms;
+        if self.playing_since.is_some() {
+            self.playing_since = Some(now);
+        }
+    }
+
+    /// Sets the playback speed, clamped to [`MIN_SPEED`]..=[`MAX_SPEED`].
+    pub fn set_speed(&mut self, speed: f64, now: Instant) {
+        self.rebase(now);
+        if speed.is_finite() {
+            self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        }
+    }
+
+    /// The frame shown at `now`. Playback that reached the last frame is
+    /// paused there.
+    pub fn status(&mut self, now: Instant) -> ReplayStatus {
+        let position_ms = self.position_at(now);
+        if position_ms >= self.duration_ms() {
+            self.pause(now);
+        }
+        let frame_index = self
+            .frames
+            .partition_point(|frame| frame.at_ms <= position_ms)
+            - 1;
+        ReplayStatus {
+            battle_id: self.battle_id.clone(),
+            stream_id: self.stream_id.clone(),
+            frame_index,
+            frame_count: self.frames.len(),
+            frame: self.frames[frame_index].clone(),
+            playing: self.playing_since.is_some(),
+            speed: self.speed,
+            position_ms,
+            duration_ms: self.duration_ms(),
+        }
+    }
+}
+
+/// The open replays by battle id, managed as Tauri state.
+#[derive(Debug, Default)]
+pub struct Replays {
+    open: Mutex<HashMap<String, Replay>>,
+}
+
+impl Replays {
+    /// Keeps `replay` open, replacing an open replay of the same battle.
+    pub fn insert(&self, mut replay: Replay) -> ReplayStatus {
+        let status = replay.status(Instant::now());
+        self.open
+            .lock()
+            .unwrap()
+            .insert(replay.battle_id.clone(), replay);
+        status
+    }
+
+    /// Runs `f` on the open replay of `battle_id` and returns its status
+    /// afterwards.
+    pub fn update(
+        &self,
+        battle_id: &str,
+        f: impl FnOnce(&mut Replay, Instant),
+    ) -> Result<ReplayStatus> {
+        let mut open = self.open.lock().unwrap();
+        let replay = open
+            .get_mut(battle_id)
+            .ok_or_else(|| Error::ReplayNotFound(battle_id.to_string()))?;
+        let now = Instant::now();
+        f(replay, now);
+        Ok(replay.status(now))
+    }
+
+    pub fn close(&self, battle_id: &str) {
+        self.open.lock().unwrap().remove(battle_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::event_store::NewEvent;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    fn event(event_type: &str, data: Value) -> NewEvent {
+        let mut data = data;
+        data["timestamp"] = json!("2025-01-01T00:00:00.000Z");
+        NewEvent {
+            event_type: event_type.to_string(),
+            data,
+        }
+    }
+
+    fn battle(battle_id: &str) -> Vec<NewEvent> {
+        vec![
+            event(
+                "BATTLE_STARTED",
+                json!({ "battleId": battle_id, "playerCardIds": [], "opponentCards": [] }),
+            ),
+            event(
+                "CARD_SELECTED",
+                json!({ "battleId": battle_id, "cardId": "c1" }),
+            ),
+            event(
+                "ROUND_STARTED",
+                json!({ "battleId": battle_id, "roundNumber": 1 }),
+            ),
+            event(
+                "INITIATIVE_RESOLVED",
+                json!({
+                    "battleId": battle_id,
+                    "roundNumber": 1,
+                    "firstStriker": "player",
+                    "playerAgility": 3,
+                    "opponentAgility": 1,
+                }),
+            ),
+            event(
+                "ATTACK_ROLLED",
+                json!({
+                    "battleId": battle_id,
+                    "roundNumber": 1,
+                    "attacker": "player",
+                    "roll": 14,
+                    "modifier": 2,
+                    "total": 16,
+                    "targetArmor": 3,
+                    "targetNumber": 13,
+                    "hit": true,
+                }),
+            ),
+        ]
+    }
+
+    async fn replay() -> Replay {
+        let store = EventStore::new(db::connect_in_memory().await.unwrap());
+        store
+            .append_events("player-1", None, None, &battle("b1"))
+            .await
+            .unwrap();
+        store
+            .append_events("player-1", None, None, &battle("b2"))
+            .await
+            .unwrap();
+        store
+            .fork_stream("player-1", 10, "player-1-fork")
+            .await
+            .unwrap();
+        Replay::load(&store, "b2").await.unwrap()
+    }
+
+    #[test]
+    fn loads_the_battles_frames_from_one_stream() {
+        tauri::async_runtime::block_on(async {
+            let mut replay = replay().await;
+            let status = replay.status(Instant::now());
+            assert_eq!(status.stream_id, "player-1");
+            assert_eq!(status.frame_count, 4);
+            assert_eq!(status.frame_index, 0);
+            assert!(!status.playing);
+            let sequences: Vec<_> = replay.frames.iter().map(|f| f.sequence).collect();
+            assert_eq!(sequences, [6, 8, 9, 10]);
+            assert_eq!(status.duration_ms, 1500 + 800 + 1000);
+            assert!(matches!(
+                &replay.frames[3].event,
+                GameEvent::AttackRolled {
+                    roll: 14,
+                    hit: true,
+                    ..
+                }
+            ));
+
+            let store = EventStore::new(db::connect_in_memory().await.unwrap());
+            let err = Replay::load(&store, "b1").await.unwrap_err();
+            assert!(matches!(err, Error::ReplayNotFound(id) if id == "b1"));
+        });
+    }
+
+    #[test]
+    fn plays_at_speed_and_stops_at_the_end() {
+        tauri::async_runtime::block_on(async {
+            let mut replay = replay().await;
+            let start = Instant::now();
+            let at = |ms| start + Duration::from_millis(ms);
+
+            replay.play(start);
+            assert_eq!(replay.status(at(1499)).frame_index, 0);
+            assert_eq!(replay.status(at(1500)).frame_index, 1);
+
+            replay.set_speed(2.0, at(1500));
+            assert_eq!(replay.status(at(1900)).frame_index, 2);
+
+            replay.pause(at(1900));
+            assert_eq!(replay.status(at(60_000)).frame_index, 2);
+
+            replay.play(at(60_000));
+            let end = replay.status(at(61_000));
+            assert_eq!(end.frame_index, 3);
+            assert!(!end.playing);
+            assert_eq!(end.position_ms, end.duration_ms);
+
+            // Playing again from the end restarts the battle.
+            replay.play(at(62_000));
+            assert_eq!(replay.status(at(62_000)).frame_index, 0);
+
+            replay.set_speed(100.0, at(62_000));
+            assert_eq!(replay.status(at(62_000)).speed, MAX_SPEED);
+        });
+    }
+
+    #[test]
+    fn seeks_to_a_frame() {
+        tauri::async_runtime::block_on(async {
+            let mut replay = replay().await;
+            let now = Instant::now();
+            replay.seek(2, now);
+            let status = replay.status(now);
+            assert_eq!(status.frame_index, 2);
+            assert_eq!(status.position_ms, 2300);
+            assert!(!status.playing);
+
+            replay.seek(99, now);
+            assert_eq!(replay.status(now).frame_index, 3);
+        });
+    }
+}